@@ -0,0 +1,368 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A clause `l[0] \/ ... \/ l[n-1]` over literals which are boolean variables or their negations.
+//!
+//! It is propagated with two watched literals as in SAT solvers: the clause can only become unit or false when one of the two watched literals is false.
+//! The propagator is woken up on every literal since the dependencies are static, but it only looks at the watched literals unless one of them is false, in which case it searches for a replacement among the other literals.
+//! Contrarily to `Disjunction`, which calls `is_subsumed` on every formula at each wake-up, most wake-ups are done in constant time.
+//! The watched literals are not restored on backtracking: un-assigning variables cannot make a watched literal false.
+
+use trilean::SKleene;
+use trilean::SKleene::*;
+use kernel::*;
+use model::*;
+use logic::{Boolean, BooleanNeg, NotFormula, Conjunction};
+use propagation::*;
+use propagation::events::*;
+use gcollections::kind::*;
+use std::fmt::{Debug, Formatter, Result};
+use concept::*;
+
+/// A boolean variable or its negation.
+pub enum Literal<VStore> {
+  Positive(Boolean<VStore>),
+  Negative(BooleanNeg<VStore>)
+}
+
+impl<VStore> From<Boolean<VStore>> for Literal<VStore>
+{
+  fn from(b: Boolean<VStore>) -> Self {
+    Literal::Positive(b)
+  }
+}
+
+impl<VStore> From<BooleanNeg<VStore>> for Literal<VStore>
+{
+  fn from(b: BooleanNeg<VStore>) -> Self {
+    Literal::Negative(b)
+  }
+}
+
+impl<VStore> Debug for Literal<VStore>
+{
+  fn fmt(&self, fmt: &mut Formatter) -> Result {
+    match *self {
+      Literal::Positive(ref b) => b.fmt(fmt),
+      Literal::Negative(ref b) => b.fmt(fmt)
+    }
+  }
+}
+
+impl<VStore> Clone for Literal<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    match *self {
+      Literal::Positive(ref b) => Literal::Positive(b.clone()),
+      Literal::Negative(ref b) => Literal::Negative(b.clone())
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Literal<VStore>
+{
+  fn display(&self, model: &Model) {
+    match *self {
+      Literal::Positive(ref b) => b.display(model),
+      Literal::Negative(ref b) => b.display(model)
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Literal<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    match *self {
+      Literal::Positive(ref b) => b.not(),
+      Literal::Negative(ref b) => b.not()
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Literal<VStore> where
+  VStore: VStoreConcept<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn value(&self, store: &VStore) -> SKleene {
+    match *self {
+      Literal::Positive(ref b) => b.is_subsumed(store),
+      Literal::Negative(ref b) => b.is_subsumed(store)
+    }
+  }
+
+  /// Makes the literal true, returns `false` if it is already false.
+  fn satisfy(&mut self, store: &mut VStore) -> bool {
+    match *self {
+      Literal::Positive(ref mut b) => b.propagate(store),
+      Literal::Negative(ref mut b) => b.propagate(store)
+    }
+  }
+}
+
+impl<VStore> Literal<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    match *self {
+      Literal::Positive(ref b) => PropagatorDependencies::dependencies(b),
+      Literal::Negative(ref b) => PropagatorDependencies::dependencies(b)
+    }
+  }
+}
+
+/// Posts the formula in conjunctive normal form `cnf` with one `Clause` per element.
+pub fn join_cnf<VStore, CStore, Domain, Bound>(cstore: &mut CStore, cnf: Vec<Vec<Literal<VStore>>>) where
+ VStore: VStoreConcept<Item=Domain> + 'static,
+ Domain: IntDomain<Item=Bound> + 'static,
+ Bound: IntBound + 'static,
+ CStore: IntCStore<VStore> + 'static
+{
+  for lits in cnf {
+    cstore.alloc(Box::new(Clause::new(lits)));
+  }
+}
+
+pub struct Clause<VStore> {
+  lits: Vec<Literal<VStore>>,
+  /// The indexes of the two watched literals, they are distinct if there are at least two literals.
+  watched: [usize; 2]
+}
+
+impl<VStore> Clause<VStore>
+{
+  pub fn new(lits: Vec<Literal<VStore>>) -> Self {
+    Clause {
+      watched: [0, if lits.len() > 1 { 1 } else { 0 }],
+      lits: lits
+    }
+  }
+}
+
+impl<VStore> Debug for Clause<VStore>
+{
+  fn fmt(&self, fmt: &mut Formatter) -> Result {
+    fmt.debug_struct("Clause")
+      .field("lits", &self.lits)
+      .field("watched", &self.watched)
+      .finish()
+  }
+}
+
+impl<VStore> Clone for Clause<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Clause {
+      lits: self.lits.clone(),
+      watched: self.watched
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Clause<VStore>
+{
+  fn display(&self, model: &Model) {
+    if self.lits.is_empty() {
+      print!("false");
+    }
+    for (i, l) in self.lits.iter().enumerate() {
+      if i > 0 {
+        print!(" \\/ ");
+      }
+      l.display(model);
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Clause<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// Apply De Morgan's laws.
+  fn not(&self) -> Formula<VStore> {
+    let fs = self.lits.iter().map(|l| l.not()).collect();
+    Box::new(Conjunction::new(fs))
+  }
+}
+
+impl<VStore, Domain, Bound> Clause<VStore> where
+  VStore: VStoreConcept<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// Replaces the watched literal `w` by a literal that is not false and not watched, if it is false.
+  fn update_watch(&mut self, store: &VStore, w: usize) {
+    if self.lits[self.watched[w]].value(store) == False {
+      let other = self.watched[1 - w];
+      let n = self.lits.len();
+      let start = self.watched[w];
+      if let Some(i) = (1..n).map(|k| (start + k) % n)
+        .find(|&i| i != other && self.lits[i].value(store) != False)
+      {
+        self.watched[w] = i;
+      }
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Clause<VStore> where
+  VStore: VStoreConcept<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: every literal is false.
+    // True: one of the literals is true.
+    // Unknown: Everything else.
+    let mut all_false = true;
+    for l in &self.lits {
+      match l.value(store) {
+        True => return True,
+        Unknown => all_false = false,
+        False => ()
+      }
+    }
+    if all_false { False } else { Unknown }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Clause<VStore> where
+  VStore: VStoreConcept<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    match self.lits.len() {
+      0 => return false,
+      1 => return self.lits[0].satisfy(store),
+      _ => ()
+    }
+    self.update_watch(store, 0);
+    self.update_watch(store, 1);
+    let [w0, w1] = self.watched;
+    match (self.lits[w0].value(store), self.lits[w1].value(store)) {
+      (False, False) => false,
+      (False, Unknown) => self.lits[w1].satisfy(store),
+      (Unknown, False) => self.lits[w0].satisfy(store),
+      _ => true
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Clause<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.lits.iter()
+      .flat_map(|l| l.dependencies())
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use variable::VStoreFD;
+  use variable::ops::DrainDelta;
+  use term::ops::*;
+  use propagators::test::*;
+  use gcollections::ops::*;
+
+  /// The literal `i` is negated if `negated[i]`, and its variable is assigned if `values[i]` is not `None`.
+  fn clause_test_one(test_num: u32, values: Vec<Option<i32>>, negated: Vec<bool>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    let mut store = VStoreFD::empty();
+    let mut lits = vec![];
+    for (v, neg) in values.into_iter().zip(negated) {
+      let mut b = Boolean::new(&mut store);
+      if let Some(v) = v {
+        assert!(b.update(&mut store, (v,v).to_interval()));
+      }
+      lits.push(if neg { BooleanNeg::new(b).into() } else { b.into() });
+    }
+    let assigned: Vec<_> = store.drain_delta().collect();
+    assert!(assigned.iter().all(|&(_, e)| e == Assignment));
+    test_propagation(test_num, Clause::new(lits), &mut store, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn clause_test() {
+    let f = Some(0);
+    let t = Some(1);
+    let u = None;
+
+    clause_test_one(1, vec![u, u, u], vec![false, false, false], Unknown, Unknown, vec![], true);
+    clause_test_one(2, vec![f, f, u], vec![false, false, false], Unknown, True, vec![(2, Assignment)], true);
+    clause_test_one(3, vec![f, f, f], vec![false, false, false], False, False, vec![], false);
+    clause_test_one(4, vec![f, u, t], vec![false, false, false], True, True, vec![], true);
+    // `b0 \/ not b1 \/ b2`
+    clause_test_one(5, vec![f, u, f], vec![false, true, false], Unknown, True, vec![(1, Assignment)], true);
+    clause_test_one(6, vec![f, t, f], vec![false, true, false], False, False, vec![], false);
+    clause_test_one(7, vec![t, f], vec![true, true], True, True, vec![], true);
+    clause_test_one(8, vec![u], vec![true], Unknown, True, vec![(0, Assignment)], true);
+    clause_test_one(9, vec![], vec![], False, False, vec![], false);
+  }
+
+  #[test]
+  fn watched_literals_test() {
+    let mut store = VStoreFD::empty();
+    let mut b: Vec<_> = (0..4).map(|_| Boolean::new(&mut store)).collect();
+    let mut clause = Clause::new(b.iter().cloned().map(Literal::from).collect());
+    let initial = store.clone();
+    let zero = (0,0).to_interval();
+    // The watches move to `b2` and `b3` as `b0` and `b1` become false.
+    for x in b.iter_mut().take(3) {
+      assert!(clause.propagate(&mut store));
+      assert!(x.update(&mut store, zero));
+    }
+    assert!(clause.propagate(&mut store));
+    assert_eq!(b[3].read(&store), (1,1).to_interval());
+    // The watches are still valid on the store before the assignments, without being restored.
+    let mut store = initial;
+    for x in b.iter_mut().skip(1) {
+      assert!(x.update(&mut store, zero));
+    }
+    assert!(clause.propagate(&mut store));
+    assert_eq!(b[0].read(&store), (1,1).to_interval());
+  }
+
+  #[test]
+  fn join_cnf_test() {
+    let mut vstore = VStoreFD::empty();
+    let mut cstore: CStoreFD<VStoreFD> = CStoreFD::empty();
+    let mut b: Vec<_> = (0..3).map(|_| Boolean::new(&mut vstore)).collect();
+    let lit = |b: &Boolean<VStoreFD>| Literal::from(b.clone());
+    let neg = |b: &Boolean<VStoreFD>| Literal::from(BooleanNeg::new(b.clone()));
+    // `(b0 \/ b1) /\ (not b1 \/ b2) /\ (not b0 \/ b2)`
+    join_cnf(&mut cstore, vec![
+      vec![lit(&b[0]), lit(&b[1])],
+      vec![neg(&b[1]), lit(&b[2])],
+      vec![neg(&b[0]), lit(&b[2])]]);
+    assert_eq!(cstore.size(), 3);
+    assert_eq!(cstore.consistency(&mut vstore), Unknown);
+    assert!(b[2].update(&mut vstore, (0,0).to_interval()));
+    assert_eq!(cstore.consistency(&mut vstore), False);
+  }
+}
@@ -15,7 +15,7 @@
 use trilean::SKleene;
 use kernel::*;
 use model::*;
-use logic::{NotFormula, Disjunction};
+use logic::{NotFormula, Disjunction, save_formulas_state, restore_formulas_state};
 use propagation::events::*;
 use propagation::*;
 use gcollections::kind::*;
@@ -104,6 +104,14 @@ impl<VStore> Propagator<VStore> for Conjunction<VStore>
     }
     true
   }
+
+  fn save_state(&self) -> Option<PropagatorState> {
+    save_formulas_state(&self.fs)
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    restore_formulas_state(&mut self.fs, state)
+  }
 }
 
 impl<VStore> PropagatorDependencies<FDEvent> for Conjunction<VStore>
@@ -15,7 +15,7 @@
 use trilean::SKleene;
 use kernel::*;
 use model::*;
-use logic::{NotFormula, Conjunction};
+use logic::{NotFormula, Conjunction, save_formulas_state, restore_formulas_state};
 use propagation::events::*;
 use propagation::*;
 use gcollections::kind::*;
@@ -115,6 +115,14 @@ impl<VStore> Propagator<VStore> for Disjunction<VStore>
     }
     else { true }
   }
+
+  fn save_state(&self) -> Option<PropagatorState> {
+    save_formulas_state(&self.fs)
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    restore_formulas_state(&mut self.fs, state)
+  }
 }
 
 impl<VStore> PropagatorDependencies<FDEvent> for Disjunction<VStore>
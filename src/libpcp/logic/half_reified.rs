@@ -0,0 +1,190 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Half-reification `b => p` of a propagator `p` by a boolean variable `b`.
+//! `p` is propagated once `b` is true, and `b` is set to false as soon as `p` is disentailed.
+//! Contrarily to `Reified`, the negation of `p` is never propagated.
+
+use trilean::SKleene;
+use trilean::SKleene::*;
+use kernel::*;
+use model::*;
+use logic::{Boolean, NotFormula, Conjunction};
+use propagation::*;
+use propagation::events::*;
+use term::ops::*;
+use gcollections::kind::*;
+use std::fmt::{Debug, Formatter, Result};
+use concept::*;
+
+pub struct HalfReified<VStore, P> {
+  b: Boolean<VStore>,
+  p: P
+}
+
+impl<VStore, P> HalfReified<VStore, P>
+{
+  pub fn new(b: Boolean<VStore>, p: P) -> Self {
+    HalfReified {
+      b: b,
+      p: p
+    }
+  }
+}
+
+impl<VStore, P> Debug for HalfReified<VStore, P> where
+ P: Debug
+{
+  fn fmt(&self, fmt: &mut Formatter) -> Result {
+    fmt.debug_struct("HalfReified")
+      .field("b", &self.b)
+      .field("p", &self.p)
+      .finish()
+  }
+}
+
+impl<VStore, P> Clone for HalfReified<VStore, P> where
+ VStore: Collection,
+ P: Clone
+{
+  fn clone(&self) -> Self {
+    HalfReified {
+      b: self.b.clone(),
+      p: self.p.clone()
+    }
+  }
+}
+
+impl<VStore, P> DisplayStateful<Model> for HalfReified<VStore, P> where
+ P: DisplayStateful<Model>
+{
+  fn display(&self, model: &Model) {
+    self.b.display(model);
+    print!(" => (");
+    self.p.display(model);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound, P> NotFormula<VStore> for HalfReified<VStore, P> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+  P: NotFormula<VStore>
+{
+  /// `not(b => p)` is `b /\ not(p)`.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Conjunction::new(vec![Box::new(self.b.clone()), self.p.not()]))
+  }
+}
+
+impl<VStore, Domain, Bound, P> Subsumption<VStore> for HalfReified<VStore, P> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound,
+  P: Subsumption<VStore>
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    match self.b.is_subsumed(store) {
+      True => self.p.is_subsumed(store),
+      False => True,
+      Unknown =>
+        if self.p.is_subsumed(store) == True { True }
+        else { Unknown }
+    }
+  }
+}
+
+impl<VStore, Domain, Bound, P> Propagator<VStore> for HalfReified<VStore, P> where
+  VStore: VStoreConcept<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound,
+  P: Propagator<VStore> + Subsumption<VStore>
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    match self.b.is_subsumed(store) {
+      True => self.p.propagate(store),
+      False => true,
+      Unknown =>
+        if self.p.is_subsumed(store) == False {
+          self.b.update(store, Domain::singleton(Bound::zero()))
+        }
+        else { true }
+    }
+  }
+
+  fn save_state(&self) -> Option<PropagatorState> {
+    self.p.save_state()
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    self.p.restore_state(state)
+  }
+}
+
+impl<VStore, P> PropagatorDependencies<FDEvent> for HalfReified<VStore, P> where
+ P: PropagatorDependencies<FDEvent>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = PropagatorDependencies::dependencies(&self.b);
+    deps.append(&mut self.p.dependencies());
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use variable::VStoreFD;
+  use variable::store::test::consume_delta;
+  use propagators::XLessY;
+  use propagators::test::*;
+  use gcollections::ops::*;
+
+  #[test]
+  fn half_reified_test() {
+    let dom0_3 = (0,3).to_interval();
+    let dom2_5 = (2,5).to_interval();
+    let dom3_6 = (3,6).to_interval();
+    let dom5_6 = (5,6).to_interval();
+
+    half_reified_test_one(1, None, dom0_3, dom5_6, True, True, vec![], true);
+    // `b` cannot be true since `x < y` is disentailed.
+    half_reified_test_one(2, None, dom5_6, dom0_3, Unknown, True, vec![(0, Assignment)], true);
+    half_reified_test_one(3, None, dom0_3, dom2_5, Unknown, Unknown, vec![], true);
+    half_reified_test_one(4, Some(1), dom3_6, dom2_5, Unknown, Unknown, vec![(1, Bound), (2, Bound)], true);
+    half_reified_test_one(5, Some(1), dom5_6, dom0_3, False, False, vec![], false);
+    half_reified_test_one(6, Some(0), dom5_6, dom0_3, True, True, vec![], true);
+  }
+
+  fn half_reified_test_one(test_num: u32, b_value: Option<i32>, x: Interval<i32>, y: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    let mut store = VStoreFD::empty();
+    let mut b = Boolean::new(&mut store);
+    let x = Box::new(store.alloc(x)) as Var<VStoreFD>;
+    let y = Box::new(store.alloc(y)) as Var<VStoreFD>;
+    if let Some(v) = b_value {
+      assert!(b.update(&mut store, (v,v).to_interval()));
+      consume_delta(&mut store, vec![(0, Assignment)]);
+    }
+    let half_reified = HalfReified::new(b, XLessY::new(x, y));
+    test_propagation(test_num, half_reified, &mut store, before, after, delta_expected, propagate_success);
+  }
+}
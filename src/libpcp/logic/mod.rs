@@ -14,25 +14,36 @@
 
 pub mod boolean;
 pub mod boolean_neg;
+pub mod clause;
 pub mod conjunction;
 pub mod disjunction;
+pub mod half_reified;
+pub mod negation;
 pub mod ops;
+pub mod reified;
 
 pub use logic::boolean::*;
 pub use logic::boolean_neg::*;
+pub use logic::clause::*;
 pub use logic::conjunction::*;
 pub use logic::disjunction::*;
+pub use logic::half_reified::*;
+pub use logic::negation::*;
 pub use logic::ops::*;
+pub use logic::reified::*;
 
 use gcollections::*;
 use concept::*;
+use propagation::PropagatorState;
 
+/// `f => g`, see `HalfReified` when `f` is a boolean variable.
 pub fn implication<VStore>(f: Formula<VStore>, g: Formula<VStore>) -> Formula<VStore> where
  VStore: Collection + 'static
 {
   Box::new(Disjunction::new(vec![f, g.not()]))
 }
 
+/// `f <=> g` as two implications, see `Reified` when `f` is a boolean variable.
 pub fn equivalence<VStore>(f: Formula<VStore>, g: Formula<VStore>) -> Formula<VStore> where
  VStore: Collection + 'static
 {
@@ -41,3 +52,25 @@ pub fn equivalence<VStore>(f: Formula<VStore>, g: Formula<VStore>) -> Formula<VS
     implication(g, f)
   ]))
 }
+
+/// Saves the states of the formulas `fs`, `None` if they are all stateless.
+pub fn save_formulas_state<VStore>(fs: &[Formula<VStore>]) -> Option<PropagatorState> {
+  let states: Vec<Option<PropagatorState>> = fs.iter().map(|f| f.save_state()).collect();
+  if states.iter().all(Option::is_none) {
+    None
+  }
+  else {
+    Some(Box::new(states))
+  }
+}
+
+/// Restores the states of the formulas `fs` saved with `save_formulas_state`.
+pub fn restore_formulas_state<VStore>(fs: &mut [Formula<VStore>], state: PropagatorState) {
+  if let Ok(states) = state.downcast::<Vec<Option<PropagatorState>>>() {
+    for (f, s) in fs.iter_mut().zip(*states) {
+      if let Some(s) = s {
+        f.restore_state(s);
+      }
+    }
+  }
+}
@@ -0,0 +1,104 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Negation of a formula that does not have a dedicated propagator for its negation.
+//! It is check-only: it does not prune the domains but fails as soon as the formula is entailed, so it is exact once its variables are assigned.
+//!
+//! It is the negation of the global constraints whose negation cannot be stated with propagators over their own variables:
+//!
+//! * `Cumulative` and `BinPacking`, whose negations are sums of reified constraints, but `Reified` needs a Boolean variable per reified constraint and `not` cannot allocate variables.
+//! * `Circuit` and `Subcircuit`, whose negations would need a number of propagators exponential in the number of variables.
+//! * `Sort`, whose negation depends on the values of the domains, which `not` cannot read.
+//!
+//! The other propagators only use it when they have too few variables to state their negation, they are then always entailed, e.g. `Increasing` over a single variable.
+
+use trilean::SKleene;
+use trilean::SKleene::*;
+use kernel::*;
+use model::*;
+use logic::NotFormula;
+use propagation::events::*;
+use propagation::*;
+use gcollections::kind::*;
+use std::fmt::{Debug, Formatter, Result};
+use concept::*;
+
+pub struct Negation<VStore> {
+  f: Formula<VStore>
+}
+
+impl<VStore> Negation<VStore>
+{
+  pub fn new(f: Formula<VStore>) -> Self {
+    Negation {
+      f: f
+    }
+  }
+}
+
+impl<VStore> Debug for Negation<VStore>
+{
+  fn fmt(&self, fmt: &mut Formatter) -> Result {
+    fmt.debug_struct("Negation")
+      .field("f", &self.f)
+      .finish()
+  }
+}
+
+impl<VStore> Clone for Negation<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Negation {
+      f: self.f.bclone()
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Negation<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("not(");
+    self.f.display(model);
+    print!(")");
+  }
+}
+
+impl<VStore> NotFormula<VStore> for Negation<VStore>
+{
+  fn not(&self) -> Formula<VStore> {
+    self.f.bclone()
+  }
+}
+
+impl<VStore> Subsumption<VStore> for Negation<VStore>
+{
+  fn is_subsumed(&self, vstore: &VStore) -> SKleene {
+    !self.f.is_subsumed(vstore)
+  }
+}
+
+impl<VStore> Propagator<VStore> for Negation<VStore>
+{
+  fn propagate(&mut self, vstore: &mut VStore) -> bool {
+    self.f.is_subsumed(vstore) != True
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Negation<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    self.f.dependencies()
+  }
+}
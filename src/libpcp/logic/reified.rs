@@ -0,0 +1,237 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reification `b <=> p` of a propagator `p` by a boolean variable `b`.
+//! The value of `b` selects whether `p` or its negation is propagated, and `b` is fixed as soon as `p` is entailed or disentailed.
+//! The negation `not(b <=> p)` is `b <=> not(p)`, it is represented by the same structure with the roles of `p` and `not(p)` swapped.
+
+use trilean::SKleene;
+use trilean::SKleene::*;
+use kernel::*;
+use model::*;
+use logic::{Boolean, NotFormula};
+use propagation::*;
+use propagation::events::*;
+use term::ops::*;
+use gcollections::kind::*;
+use std::fmt::{Debug, Formatter, Result};
+use concept::*;
+
+pub struct Reified<VStore, P> {
+  b: Boolean<VStore>,
+  p: P,
+  not_p: Formula<VStore>,
+  /// `true` for `b <=> p`, `false` for `b <=> not(p)`.
+  positive: bool
+}
+
+impl<VStore, P> Reified<VStore, P> where
+ P: NotFormula<VStore>
+{
+  pub fn new(b: Boolean<VStore>, p: P) -> Self {
+    let not_p = p.not();
+    Reified {
+      b: b,
+      p: p,
+      not_p: not_p,
+      positive: true
+    }
+  }
+}
+
+impl<VStore, P> Reified<VStore, P> where
+ P: Subsumption<VStore>
+{
+  /// Entailment of the reified formula, `p` or `not(p)` depending on the sign.
+  fn formula_subsumed(&self, store: &VStore) -> SKleene {
+    let entailed = self.p.is_subsumed(store);
+    if self.positive { entailed } else { !entailed }
+  }
+}
+
+impl<VStore, P> Reified<VStore, P> where
+ P: Propagator<VStore>
+{
+  /// Propagates the reified formula if `holds`, its negation otherwise.
+  fn propagate_formula(&mut self, store: &mut VStore, holds: bool) -> bool {
+    if holds == self.positive { self.p.propagate(store) }
+    else { self.not_p.propagate(store) }
+  }
+}
+
+impl<VStore, P> Debug for Reified<VStore, P> where
+ P: Debug
+{
+  fn fmt(&self, fmt: &mut Formatter) -> Result {
+    fmt.debug_struct("Reified")
+      .field("b", &self.b)
+      .field("p", &self.p)
+      .field("not_p", &self.not_p)
+      .field("positive", &self.positive)
+      .finish()
+  }
+}
+
+impl<VStore, P> Clone for Reified<VStore, P> where
+ VStore: Collection,
+ P: Clone
+{
+  fn clone(&self) -> Self {
+    Reified {
+      b: self.b.clone(),
+      p: self.p.clone(),
+      not_p: self.not_p.bclone(),
+      positive: self.positive
+    }
+  }
+}
+
+impl<VStore, P> DisplayStateful<Model> for Reified<VStore, P> where
+ P: DisplayStateful<Model>
+{
+  fn display(&self, model: &Model) {
+    self.b.display(model);
+    print!(" <=> (");
+    if self.positive { self.p.display(model); }
+    else { self.not_p.display(model); }
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound, P> NotFormula<VStore> for Reified<VStore, P> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+  P: PropagatorConcept<VStore, FDEvent> + Clone + 'static
+{
+  fn not(&self) -> Formula<VStore> {
+    let mut not_reified = self.clone();
+    not_reified.positive = !self.positive;
+    Box::new(not_reified)
+  }
+}
+
+impl<VStore, Domain, Bound, P> Subsumption<VStore> for Reified<VStore, P> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound,
+  P: Subsumption<VStore>
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    match self.b.is_subsumed(store) {
+      True => self.formula_subsumed(store),
+      False => !self.formula_subsumed(store),
+      Unknown => Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound, P> Propagator<VStore> for Reified<VStore, P> where
+  VStore: VStoreConcept<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound,
+  P: Propagator<VStore> + Subsumption<VStore>
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    match self.b.is_subsumed(store) {
+      True => self.propagate_formula(store, true),
+      False => self.propagate_formula(store, false),
+      Unknown => match self.formula_subsumed(store) {
+        True => self.b.update(store, Domain::singleton(Bound::one())),
+        False => self.b.update(store, Domain::singleton(Bound::zero())),
+        Unknown => true
+      }
+    }
+  }
+
+  fn save_state(&self) -> Option<PropagatorState> {
+    match (self.p.save_state(), self.not_p.save_state()) {
+      (None, None) => None,
+      states => Some(Box::new(states))
+    }
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    if let Ok(states) = state.downcast::<(Option<PropagatorState>, Option<PropagatorState>)>() {
+      let (p_state, not_p_state) = *states;
+      if let Some(s) = p_state {
+        self.p.restore_state(s);
+      }
+      if let Some(s) = not_p_state {
+        self.not_p.restore_state(s);
+      }
+    }
+  }
+}
+
+impl<VStore, P> PropagatorDependencies<FDEvent> for Reified<VStore, P> where
+ P: PropagatorDependencies<FDEvent>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = PropagatorDependencies::dependencies(&self.b);
+    deps.append(&mut self.p.dependencies());
+    deps.append(&mut self.not_p.dependencies());
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use variable::VStoreFD;
+  use variable::store::test::consume_delta;
+  use propagators::XLessY;
+  use propagators::test::*;
+  use gcollections::ops::*;
+
+  #[test]
+  fn reified_test() {
+    let dom0_3 = (0,3).to_interval();
+    let dom2_5 = (2,5).to_interval();
+    let dom3_6 = (3,6).to_interval();
+    let dom5_6 = (5,6).to_interval();
+
+    reified_test_one(1, None, dom0_3, dom5_6, true, Unknown, True, vec![(0, Assignment)], true);
+    reified_test_one(2, None, dom5_6, dom0_3, true, Unknown, True, vec![(0, Assignment)], true);
+    reified_test_one(3, None, dom0_3, dom2_5, true, Unknown, Unknown, vec![], true);
+    reified_test_one(4, Some(1), dom3_6, dom2_5, true, Unknown, Unknown, vec![(1, Bound), (2, Bound)], true);
+    // `x >= y` is propagated.
+    reified_test_one(5, Some(0), dom0_3, dom2_5, true, Unknown, Unknown, vec![(1, Bound), (2, Bound)], true);
+    reified_test_one(6, Some(0), dom0_3, dom5_6, true, False, False, vec![], false);
+    // `b <=> not(x < y)`.
+    reified_test_one(7, None, dom0_3, dom5_6, false, Unknown, True, vec![(0, Assignment)], true);
+    reified_test_one(8, Some(1), dom0_3, dom5_6, false, False, False, vec![], false);
+  }
+
+  fn reified_test_one(test_num: u32, b_value: Option<i32>, x: Interval<i32>, y: Interval<i32>,
+    positive: bool, before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    let mut store = VStoreFD::empty();
+    let mut b = Boolean::new(&mut store);
+    let x = Box::new(store.alloc(x)) as Var<VStoreFD>;
+    let y = Box::new(store.alloc(y)) as Var<VStoreFD>;
+    if let Some(v) = b_value {
+      assert!(b.update(&mut store, (v,v).to_interval()));
+      consume_delta(&mut store, vec![(0, Assignment)]);
+    }
+    let mut reified = Reified::new(b, XLessY::new(x, y));
+    reified.positive = positive;
+    test_propagation(test_num, reified, &mut store, before, after, delta_expected, propagate_success);
+  }
+}
@@ -93,6 +93,11 @@ impl Model
       i += 1;
     }
     args[i].display(self);
-    print!(") (decomposed)");
+    print!(")");
+  }
+
+  pub fn display_decomposed_global<VStore>(&self, name: &str, args: &Vec<Var<VStore>>) {
+    self.display_global(name, args);
+    print!(" (decomposed)");
   }
 }
@@ -13,6 +13,10 @@
 // limitations under the License.
 
 use trilean::SKleene;
+use std::any::Any;
+
+/// Internal state of a propagator, it is saved in the label of the constraints store.
+pub type PropagatorState = Box<dyn Any>;
 
 pub trait Subsumption<Store>
 {
@@ -23,6 +27,15 @@ pub trait Propagator<VStore>
 {
   /// Returns `false` if it failed to propagate (a variable has an empty domain after propagation).
   fn propagate(&mut self, store: &mut VStore) -> bool;
+
+  /// Returns the internal state of the propagator depending on the domains of the variables, it is saved when a label of the constraints store is created and restored on backtracking.
+  /// Stateless propagators return `None`, which is the default.
+  fn save_state(&self) -> Option<PropagatorState> {
+    None
+  }
+
+  /// Restores a state previously returned by `save_state`.
+  fn restore_state(&mut self, _state: PropagatorState) {}
 }
 
 pub trait PropagatorDependencies<Event>
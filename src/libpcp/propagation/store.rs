@@ -312,16 +312,23 @@ impl<VStore, Event, R, S> Snapshot for FrozenStore<VStore, Event, R, S> where
  R: Reactor + Clone,
  S: Scheduler
 {
-  type Label = (usize, BitSet);
+  type Label = (usize, BitSet, Vec<(usize, PropagatorState)>);
   type State = Store<VStore, Event, R, S>;
 
   fn label(&mut self) -> Self::Label {
-    (self.cstore.propagators.len(), self.cstore.active.clone())
+    let states = self.cstore.propagators.iter()
+      .enumerate()
+      .filter_map(|(i, p)| p.save_state().map(|s| (i, s)))
+      .collect();
+    (self.cstore.propagators.len(), self.cstore.active.clone(), states)
   }
 
   fn restore(mut self, label: Self::Label) -> Self::State {
     self.cstore.propagators.truncate(label.0);
     self.cstore.active = label.1;
+    for (i, state) in label.2 {
+      self.cstore.propagators[i].restore_state(state);
+    }
     self.cstore
   }
 }
@@ -72,7 +72,7 @@ impl<VStore> Clone for AllEqual<VStore> where
 impl<VStore> DisplayStateful<Model> for AllEqual<VStore>
 {
   fn display(&self, model: &Model) {
-    model.display_global("all_equal", &self.vars);
+    model.display_decomposed_global("all_equal", &self.vars);
   }
 }
 
@@ -0,0 +1,337 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Counting constraints on the number `c` of variables taking a value in a set `S`, related to a variable `n` by `c = n`, `c <= n` or `c >= n`.
+//!
+//! A variable is surely counted if its domain is included in `S`, and possibly counted if its domain intersects `S`, so `c` is between the numbers of sure and possible variables.
+//! When the upper bound of `n` is reached by the sure variables, the values of `S` are removed from the other variables.
+//! When the lower bound of `n` can only be reached with all the possible variables, their domains are restricted to `S`.
+//! The filtering is domain consistent if the domains can represent holes.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use term::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use propagators::{Relation, positions, cardinality_upto, restrict_to_values};
+
+/// The number of variables of `vars` taking a value in `values` is equal to `n`, at most `n` if it is built with `at_most` or at least `n` if it is built with `at_least`.
+#[derive(Debug)]
+pub struct Among<VStore, Bound>
+{
+  n: Var<VStore>,
+  vars: Vec<Var<VStore>>,
+  values: Vec<Bound>,
+  relation: Relation
+}
+
+pub type Count<VStore, Bound> = Among<VStore, Bound>;
+pub type AtMost<VStore, Bound> = Among<VStore, Bound>;
+pub type AtLeast<VStore, Bound> = Among<VStore, Bound>;
+
+impl<VStore, Bound> Among<VStore, Bound> where
+  Bound: IntBound
+{
+  pub fn new(n: Var<VStore>, vars: Vec<Var<VStore>>, values: Vec<Bound>) -> Self {
+    Among::make(n, vars, values, Relation::Eq)
+  }
+
+  fn make(n: Var<VStore>, vars: Vec<Var<VStore>>, mut values: Vec<Bound>, relation: Relation) -> Self {
+    values.sort();
+    values.dedup();
+    Among {
+      n: n,
+      vars: vars,
+      values: values,
+      relation: relation
+    }
+  }
+}
+
+/// The number of variables of `vars` equal to `value` is `n`.
+pub fn count<VStore, Bound>(n: Var<VStore>, vars: Vec<Var<VStore>>, value: Bound) -> Count<VStore, Bound> where
+  Bound: IntBound
+{
+  Among::make(n, vars, vec![value], Relation::Eq)
+}
+
+/// At most `n` variables of `vars` are equal to `value`.
+pub fn at_most<VStore, Bound>(n: Var<VStore>, vars: Vec<Var<VStore>>, value: Bound) -> AtMost<VStore, Bound> where
+  Bound: IntBound
+{
+  Among::make(n, vars, vec![value], Relation::AtMost)
+}
+
+/// At least `n` variables of `vars` are equal to `value`.
+pub fn at_least<VStore, Bound>(n: Var<VStore>, vars: Vec<Var<VStore>>, value: Bound) -> AtLeast<VStore, Bound> where
+  Bound: IntBound
+{
+  Among::make(n, vars, vec![value], Relation::AtLeast)
+}
+
+impl<VStore, Bound> Clone for Among<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    Among {
+      n: self.n.bclone(),
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      values: self.values.clone(),
+      relation: self.relation
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for Among<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    let name = match self.relation {
+      Relation::Eq => "among",
+      Relation::AtMost => "among_at_most",
+      Relation::AtLeast => "among_at_least"
+    };
+    print!("{}(", name);
+    self.n.display(model);
+    print!(", ");
+    if !self.vars.is_empty() {
+      model.display_global("x", &self.vars);
+    }
+    print!(", {:?})", self.values);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Among<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// `c != n` is `c <= n - 1 \/ c >= n + 1`, `c > n` is `c >= n + 1` and `c < n` is `c <= n - 1`.
+  fn not(&self) -> Formula<VStore> {
+    let at_most_pred = || Box::new(Among::make(
+      Box::new(Addition::new(self.n.bclone(), -Bound::one())),
+      self.vars.iter().map(|v| v.bclone()).collect(),
+      self.values.clone(), Relation::AtMost)) as Formula<VStore>;
+    let at_least_succ = || Box::new(Among::make(
+      Box::new(Addition::new(self.n.bclone(), Bound::one())),
+      self.vars.iter().map(|v| v.bclone()).collect(),
+      self.values.clone(), Relation::AtLeast)) as Formula<VStore>;
+    match self.relation {
+      Relation::Eq => Box::new(Disjunction::new(vec![at_most_pred(), at_least_succ()])),
+      Relation::AtMost => at_least_succ(),
+      Relation::AtLeast => at_most_pred()
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Among<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The values of `self.values` in `dom`.
+  fn values_in(&self, dom: &Domain) -> Vec<Bound> {
+    self.values.iter().filter(|v| dom.contains(v)).cloned().collect()
+  }
+
+  /// `Some(true)` if the variable is surely counted, `Some(false)` if it is not counted and `None` if it is undecided.
+  fn counted(&self, dom: &Domain) -> Option<bool> {
+    let k = self.values_in(dom).len() as u64;
+    if k == 0 { Some(false) }
+    else if cardinality_upto(dom, k + 1) == k { Some(true) }
+    else { None }
+  }
+
+  /// The numbers of sure and possible variables.
+  fn sure_possible(&self, store: &VStore) -> (usize, usize) {
+    let mut sure = 0;
+    let mut possible = 0;
+    for x in &self.vars {
+      match self.counted(&x.read(store)) {
+        Some(true) => { sure += 1; possible += 1; }
+        None => possible += 1,
+        Some(false) => ()
+      }
+    }
+    (sure, possible)
+  }
+
+  /// Restricts the undecided variables to `self.values` if `inside`, or removes `self.values` from them otherwise.
+  fn decide_all(&mut self, store: &mut VStore, inside: bool) -> bool {
+    for i in 0..self.vars.len() {
+      let dom = self.vars[i].read(store);
+      if self.counted(&dom).is_none() {
+        let dom =
+          if inside { restrict_to_values(dom.clone(), &self.values_in(&dom)) }
+          else { self.values.iter().fold(dom, |d, v| d.difference(v)) };
+        if !self.vars[i].update(store, dom) {
+          return false;
+        }
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Among<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: `[sure..possible]` and `n` cannot satisfy the relation.
+    // True: every value of `[sure..possible]` and `n` satisfies the relation.
+    // Unknown: Everything else.
+    let (sure, possible) = self.sure_possible(store);
+    let numbers: Vec<Bound> = positions(possible + 1);
+    let (sure, possible) = (numbers[sure].clone(), numbers[possible].clone());
+    let n = self.n.read(store);
+    if n.is_empty() {
+      return False;
+    }
+    match self.relation {
+      Relation::Eq =>
+        if n.is_disjoint(&Domain::new(sure.clone(), possible.clone())) { False }
+        else if n.is_singleton() && sure == possible { True }
+        else { Unknown },
+      Relation::AtMost =>
+        if sure > n.upper() { False }
+        else if possible <= n.lower() { True }
+        else { Unknown },
+      Relation::AtLeast =>
+        if possible < n.lower() { False }
+        else if sure >= n.upper() { True }
+        else { Unknown }
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Among<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let numbers: Vec<Bound> = positions(self.vars.len() + 1);
+    let (mut sure, mut possible) = self.sure_possible(store);
+    loop {
+      let mut n = self.n.read(store);
+      if self.relation != Relation::AtLeast {
+        n = n.shrink_left(numbers[sure].clone());
+      }
+      if self.relation != Relation::AtMost {
+        n = n.shrink_right(numbers[possible].clone());
+      }
+      if !self.n.update(store, n) {
+        return false;
+      }
+      let n = self.n.read(store);
+      let inside =
+        if self.relation != Relation::AtLeast && numbers[sure] == n.upper() { Some(false) }
+        else if self.relation != Relation::AtMost && numbers[possible] == n.lower() { Some(true) }
+        else { None };
+      if let Some(inside) = inside {
+        if !self.decide_all(store, inside) {
+          return false;
+        }
+      }
+      // Removing a value in the middle of a domain can fix a variable, so we iterate until the counts are stable.
+      let counts = self.sure_possible(store);
+      if counts == (sure, possible) {
+        return true;
+      }
+      sure = counts.0;
+      possible = counts.1;
+    }
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for Among<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.extend(self.n.dependencies(FDEvent::Bound));
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use variable::VStoreSet;
+  use propagators::test::*;
+
+  /// The count `n` is the last variable.
+  fn among_test_one<F>(test_num: u32, make: F, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool) where
+   F: FnOnce(SetFDVar, Vec<SetFDVar>) -> Among<VStoreSet, i32>
+  {
+    nary_set_propagator_test(test_num, |mut vars| {
+        let n = vars.pop().unwrap();
+        make(n, vars)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn among_test() {
+    let dom0_3 = (0,3).to_interval_set();
+    let dom1_2 = (1,2).to_interval_set();
+    let dom2_3 = (2,3).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let three = (3,3).to_interval_set();
+    let among = |n, vars| Among::new(n, vars, vec![1, 3]);
+
+    among_test_one(1, among, vec![dom0_3.clone(), dom1_2.clone(), dom0_3.clone()], Unknown, Unknown, vec![(2, Bound)], true);
+    among_test_one(2, among, vec![one.clone(), three.clone(), dom0_3.clone()], Unknown, True, vec![(2, Assignment)], true);
+    // The sure variable reaches the count, `1` and `3` are removed from the others.
+    among_test_one(3, among, vec![one.clone(), dom1_2.clone(), dom2_3.clone(), one.clone()], Unknown, True, vec![(1, Assignment), (2, Assignment)], true);
+    // Every possible variable is needed.
+    among_test_one(4, among, vec![dom0_3.clone(), dom1_2.clone(), two.clone(), two.clone()], Unknown, True, vec![(0, Bound), (1, Assignment)], true);
+    among_test_one(5, among, vec![one.clone(), three.clone(), one.clone()], False, False, vec![], false);
+    among_test_one(6, among, vec![two.clone(), dom1_2.clone(), two.clone()], False, False, vec![], false);
+  }
+
+  #[test]
+  fn count_test() {
+    let dom0_3 = (0,3).to_interval_set();
+    let dom1_2 = (1,2).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let count_one = |n, vars| count(n, vars, 1);
+    let at_most_one = |n, vars| at_most(n, vars, 1);
+    let at_least_one = |n, vars| at_least(n, vars, 1);
+
+    among_test_one(1, count_one, vec![one.clone(), dom1_2.clone(), dom0_3.clone()], Unknown, Unknown, vec![(2, Bound)], true);
+    among_test_one(2, count_one, vec![one.clone(), dom1_2.clone(), two.clone()], Unknown, True, vec![(1, Assignment)], true);
+    among_test_one(3, at_most_one, vec![one.clone(), dom1_2.clone(), one.clone()], Unknown, True, vec![(1, Assignment)], true);
+    among_test_one(4, at_most_one, vec![one.clone(), dom1_2.clone(), dom0_3.clone()], Unknown, Unknown, vec![(2, Bound)], true);
+    among_test_one(5, at_least_one, vec![two.clone(), dom1_2.clone(), dom0_3.clone()], Unknown, Unknown, vec![(2, Bound)], true);
+    among_test_one(6, at_least_one, vec![two.clone(), dom1_2.clone(), one.clone()], Unknown, True, vec![(1, Assignment)], true);
+    among_test_one(7, at_least_one, vec![two.clone(), two.clone(), one.clone()], False, False, vec![], false);
+  }
+}
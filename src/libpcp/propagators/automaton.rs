@@ -0,0 +1,521 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Deterministic finite automata over integer symbols, used by the `Regular` constraint.
+//!
+//! An automaton is either built transition by transition, or compiled from a regular expression where the symbols are integers:
+//!
+//! * `e1 e2` is the concatenation (symbols are separated by spaces), and `e1 | e2` the alternative.
+//! * `e*`, `e+` and `e?` are the repetitions, `e{n}`, `e{n,}` and `e{n,m}` repeat `e` exactly `n` times, at least `n` times and between `n` and `m` times.
+//! * `[1 2 5]` is one symbol among `1`, `2` and `5`, and parenthesis group expressions.
+//!
+//! For example, with `0` a day shift and `1` a night shift, `(0 | 1{1,3} 0)* 1{0,3}` forbids more than three night shifts in a row.
+//!
+//! A state can also have a transition labelled by an extra symbol standing for every value without its own transition from this state.
+//! It makes possible to complement an automaton over the integers, whose words can contain values outside of its alphabet.
+
+use concept::*;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// The states are numbered from `0` to `num_states - 1` and a state has at most one transition per symbol.
+#[derive(Clone, Debug)]
+pub struct Automaton<Bound>
+{
+  initial: usize,
+  accepting: Vec<bool>,
+  transitions: Vec<Vec<(Bound, usize)>>,
+  /// `others[q]` is the target of the transition of `q` labelled by every value without its own transition from `q`.
+  others: Vec<Option<usize>>
+}
+
+impl<Bound> Automaton<Bound> where
+  Bound: IntBound
+{
+  /// Creates an automaton with `num_states` states, without transition and accepting state.
+  pub fn new(num_states: usize, initial: usize) -> Self {
+    assert!(initial < num_states, "The initial state must be a state of the automaton.");
+    Automaton {
+      initial: initial,
+      accepting: vec![false; num_states],
+      transitions: vec![vec![]; num_states],
+      others: vec![None; num_states]
+    }
+  }
+
+  /// Compiles the regular expression `regex` (see the module documentation for the syntax) into an automaton.
+  pub fn from_regex(regex: &str) -> Result<Self, String> {
+    let regex = RegexParser::parse(regex)?;
+    let mut nfa = Nfa::new();
+    let (start, end) = nfa.build(&regex);
+    Ok(nfa.determinize(start, end))
+  }
+
+  pub fn num_states(&self) -> usize {
+    self.accepting.len()
+  }
+
+  pub fn initial(&self) -> usize {
+    self.initial
+  }
+
+  pub fn is_accepting(&self, state: usize) -> bool {
+    self.accepting[state]
+  }
+
+  /// The transitions of `state` sorted by symbols.
+  pub fn transitions(&self, state: usize) -> &[(Bound, usize)] {
+    &self.transitions[state]
+  }
+
+  pub fn accept(&mut self, state: usize) {
+    self.accepting[state] = true;
+  }
+
+  /// Adds the transition from `from` to `to` labelled by `symbol`.
+  pub fn transition(&mut self, from: usize, symbol: Bound, to: usize) {
+    assert!(to < self.num_states(), "The target of a transition must be a state of the automaton.");
+    match self.transitions[from].binary_search_by(|t| t.0.cmp(&symbol)) {
+      Ok(_) => panic!("The automaton must be deterministic: the state {} has two transitions labelled by {:?}.", from, symbol),
+      Err(i) => self.transitions[from].insert(i, (symbol, to))
+    }
+  }
+
+  /// Adds the transition from `from` to `to` labelled by every value without its own transition from `from`.
+  pub fn transition_others(&mut self, from: usize, to: usize) {
+    assert!(to < self.num_states(), "The target of a transition must be a state of the automaton.");
+    assert!(self.others[from].is_none(), "The automaton must be deterministic: the state {} has two transitions for the other values.", from);
+    self.others[from] = Some(to);
+  }
+
+  /// The state reached from `state` with the values without their own transition from `state`.
+  pub fn others(&self, state: usize) -> Option<usize> {
+    self.others[state]
+  }
+
+  /// The state reached from `state` with `symbol`.
+  pub fn next(&self, state: usize, symbol: &Bound) -> Option<usize> {
+    let transitions = &self.transitions[state];
+    match transitions.binary_search_by(|t| t.0.cmp(symbol)) {
+      Ok(i) => Some(transitions[i].1),
+      Err(_) => self.others[state]
+    }
+  }
+
+  /// The automaton accepting the words over the integers rejected by this automaton.
+  /// It is completed with a sink state, reached by the missing transitions, and its accepting states are flipped.
+  pub fn complement(&self) -> Self {
+    let mut alphabet: Vec<Bound> = self.transitions.iter()
+      .flat_map(|ts| ts.iter().map(|t| t.0.clone()))
+      .collect();
+    alphabet.sort();
+    alphabet.dedup();
+    let sink = self.num_states();
+    let mut complement = Automaton::new(sink + 1, self.initial);
+    for q in 0..sink + 1 {
+      let next = |symbol: &Bound| if q == sink { sink } else { self.next(q, symbol).unwrap_or(sink) };
+      for symbol in &alphabet {
+        complement.transition(q, symbol.clone(), next(symbol));
+      }
+      complement.transition_others(q, if q == sink { sink } else { self.others(q).unwrap_or(sink) });
+      if q == sink || !self.is_accepting(q) {
+        complement.accept(q);
+      }
+    }
+    complement
+  }
+
+  pub fn accepts(&self, word: &[Bound]) -> bool {
+    let mut state = self.initial;
+    for symbol in word {
+      match self.next(state, symbol) {
+        Some(next) => state = next,
+        None => return false
+      }
+    }
+    self.accepting[state]
+  }
+}
+
+#[derive(Clone, Debug)]
+enum Regex<Bound>
+{
+  Symbols(Vec<Bound>),
+  Concat(Vec<Regex<Bound>>),
+  Alt(Vec<Regex<Bound>>),
+  /// `Repeat(e, n, m)` repeats `e` between `n` and `m` times, `None` is unbounded.
+  Repeat(Box<Regex<Bound>>, usize, Option<usize>)
+}
+
+struct RegexParser<'a>
+{
+  chars: Peekable<Chars<'a>>
+}
+
+impl<'a> RegexParser<'a>
+{
+  fn parse<Bound: IntBound>(regex: &'a str) -> Result<Regex<Bound>, String> {
+    let mut parser = RegexParser { chars: regex.chars().peekable() };
+    let regex = parser.alternative()?;
+    match parser.peek() {
+      None => Ok(regex),
+      Some(c) => Err(format!("Unexpected `{}` in the regular expression.", c))
+    }
+  }
+
+  /// Next character which is not a space.
+  fn peek(&mut self) -> Option<char> {
+    while let Some(&c) = self.chars.peek() {
+      if c.is_whitespace() {
+        self.chars.next();
+      }
+      else {
+        return Some(c);
+      }
+    }
+    None
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), String> {
+    match self.peek() {
+      Some(c) if c == expected => {
+        self.chars.next();
+        Ok(())
+      }
+      Some(c) => Err(format!("Expected `{}` but found `{}` in the regular expression.", expected, c)),
+      None => Err(format!("Expected `{}` but reached the end of the regular expression.", expected))
+    }
+  }
+
+  fn alternative<Bound: IntBound>(&mut self) -> Result<Regex<Bound>, String> {
+    let mut alternatives = vec![self.concatenation()?];
+    while self.peek() == Some('|') {
+      self.chars.next();
+      alternatives.push(self.concatenation()?);
+    }
+    Ok(if alternatives.len() == 1 { alternatives.pop().unwrap() } else { Regex::Alt(alternatives) })
+  }
+
+  fn concatenation<Bound: IntBound>(&mut self) -> Result<Regex<Bound>, String> {
+    let mut regexes = vec![];
+    loop {
+      match self.peek() {
+        Some(c) if c == '(' || c == '[' || c == '-' || c.is_ascii_digit() => regexes.push(self.repetition()?),
+        _ => break
+      }
+    }
+    Ok(if regexes.len() == 1 { regexes.pop().unwrap() } else { Regex::Concat(regexes) })
+  }
+
+  fn repetition<Bound: IntBound>(&mut self) -> Result<Regex<Bound>, String> {
+    let mut regex = self.atom()?;
+    loop {
+      let (min, max) = match self.peek() {
+        Some('*') => (0, None),
+        Some('+') => (1, None),
+        Some('?') => (0, Some(1)),
+        Some('{') => {
+          self.chars.next();
+          let min = self.integer()?;
+          let max =
+            if self.peek() == Some(',') {
+              self.chars.next();
+              if self.peek() == Some('}') { None } else { Some(self.integer()?) }
+            }
+            else { Some(min) };
+          if let Some(max) = max {
+            if max < min {
+              return Err(format!("Empty repetition `{{{},{}}}` in the regular expression.", min, max));
+            }
+          }
+          self.expect('}')?;
+          regex = Regex::Repeat(Box::new(regex), min, max);
+          continue;
+        }
+        _ => return Ok(regex)
+      };
+      self.chars.next();
+      regex = Regex::Repeat(Box::new(regex), min, max);
+    }
+  }
+
+  fn atom<Bound: IntBound>(&mut self) -> Result<Regex<Bound>, String> {
+    match self.peek() {
+      Some('(') => {
+        self.chars.next();
+        let regex = self.alternative()?;
+        self.expect(')')?;
+        Ok(regex)
+      }
+      Some('[') => {
+        self.chars.next();
+        let mut symbols = vec![];
+        while self.peek() != Some(']') {
+          symbols.push(self.symbol()?);
+        }
+        self.chars.next();
+        Ok(Regex::Symbols(symbols))
+      }
+      _ => Ok(Regex::Symbols(vec![self.symbol()?]))
+    }
+  }
+
+  fn token(&mut self) -> String {
+    let mut token = String::new();
+    self.peek();
+    if self.chars.peek() == Some(&'-') {
+      token.push('-');
+      self.chars.next();
+    }
+    while let Some(&c) = self.chars.peek() {
+      if !c.is_ascii_digit() {
+        break;
+      }
+      token.push(c);
+      self.chars.next();
+    }
+    token
+  }
+
+  fn symbol<Bound: IntBound>(&mut self) -> Result<Bound, String> {
+    let token = self.token();
+    Bound::from_str_radix(&token, 10)
+      .map_err(|_| format!("Expected a symbol but found `{}` in the regular expression.", token))
+  }
+
+  fn integer(&mut self) -> Result<usize, String> {
+    let token = self.token();
+    token.parse()
+      .map_err(|_| format!("Expected a number of repetitions but found `{}` in the regular expression.", token))
+  }
+}
+
+/// Non-deterministic automaton with epsilon transitions obtained by the construction of Thompson.
+struct Nfa<Bound>
+{
+  epsilon: Vec<Vec<usize>>,
+  transitions: Vec<Vec<(Bound, usize)>>
+}
+
+impl<Bound> Nfa<Bound> where
+  Bound: IntBound
+{
+  fn new() -> Self {
+    Nfa {
+      epsilon: vec![],
+      transitions: vec![]
+    }
+  }
+
+  fn state(&mut self) -> usize {
+    self.epsilon.push(vec![]);
+    self.transitions.push(vec![]);
+    self.epsilon.len() - 1
+  }
+
+  /// Builds the states of `regex` and returns its start and end states.
+  fn build(&mut self, regex: &Regex<Bound>) -> (usize, usize) {
+    let start = self.state();
+    let mut end = start;
+    match *regex {
+      Regex::Symbols(ref symbols) => {
+        end = self.state();
+        for s in symbols {
+          self.transitions[start].push((s.clone(), end));
+        }
+      }
+      Regex::Concat(ref regexes) => {
+        for r in regexes {
+          let (s, e) = self.build(r);
+          self.epsilon[end].push(s);
+          end = e;
+        }
+      }
+      Regex::Alt(ref regexes) => {
+        end = self.state();
+        for r in regexes {
+          let (s, e) = self.build(r);
+          self.epsilon[start].push(s);
+          self.epsilon[e].push(end);
+        }
+      }
+      Regex::Repeat(ref r, min, max) => {
+        for _ in 0..min {
+          let (s, e) = self.build(r);
+          self.epsilon[end].push(s);
+          end = e;
+        }
+        match max {
+          None => {
+            let (s, e) = self.build(r);
+            self.epsilon[end].push(s);
+            self.epsilon[e].push(end);
+          }
+          Some(max) => {
+            let mut optionals = vec![];
+            for _ in min..max {
+              let (s, e) = self.build(r);
+              self.epsilon[end].push(s);
+              optionals.push(end);
+              end = e;
+            }
+            for o in optionals {
+              self.epsilon[o].push(end);
+            }
+          }
+        }
+      }
+    }
+    (start, end)
+  }
+
+  fn closure(&self, mut states: Vec<usize>) -> Vec<usize> {
+    let mut visited = vec![false; self.epsilon.len()];
+    let mut stack = states.clone();
+    for &s in &states {
+      visited[s] = true;
+    }
+    while let Some(s) = stack.pop() {
+      for &t in &self.epsilon[s] {
+        if !visited[t] {
+          visited[t] = true;
+          states.push(t);
+          stack.push(t);
+        }
+      }
+    }
+    states.sort();
+    states
+  }
+
+  /// Subset construction, only the states reachable from `start` are created.
+  fn determinize(&self, start: usize, end: usize) -> Automaton<Bound> {
+    let mut alphabet: Vec<Bound> = self.transitions.iter()
+      .flat_map(|ts| ts.iter().map(|t| t.0.clone()))
+      .collect();
+    alphabet.sort();
+    alphabet.dedup();
+    let mut subsets = vec![self.closure(vec![start])];
+    let mut index = HashMap::new();
+    index.insert(subsets[0].clone(), 0);
+    let mut transitions = vec![];
+    let mut i = 0;
+    while i < subsets.len() {
+      let mut from_i = vec![];
+      for a in &alphabet {
+        let targets: Vec<usize> = subsets[i].iter()
+          .flat_map(|&s| self.transitions[s].iter().filter(|t| &t.0 == a).map(|t| t.1))
+          .collect();
+        if targets.is_empty() {
+          continue;
+        }
+        let subset = self.closure(targets);
+        let next = subsets.len();
+        let j = *index.entry(subset.clone()).or_insert(next);
+        if j == next {
+          subsets.push(subset);
+        }
+        from_i.push((a.clone(), j));
+      }
+      transitions.push(from_i);
+      i += 1;
+    }
+    let mut automaton = Automaton::new(subsets.len(), 0);
+    for (q, subset) in subsets.iter().enumerate() {
+      if subset.contains(&end) {
+        automaton.accept(q);
+      }
+    }
+    automaton.transitions = transitions;
+    automaton
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn accepts(automaton: &Automaton<i32>, words: Vec<Vec<i32>>, expected: bool) {
+    for word in words {
+      assert_eq!(automaton.accepts(&word), expected, "word {:?}", word);
+    }
+  }
+
+  #[test]
+  fn automaton_test() {
+    // Words over `{0, 1}` with an even number of `1`.
+    let mut even = Automaton::new(2, 0);
+    even.accept(0);
+    even.transition(0, 0, 0);
+    even.transition(0, 1, 1);
+    even.transition(1, 0, 1);
+    even.transition(1, 1, 0);
+    accepts(&even, vec![vec![], vec![0, 0], vec![1, 0, 1], vec![1, 1, 1, 1]], true);
+    accepts(&even, vec![vec![1], vec![0, 1, 0], vec![2], vec![1, 1, 1]], false);
+    assert_eq!(even.next(1, &1), Some(0));
+    assert_eq!(even.next(1, &2), None);
+
+    // `2` is skipped.
+    even.transition_others(0, 0);
+    even.transition_others(1, 1);
+    accepts(&even, vec![vec![2], vec![1, 2, 1], vec![-5, 0]], true);
+    accepts(&even, vec![vec![1, 3], vec![2, 2, 1]], false);
+  }
+
+  #[test]
+  fn complement_test() {
+    let nights = Automaton::from_regex("(0 | 1{1,3} 0)* 1{0,3}").unwrap().complement();
+    accepts(&nights, vec![vec![1, 1, 1, 1], vec![0, 1, 1, 1, 1, 0], vec![2], vec![0, -1, 0]], true);
+    accepts(&nights, vec![vec![], vec![1, 1, 1], vec![0, 1, 1, 1, 0, 1]], false);
+    let nights = nights.complement();
+    accepts(&nights, vec![vec![], vec![1, 1, 1], vec![0, 1, 1, 1, 0, 1]], true);
+    accepts(&nights, vec![vec![1, 1, 1, 1], vec![2], vec![0, -1, 0]], false);
+  }
+
+  #[test]
+  #[should_panic]
+  fn non_deterministic_test() {
+    let mut automaton = Automaton::new(2, 0);
+    automaton.transition(0, 1, 0);
+    automaton.transition(0, 1, 1);
+  }
+
+  #[test]
+  fn regex_test() {
+    let nights = Automaton::from_regex("(0 | 1{1,3} 0)* 1{0,3}").unwrap();
+    accepts(&nights, vec![vec![], vec![1, 1, 1], vec![0, 1, 1, 1, 0, 1], vec![1, 1, 0, 1, 1, 1]], true);
+    accepts(&nights, vec![vec![1, 1, 1, 1], vec![0, 1, 1, 1, 1, 0], vec![2]], false);
+
+    // A rest day `2` after the night shifts.
+    let rest = Automaton::from_regex("([0 2] | 1+ 2)* 1*").unwrap();
+    accepts(&rest, vec![vec![0, 1, 1, 2, 0], vec![1, 2, 2], vec![0, 0, 1]], true);
+    accepts(&rest, vec![vec![1, 0], vec![1, 1, 0, 2]], false);
+
+    let exact = Automaton::from_regex("-1{2} 10{2,}").unwrap();
+    accepts(&exact, vec![vec![-1, -1, 10, 10], vec![-1, -1, 10, 10, 10]], true);
+    accepts(&exact, vec![vec![-1, 10, 10], vec![-1, -1, 10]], false);
+
+    let empty = Automaton::<i32>::from_regex("").unwrap();
+    accepts(&empty, vec![vec![]], true);
+    accepts(&empty, vec![vec![0]], false);
+  }
+
+  #[test]
+  fn regex_error_test() {
+    for regex in vec!["(0 1", "0 )", "0{2,1}", "[0 1", "0{a}", "a", "- 1"] {
+      assert!(Automaton::<i32>::from_regex(regex).is_err(), "regex `{}`", regex);
+    }
+  }
+}
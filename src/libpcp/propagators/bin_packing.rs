@@ -0,0 +1,382 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The bin packing constraint assigns each item `i` of size `sizes[i]` to a bin `assignment[i]` in `0..loads.len()`, and the load of the bin `j` is the sum of the sizes of its items.
+//!
+//! It is the algorithm described in `A Constraint for Bin Packing`, Shaw, 2004.
+//! The items assigned to a bin are required and the items that can still be assigned to it are candidates.
+//! The load of a bin is between the sizes of its required items and of its candidates, and the sum of the loads is the sum of all the sizes.
+//! The knapsack reasoning detects with `no_sum` that no subset of the candidates can reach the load of a bin, it tightens the load bounds and eliminates or commits candidates.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use propagators::positions;
+
+fn sum<Bound: IntBound>(values: &[Bound]) -> Bound {
+  values.iter().fold(Bound::zero(), |acc, v| acc + v.clone())
+}
+
+/// `Some((alpha', beta'))` if no subset of `sizes`, sorted in decreasing order, has a sum in `[alpha..beta]`.
+/// `alpha' < alpha` and `beta' > beta` are sums of subsets of `sizes`.
+/// It is a relaxation and `None` does not guarantee that such a subset exists.
+pub fn no_sum<Bound: IntBound>(sizes: &[Bound], alpha: Bound, beta: Bound) -> Option<(Bound, Bound)> {
+  if alpha <= Bound::zero() || beta >= sum(sizes) {
+    return None;
+  }
+  // The items are numbered from `1` to `n` as in the paper.
+  let n = sizes.len();
+  let x = |i: usize| sizes[i - 1].clone();
+  let mut sum_a = Bound::zero();
+  let mut sum_c = Bound::zero();
+  let mut k = 0;
+  let mut k2 = 0;
+  while sum_c.clone() + x(n - k2) < alpha {
+    sum_c = sum_c + x(n - k2);
+    k2 += 1;
+  }
+  let mut sum_b = x(n - k2);
+  while sum_a < alpha && sum_b <= beta {
+    k += 1;
+    sum_a = sum_a + x(k);
+    if sum_a < alpha {
+      k2 -= 1;
+      sum_b = sum_b + x(n - k2);
+      sum_c = sum_c - x(n - k2);
+      while sum_a.clone() + sum_c.clone() >= alpha {
+        k2 -= 1;
+        sum_c = sum_c - x(n - k2);
+        sum_b = sum_b + x(n - k2) - x(n - k2 - k - 1);
+      }
+    }
+  }
+  if sum_a < alpha { Some((sum_a + sum_c, sum_b)) } else { None }
+}
+
+/// `true` if it is detected that no subset of `sizes` has a sum in `[alpha..beta]`.
+fn no_subset_sum<Bound: IntBound>(sizes: &[Bound], alpha: Bound, beta: Bound) -> bool {
+  beta < Bound::zero() || alpha > sum(sizes) || no_sum(sizes, alpha, beta).is_some()
+}
+
+#[derive(Debug)]
+pub struct BinPacking<VStore, Bound>
+{
+  assignment: Vec<Var<VStore>>,
+  sizes: Vec<Bound>,
+  loads: Vec<Var<VStore>>
+}
+
+impl<VStore, Bound> BinPacking<VStore, Bound> where
+  Bound: IntBound
+{
+  /// Precondition: there is one non-negative size per item.
+  pub fn new(assignment: Vec<Var<VStore>>, sizes: Vec<Bound>, loads: Vec<Var<VStore>>) -> Self {
+    assert_eq!(assignment.len(), sizes.len(), "Every item of `BinPacking` must have a size.");
+    assert!(sizes.iter().all(|s| *s >= Bound::zero()), "The sizes of the items of `BinPacking` must be non-negative.");
+    BinPacking {
+      assignment: assignment,
+      sizes: sizes,
+      loads: loads
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for BinPacking<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    BinPacking {
+      assignment: self.assignment.iter().map(|v| v.bclone()).collect(),
+      sizes: self.sizes.clone(),
+      loads: self.loads.iter().map(|v| v.bclone()).collect()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for BinPacking<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    print!("bin_packing(");
+    if !self.assignment.is_empty() {
+      model.display_global("bin", &self.assignment);
+    }
+    print!(", {:?}, ", self.sizes);
+    if !self.loads.is_empty() {
+      model.display_global("load", &self.loads);
+    }
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for BinPacking<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// A load different from the sizes of the items of its bin is a sum of the reified assignments `assignment[i] = j`, `Reified` would need Boolean variables which `not` cannot allocate, so the negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Negation::new(Box::new(self.clone())))
+  }
+}
+
+/// The required and candidate items of a bin.
+struct BinItems<Bound>
+{
+  required: Bound,
+  // The candidates are sorted by decreasing size.
+  candidates: Vec<usize>,
+  candidate_sizes: Vec<Bound>
+}
+
+impl<Bound: IntBound> BinItems<Bound>
+{
+  fn possible(&self) -> Bound {
+    self.required.clone() + sum(&self.candidate_sizes)
+  }
+}
+
+impl<VStore, Domain, Bound> BinPacking<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn bin_items(&self, store: &VStore, bin: &Bound) -> BinItems<Bound> {
+    let mut required = Bound::zero();
+    let mut candidates = vec![];
+    for (i, x) in self.assignment.iter().enumerate() {
+      let x = x.read(store);
+      if x.is_singleton() && x.lower() == *bin {
+        required = required + self.sizes[i].clone();
+      }
+      else if x.contains(bin) {
+        candidates.push(i);
+      }
+    }
+    candidates.sort_by(|&i, &j| self.sizes[j].cmp(&self.sizes[i]));
+    let candidate_sizes = candidates.iter().map(|&i| self.sizes[i].clone()).collect();
+    BinItems {
+      required: required,
+      candidates: candidates,
+      candidate_sizes: candidate_sizes
+    }
+  }
+
+  fn restrict_to_bins(&mut self, store: &mut VStore) -> bool {
+    let m: Bound = positions(self.loads.len() + 1).pop().unwrap();
+    for x in &mut self.assignment {
+      let dom = x.read(store).shrink_left(Bound::zero()).shrink_right(m.clone() - Bound::one());
+      if !x.update(store, dom) {
+        return false;
+      }
+    }
+    true
+  }
+
+  /// The sum of the loads is the sum of the sizes.
+  fn pack_all(&mut self, store: &mut VStore) -> bool {
+    let total = sum(&self.sizes);
+    let loads: Vec<Domain> = self.loads.iter().map(|l| l.read(store)).collect();
+    let sum_lb = loads.iter().fold(Bound::zero(), |acc, l| acc + l.lower());
+    let sum_ub = loads.iter().fold(Bound::zero(), |acc, l| acc + l.upper());
+    for (load, dom) in self.loads.iter_mut().zip(loads) {
+      let lb = total.clone() - (sum_ub.clone() - dom.upper());
+      let ub = total.clone() - (sum_lb.clone() - dom.lower());
+      if !load.update(store, dom.shrink_left(lb).shrink_right(ub)) {
+        return false;
+      }
+    }
+    true
+  }
+
+  /// Load maintenance, knapsack reasoning on the load and elimination or commitment of the candidates of the bin `j`.
+  fn filter_bin(&mut self, store: &mut VStore, j: usize, bin: Bound) -> bool {
+    let items = self.bin_items(store, &bin);
+    let load = self.loads[j].read(store)
+      .shrink_left(items.required.clone())
+      .shrink_right(items.possible());
+    if !self.loads[j].update(store, load) {
+      return false;
+    }
+    let load = self.loads[j].read(store);
+    let low = load.lower() - items.required.clone();
+    let high = load.upper() - items.required.clone();
+    let sizes = &items.candidate_sizes;
+    if no_subset_sum(sizes, low.clone(), high.clone()) {
+      return false;
+    }
+    let mut load = load;
+    if let Some((_, above)) = no_sum(sizes, low.clone(), low.clone()) {
+      load = load.shrink_left(items.required.clone() + above);
+    }
+    if let Some((below, _)) = no_sum(sizes, high.clone(), high.clone()) {
+      load = load.shrink_right(items.required.clone() + below);
+    }
+    if !self.loads[j].update(store, load) {
+      return false;
+    }
+    let load = self.loads[j].read(store);
+    let low = load.lower() - items.required.clone();
+    let high = load.upper() - items.required.clone();
+    for (c, &i) in items.candidates.iter().enumerate() {
+      let mut others = sizes.clone();
+      let size = others.remove(c);
+      let x = self.assignment[i].read(store);
+      let x =
+        if no_subset_sum(&others, low.clone() - size.clone(), high.clone() - size.clone()) {
+          x.difference(&bin)
+        }
+        else if no_subset_sum(&others, low.clone(), high.clone()) {
+          x.intersection(&Domain::singleton(bin.clone()))
+        }
+        else { x };
+      if !self.assignment[i].update(store, x) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for BinPacking<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: an item cannot be assigned to any bin, or the load of a bin is out of the bounds given by its required and candidate items.
+    // True: the items are assigned and the loads are equal to the sizes of their items.
+    // Unknown: Everything else.
+    let bins: Vec<Bound> = positions(self.loads.len());
+    let mut assigned = true;
+    for x in &self.assignment {
+      let x = x.read(store);
+      if !bins.iter().any(|b| x.contains(b)) {
+        return False;
+      }
+      assigned &= x.is_singleton();
+    }
+    for (load, bin) in self.loads.iter().zip(bins.iter()) {
+      let load = load.read(store);
+      let items = self.bin_items(store, bin);
+      if load.is_empty() || items.required > load.upper() || items.possible() < load.lower() {
+        return False;
+      }
+      if assigned && !load.contains(&items.required) {
+        return False;
+      }
+      assigned &= load.is_singleton();
+    }
+    if assigned { True } else { Unknown }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for BinPacking<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    if !self.restrict_to_bins(store) || !self.pack_all(store) {
+      return false;
+    }
+    let bins: Vec<Bound> = positions(self.loads.len());
+    for (j, bin) in bins.into_iter().enumerate() {
+      if !self.filter_bin(store, j, bin) {
+        return false;
+      }
+    }
+    self.pack_all(store)
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for BinPacking<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.assignment.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.extend(self.loads.iter().flat_map(|l| l.dependencies(FDEvent::Bound)));
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+
+  #[test]
+  fn no_sum_test() {
+    // The subsets sums of `[5, 5, 3]` are `0, 3, 5, 8, 10, 13`.
+    assert_eq!(no_sum(&[5, 5, 3], 6, 7), Some((5, 8)));
+    assert_eq!(no_sum(&[5, 5, 3], 4, 4), Some((3, 5)));
+    assert_eq!(no_sum(&[5, 5, 3], 6, 8), None);
+    assert_eq!(no_sum(&[5, 5, 3], 0, 2), None);
+  }
+
+  /// The loads are the last `bins` variables.
+  fn bin_packing_test_one(test_num: u32, sizes: Vec<i32>, bins: usize, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, |mut vars| {
+        let loads = vars.split_off(vars.len() - bins);
+        BinPacking::new(vars, sizes, loads)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn bin_packing_test() {
+    let dom0_1 = (0,1).to_interval_set();
+    let dom0_2 = (0,2).to_interval_set();
+    let dom0_10 = (0,10).to_interval_set();
+    let dom0_6 = (0,6).to_interval_set();
+    let dom6_7 = (6,7).to_interval_set();
+    let zero = (0,0).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let five = (5,5).to_interval_set();
+    let eight = (8,8).to_interval_set();
+
+    bin_packing_test_one(1, vec![5, 5, 3], 2, vec![dom0_1.clone(), dom0_1.clone(), dom0_1.clone(), dom0_10.clone(), dom0_10.clone()],
+      Unknown, Unknown, vec![(3, Bound), (4, Bound)], true);
+    // The loads must be sums of subsets of the sizes, `[7..10]` becomes `[8..10]` and `[3..6]` becomes `[3..5]`.
+    bin_packing_test_one(2, vec![5, 5, 3], 2, vec![dom0_1.clone(), dom0_1.clone(), dom0_1.clone(), dom0_10.clone(), dom0_6.clone()],
+      Unknown, Unknown, vec![(3, Bound), (4, Bound)], true);
+    // No subset of the sizes is in `[6..7]`.
+    bin_packing_test_one(3, vec![5, 5, 3], 2, vec![dom0_1.clone(), dom0_1.clone(), dom0_1.clone(), dom6_7.clone(), dom0_10.clone()],
+      Unknown, Unknown, vec![], false);
+    // The bin `0` needs the three items to reach the load `8`, with `5 + 3`, so the two items of size `5` cannot both be in it.
+    bin_packing_test_one(4, vec![5, 5, 3], 2, vec![zero.clone(), dom0_1.clone(), dom0_1.clone(), eight.clone(), five.clone()],
+      Unknown, True, vec![(1, Assignment), (2, Assignment)], true);
+    // The bin `2` does not exist.
+    bin_packing_test_one(5, vec![5, 5, 3], 2, vec![dom0_2.clone(), one.clone(), zero.clone(), dom0_10.clone(), dom0_10.clone()],
+      Unknown, Unknown, vec![(0, Bound), (3, Bound), (4, Bound)], true);
+    bin_packing_test_one(6, vec![5, 5, 3], 2, vec![zero.clone(), one.clone(), zero.clone(), eight.clone(), five.clone()],
+      True, True, vec![], true);
+    bin_packing_test_one(7, vec![5, 5, 3], 2, vec![zero.clone(), one.clone(), one.clone(), eight.clone(), five.clone()],
+      False, False, vec![], false);
+  }
+}
@@ -0,0 +1,465 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraints `Circuit` and `Subcircuit` restrict an array of successor variables to form a single circuit.
+//!
+//! The nodes are numbered from `0` to `n-1` and the node `i` is followed by the node `x[i]`.
+//! The successors are distinct, hence the domain consistent propagator `DistinctDomain` is applied first.
+//! Then, two filtering rules are applied on the graph of the successors:
+//!  * No-cycle (`Solving Various Weighted Matching Problems with Constraints`, Caseau and Laburthe, 1997): the last node of a chain of assigned successors cannot go back to the first node of the chain, unless the chain contains every node that must be in the circuit.
+//!  * Reachability: the nodes of the circuit are in the same strongly connected component, and an edge between two components cannot be in the circuit.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use propagators::distinct_domain::*;
+use propagators::{domain_values, positions, restrict_to_values};
+
+/// The successors `x[i]` form a circuit visiting every node exactly once.
+#[derive(Debug)]
+pub struct Circuit<VStore, Bound>
+{
+  vars: Vec<Var<VStore>>,
+  distinct: DistinctDomain<VStore, Bound>
+}
+
+impl<VStore, Bound> Circuit<VStore, Bound> where
+  VStore: Collection
+{
+  /// Precondition: `vars.len() > 0`.
+  pub fn new(vars: Vec<Var<VStore>>) -> Self {
+    let distinct = DistinctDomain::new(vars.iter().map(|v| v.bclone()).collect());
+    Circuit {
+      vars: vars,
+      distinct: distinct
+    }
+  }
+}
+
+/// The nodes such that `x[i] != i` form a single circuit, and the other nodes are not visited.
+/// The circuit can be empty, in which case `x[i] = i` for every node.
+#[derive(Debug)]
+pub struct Subcircuit<VStore, Bound>
+{
+  vars: Vec<Var<VStore>>,
+  distinct: DistinctDomain<VStore, Bound>
+}
+
+impl<VStore, Bound> Subcircuit<VStore, Bound> where
+  VStore: Collection
+{
+  /// Precondition: `vars.len() > 0`.
+  pub fn new(vars: Vec<Var<VStore>>) -> Self {
+    let distinct = DistinctDomain::new(vars.iter().map(|v| v.bclone()).collect());
+    Subcircuit {
+      vars: vars,
+      distinct: distinct
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for Circuit<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    Circuit {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      distinct: self.distinct.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for Subcircuit<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    Subcircuit {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      distinct: self.distinct.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for Circuit<VStore, Bound>
+{
+  fn display(&self, model: &Model) {
+    model.display_global("circuit", &self.vars);
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for Subcircuit<VStore, Bound>
+{
+  fn display(&self, model: &Model) {
+    model.display_global("subcircuit", &self.vars);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Circuit<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// The successors can form any cycle not covering every node, and stating it would need one propagator per such cycle, so the negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Negation::new(Box::new(self.clone())))
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Subcircuit<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// The successors can form any two disjoint cycles of at least two nodes, and stating it would need one propagator per pair of such cycles, so the negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Negation::new(Box::new(self.clone())))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Circuit<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, vstore: &VStore) -> SKleene {
+    circuit_subsumption(&self.vars, vstore, false)
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Subcircuit<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, vstore: &VStore) -> SKleene {
+    circuit_subsumption(&self.vars, vstore, true)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Circuit<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, vstore: &mut VStore) -> bool {
+    propagate_circuit(&mut self.vars, &mut self.distinct, vstore, false)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Subcircuit<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, vstore: &mut VStore) -> bool {
+    propagate_circuit(&mut self.vars, &mut self.distinct, vstore, true)
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for Circuit<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    self.distinct.dependencies()
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for Subcircuit<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    self.distinct.dependencies()
+  }
+}
+
+/// False: the successors are assigned and do not form a (sub)circuit.
+/// True: the successors are assigned and form a (sub)circuit.
+/// Unknown: Everything else.
+fn circuit_subsumption<VStore, Domain, Bound>(vars: &[Var<VStore>], vstore: &VStore, subcircuit: bool) -> SKleene where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  let doms: Vec<Domain> = vars.iter().map(|v| v.read(vstore)).collect();
+  if doms.iter().any(|d| d.is_empty()) {
+    return False;
+  }
+  if !doms.iter().all(|d| d.is_singleton()) {
+    return Unknown;
+  }
+  let nodes: Vec<Bound> = positions(vars.len());
+  let mut next = vec![];
+  for dom in &doms {
+    match nodes.binary_search(&dom.lower()) {
+      Ok(j) => next.push(j),
+      Err(_) => return False
+    }
+  }
+  let visited: Vec<usize> = (0..next.len()).filter(|&i| next[i] != i).collect();
+  if visited.is_empty() {
+    return if subcircuit || next.len() == 1 { True } else { False };
+  }
+  if !subcircuit && visited.len() != next.len() {
+    return False;
+  }
+  let start = visited[0];
+  let mut i = next[start];
+  let mut length = 1;
+  while i != start && length <= visited.len() {
+    i = next[i];
+    length += 1;
+  }
+  if i == start && length == visited.len() { True } else { False }
+}
+
+fn propagate_circuit<VStore, Domain, Bound>(vars: &mut [Var<VStore>], distinct: &mut DistinctDomain<VStore, Bound>,
+  vstore: &mut VStore, subcircuit: bool) -> bool where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  let n = vars.len();
+  let nodes: Vec<Bound> = positions(n);
+  for (i, x) in vars.iter_mut().enumerate() {
+    let mut dom = x.read(vstore)
+      .shrink_left(nodes[0].clone())
+      .shrink_right(nodes[n-1].clone());
+    if !subcircuit && n > 1 {
+      dom = dom.difference(&nodes[i]);
+    }
+    if !x.update(vstore, dom) {
+      return false;
+    }
+  }
+  if !distinct.propagate(vstore) {
+    return false;
+  }
+  let doms: Vec<Domain> = vars.iter().map(|v| v.read(vstore)).collect();
+  let mut graph = SuccessorGraph::new(&doms, &nodes, subcircuit);
+  if !graph.filter() {
+    return false;
+  }
+  for (i, dom) in doms.into_iter().enumerate() {
+    let values: Vec<Bound> = graph.succ[i].iter().map(|&j| nodes[j].clone()).collect();
+    if !vars[i].update(vstore, restrict_to_values(dom, &values)) {
+      return false;
+    }
+  }
+  true
+}
+
+/// Graph of the successors where `succ[i]` contains the nodes in the domain of `x[i]`.
+/// In a subcircuit, the node `i` is not visited if `x[i] = i`, and it must be visited if `i` is not in `succ[i]`.
+struct SuccessorGraph
+{
+  succ: Vec<Vec<usize>>,
+  subcircuit: bool
+}
+
+impl SuccessorGraph
+{
+  fn new<Domain, Bound>(doms: &[Domain], nodes: &[Bound], subcircuit: bool) -> Self where
+    Domain: IntDomain<Item=Bound>,
+    Bound: IntBound
+  {
+    let succ = doms.iter()
+      .map(|d| domain_values(d).iter()
+        .map(|v| nodes.binary_search(v).unwrap())
+        .collect())
+      .collect();
+    SuccessorGraph {
+      succ: succ,
+      subcircuit: subcircuit
+    }
+  }
+
+  fn mandatory(&self, i: usize) -> bool {
+    !self.subcircuit || !self.succ[i].contains(&i)
+  }
+
+  /// The successor of `i` if it is assigned to another node.
+  fn next(&self, i: usize) -> Option<usize> {
+    if self.succ[i].len() == 1 && self.succ[i][0] != i { Some(self.succ[i][0]) } else { None }
+  }
+
+  fn remove(&mut self, i: usize, j: usize) {
+    self.succ[i].retain(|&k| k != j);
+  }
+
+  /// Returns `false` if `i` is not a successor of `i`.
+  fn leave_out(&mut self, i: usize) -> bool {
+    let self_loop = self.succ[i].contains(&i);
+    self.succ[i] = if self_loop { vec![i] } else { vec![] };
+    self_loop
+  }
+
+  fn filter(&mut self) -> bool {
+    self.no_cycle() && self.reachability()
+  }
+
+  /// The successors are assigned distinct values, therefore the assigned edges form disjoint chains and cycles.
+  fn no_cycle(&mut self) -> bool {
+    let n = self.succ.len();
+    let mandatory: Vec<bool> = (0..n).map(|i| self.mandatory(i)).collect();
+    let num_mandatory = mandatory.iter().filter(|&&m| m).count();
+    let next: Vec<Option<usize>> = (0..n).map(|i| self.next(i)).collect();
+    let mut has_pred = vec![false; n];
+    for j in next.iter().filter_map(|&j| j) {
+      has_pred[j] = true;
+    }
+    let mut on_chain = vec![false; n];
+    for start in (0..n).filter(|&s| !has_pred[s] && next[s].is_some()) {
+      let mut end = start;
+      let mut mandatory_in_chain = 1;
+      on_chain[start] = true;
+      while let Some(j) = next[end] {
+        end = j;
+        on_chain[j] = true;
+        if mandatory[j] { mandatory_in_chain += 1; }
+      }
+      if mandatory_in_chain < num_mandatory {
+        self.remove(end, start);
+      }
+    }
+    // The remaining assigned edges form cycles, and such a cycle must contain every node of the circuit.
+    if let Some(start) = (0..n).find(|&i| next[i].is_some() && !on_chain[i]) {
+      let mut in_cycle = vec![false; n];
+      let mut i = start;
+      while !in_cycle[i] {
+        in_cycle[i] = true;
+        i = next[i].unwrap();
+      }
+      for j in (0..n).filter(|&j| !in_cycle[j]) {
+        if mandatory[j] || !self.leave_out(j) {
+          return false;
+        }
+      }
+    }
+    true
+  }
+
+  /// Removes the edges between two strongly connected components of the graph without self-loops.
+  /// The nodes of the circuit are in the same component, and the nodes of the other components are not visited.
+  fn reachability(&mut self) -> bool {
+    let n = self.succ.len();
+    let graph: Vec<Vec<usize>> = (0..n)
+      .map(|i| self.succ[i].iter().cloned().filter(|&j| j != i).collect())
+      .collect();
+    let component = strongly_connected_components(&graph);
+    let mut circuit = None;
+    for i in (0..n).filter(|&i| self.mandatory(i)) {
+      match circuit {
+        None => circuit = Some(component[i]),
+        Some(c) if c != component[i] => return false,
+        _ => ()
+      }
+    }
+    for i in 0..n {
+      self.succ[i].retain(|&j| j == i || component[i] == component[j]);
+      let outside = match circuit {
+        Some(c) => component[i] != c,
+        None => false
+      };
+      if outside && !self.leave_out(i) {
+        return false;
+      }
+      if self.succ[i].is_empty() {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+
+  fn circuit_test_one(test_num: u32, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, Circuit::new, doms, before, after, delta_expected, propagate_success);
+  }
+
+  fn subcircuit_test_one(test_num: u32, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, Subcircuit::new, doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn circuit_test() {
+    let zero = (0,0).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let three = (3,3).to_interval_set();
+    let dom0_3 = (0,3).to_interval_set();
+    let dom0_5 = (0,5).to_interval_set();
+    let dom2_3 = (2,3).to_interval_set();
+    let zero_two = vec![(0,0),(2,2)].to_interval_set();
+
+    circuit_test_one(1, vec![one.clone(), two.clone(), zero.clone()], True, True, vec![], true);
+    circuit_test_one(2, vec![one.clone(), zero.clone(), zero.clone()], False, False, vec![], false);
+    circuit_test_one(3, vec![dom0_3.clone(); 4], Unknown, Unknown, vec![(0, Bound), (1, Inner), (2, Inner), (3, Bound)], true);
+    // Bounds out of the nodes and self-loops.
+    circuit_test_one(4, vec![dom0_5.clone(), dom0_5.clone()], Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+    // No-cycle: `0 -> 1` cannot be closed by `1 -> 0`.
+    circuit_test_one(5, vec![one.clone(), zero_two.clone(), dom0_3.clone(), dom0_3.clone()], Unknown, True, vec![(1, Assignment), (2, Assignment), (3, Assignment)], true);
+    // Two fixed cycles `0 -> 1 -> 0` and `2 -> 3 -> 2`.
+    circuit_test_one(6, vec![one.clone(), zero.clone(), three.clone(), two.clone()], False, False, vec![], false);
+    // Reachability: `{2, 3}` cannot reach `{0, 1}`.
+    circuit_test_one(7, vec![dom0_3.clone(), dom0_3.clone(), dom2_3.clone(), dom2_3.clone()], Unknown, False, vec![], false);
+    circuit_test_one(8, vec![zero.clone()], True, True, vec![], true);
+  }
+
+  #[test]
+  fn subcircuit_test() {
+    let zero = (0,0).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let three = (3,3).to_interval_set();
+    let dom0_2 = (0,2).to_interval_set();
+    let dom0_3 = (0,3).to_interval_set();
+    let dom2_3 = (2,3).to_interval_set();
+    let dom0_1 = (0,1).to_interval_set();
+    let one_two = (1,2).to_interval_set();
+    let zero_two = vec![(0,0),(2,2)].to_interval_set();
+    let dom3_4 = (3,4).to_interval_set();
+    let four_five = (4,5).to_interval_set();
+    let three_five = vec![(3,3),(5,5)].to_interval_set();
+
+    subcircuit_test_one(1, vec![zero.clone(), one.clone(), two.clone()], True, True, vec![], true);
+    subcircuit_test_one(2, vec![one.clone(), zero.clone(), two.clone()], True, True, vec![], true);
+    subcircuit_test_one(3, vec![dom0_3.clone(); 4], Unknown, Unknown, vec![], true);
+    // The cycle `0 -> 1 -> 0` is closed, `2` and `3` are not visited.
+    subcircuit_test_one(4, vec![one.clone(), zero.clone(), dom0_3.clone(), dom2_3.clone()], Unknown, True, vec![(2, Assignment), (3, Assignment)], true);
+    // No-cycle: `1 -> 0` closes the chain `0 -> 1` but `3` must be visited.
+    subcircuit_test_one(5, vec![one.clone(), dom0_2.clone(), dom0_3.clone(), dom0_2.clone()], Unknown, True, vec![(1, Assignment), (2, Assignment), (3, Assignment)], true);
+    // Two cycles `0 -> 1 -> 0` and `2 -> 3 -> 2`.
+    subcircuit_test_one(6, vec![one.clone(), zero.clone(), three.clone(), two.clone()], False, False, vec![], false);
+    // Reachability: `0` and `3` must be visited but they are in two components.
+    subcircuit_test_one(7, vec![one_two.clone(), zero_two.clone(), dom0_1.clone(), four_five.clone(), three_five.clone(), dom3_4.clone()], Unknown, Unknown, vec![], false);
+    // Reachability: `2` must be visited, hence `0` and `1` are not visited.
+    subcircuit_test_one(8, vec![dom0_1.clone(), dom0_1.clone(), three.clone(), dom2_3.clone()], Unknown, True, vec![(0, Assignment), (1, Assignment), (3, Assignment)], true);
+  }
+}
@@ -19,6 +19,15 @@ pub mod x_greater_y_plus_z;
 pub mod x_less_y_plus_z;
 pub mod x_eq_y_plus_z;
 pub mod x_eq_y_mul_z;
+pub mod x_eq_y_div_z;
+pub mod x_eq_y_mod_z;
+pub mod x_eq_abs_y;
+pub mod x_eq_y_pow_k;
+pub mod x_neq_y_mul_z;
+pub mod x_neq_y_div_z;
+pub mod x_neq_y_mod_z;
+pub mod x_neq_abs_y;
+pub mod x_neq_y_pow_k;
 
 use term::*;
 use gcollections::*;
@@ -27,6 +36,15 @@ pub use propagators::cmp::x_eq_y_plus_z::XEqYPlusZ;
 pub use propagators::cmp::x_less_y_plus_z::XLessYPlusZ;
 pub use propagators::cmp::x_greater_y_plus_z::XGreaterYPlusZ;
 pub use propagators::cmp::x_eq_y_mul_z::XEqYMulZ;
+pub use propagators::cmp::x_eq_y_div_z::XEqYDivZ;
+pub use propagators::cmp::x_eq_y_mod_z::XEqYModZ;
+pub use propagators::cmp::x_eq_abs_y::XEqAbsY;
+pub use propagators::cmp::x_eq_y_pow_k::XEqYPowK;
+pub use propagators::cmp::x_neq_y_mul_z::XNeqYMulZ;
+pub use propagators::cmp::x_neq_y_div_z::XNeqYDivZ;
+pub use propagators::cmp::x_neq_y_mod_z::XNeqYModZ;
+pub use propagators::cmp::x_neq_abs_y::XNeqAbsY;
+pub use propagators::cmp::x_neq_y_pow_k::XNeqYPowK;
 pub use propagators::cmp::x_less_y::XLessY;
 pub use propagators::cmp::x_eq_y::XEqY;
 pub use propagators::cmp::x_neq_y::XNeqY;
@@ -36,6 +54,9 @@ pub type XGreaterEqY<VStore> = XLessY<VStore>;
 pub type XLessEqY<VStore> = XLessY<VStore>;
 pub type XGreaterEqYPlusZ<VStore> = XGreaterYPlusZ<VStore>;
 pub type XLessEqYPlusZ<VStore> = XLessYPlusZ<VStore>;
+pub type XEqMax<VStore> = XEqY<VStore>;
+pub type XEqMin<VStore> = XEqY<VStore>;
+pub type XEqYSquare<VStore> = XEqYPowK<VStore>;
 
 pub fn x_greater_y<VStore>(x: Var<VStore>, y: Var<VStore>) -> XGreaterY<VStore> {
   XLessY::new(y, x)
@@ -75,6 +96,29 @@ pub fn x_leq_y_plus_z<VStore, Domain, Bound>(x: Var<VStore>, y: Var<VStore>, z:
   XLessYPlusZ::new(Box::new(Addition::new(x, -Bound::one())), y, z)
 }
 
+/// `x = max(ys)` with the view `Maximum`.
+pub fn x_eq_max<VStore, Domain, Bound>(x: Var<VStore>, ys: Vec<Var<VStore>>) -> XEqMax<VStore> where
+ VStore: VStoreConcept<Item=Domain> + 'static,
+ Domain: Collection<Item=Bound> + IntDomain,
+ Bound: IntBound
+{
+  XEqY::new(x, Box::new(Maximum::new(ys)))
+}
+
+/// `x = min(ys)` with the view `Minimum`.
+pub fn x_eq_min<VStore, Domain, Bound>(x: Var<VStore>, ys: Vec<Var<VStore>>) -> XEqMin<VStore> where
+ VStore: VStoreConcept<Item=Domain> + 'static,
+ Domain: Collection<Item=Bound> + IntDomain,
+ Bound: IntBound
+{
+  XEqY::new(x, Box::new(Minimum::new(ys)))
+}
+
+/// `x = y * y` with the propagator `XEqYPowK`.
+pub fn x_eq_y_square<VStore>(x: Var<VStore>, y: Var<VStore>) -> XEqYSquare<VStore> {
+  XEqYPowK::new(x, y, 2)
+}
+
 // #[cfg(test)]
 // mod test {
 //   use super::*;
@@ -0,0 +1,189 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use propagators::cmp::XNeqAbsY;
+use gcollections::*;
+use concept::*;
+
+/// Bounds of `|y|` for every `y` in `[lb..ub]`.
+pub fn abs_bounds<Bound: IntBound>(lb: Bound, ub: Bound) -> (Bound, Bound) {
+  if lb >= Bound::zero() {
+    (lb, ub)
+  }
+  else if ub <= Bound::zero() {
+    (-ub, -lb)
+  }
+  else {
+    (Bound::zero(), ::std::cmp::max(-lb, ub))
+  }
+}
+
+/// Removes from `dom` the values `v` such that `|v| < low`.
+pub fn abs_at_least<Domain, Bound>(dom: Domain, low: Bound) -> Domain where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  if low <= Bound::zero() {
+    return dom;
+  }
+  let hole = Domain::new(Bound::one() - low.clone(), low.clone() - Bound::one());
+  let dom = dom.difference(&hole);
+  // Intervals keep their hull when a hole is removed in their middle.
+  if !dom.is_empty() && dom.lower() > -low.clone() {
+    dom.shrink_left(low)
+  }
+  else if !dom.is_empty() && dom.upper() < low {
+    dom.shrink_right(-low)
+  }
+  else {
+    dom
+  }
+}
+
+// x = |y|
+#[derive(Debug)]
+pub struct XEqAbsY<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>
+}
+
+impl<VStore> XEqAbsY<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>) -> Self {
+    XEqAbsY { x: x, y: y }
+  }
+}
+
+impl<VStore> Clone for XEqAbsY<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XEqAbsY::new(self.x.bclone(), self.y.bclone())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XEqAbsY<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" = |");
+    self.y.display(model);
+    print!("|");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XEqAbsY<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XNeqAbsY::new(self.x.bclone(), self.y.bclone()))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for XEqAbsY<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: `x` and `|y|` do not overlap.
+    // True: `x` and `|y|` are singletons and equal.
+    // Unknown: Everything else.
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let (lb, ub) = abs_bounds(y.lower(), y.upper());
+    let abs_y = Domain::new(lb, ub);
+    if x.is_disjoint(&abs_y) {
+      False
+    }
+    else if x.is_singleton() && abs_y.is_singleton() {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XEqAbsY<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let (lb, ub) = abs_bounds(y.lower(), y.upper());
+    if !self.x.update(store, x.shrink_left(lb).shrink_right(ub)) {
+      return false;
+    }
+    let x = self.x.read(store);
+    let y = y.shrink_left(-x.upper()).shrink_right(x.upper());
+    self.y.update(store, abs_at_least(y, x.lower()))
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XEqAbsY<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = self.x.dependencies(FDEvent::Bound);
+    deps.append(&mut self.y.dependencies(FDEvent::Bound));
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn x_eq_abs_y_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom_10_5 = (-10,5).to_interval();
+    let dom_3_3 = (-3,3).to_interval();
+    let dom2_4 = (2,4).to_interval();
+    let dom_1_3 = (-1,3).to_interval();
+    let dom_5_5 = (-5,-5).to_interval();
+    let dom5_5 = (5,5).to_interval();
+    let dom_4_m2 = (-4,-2).to_interval();
+
+    x_eq_abs_y_test_one(1, dom0_10, dom_10_5, Unknown, Unknown, vec![], true);
+    x_eq_abs_y_test_one(2, dom0_10, dom_3_3, Unknown, Unknown, vec![(0, Bound)], true);
+    // `y` cannot be in `(-2..2)`.
+    x_eq_abs_y_test_one(3, dom2_4, dom_1_3, Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    x_eq_abs_y_test_one(4, dom0_10, dom_5_5, Unknown, True, vec![(0, Assignment)], true);
+    x_eq_abs_y_test_one(5, dom5_5, dom_5_5, True, True, vec![], true);
+    x_eq_abs_y_test_one(6, dom5_5, dom_4_m2, False, False, vec![], false);
+    x_eq_abs_y_test_one(7, dom2_4, dom_10_5, Unknown, Unknown, vec![(1, Bound)], true);
+  }
+
+  fn x_eq_abs_y_test_one(test_num: u32, x: Interval<i32>, y: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    binary_propagator_test(test_num, XEqAbsY::new, x, y, before, after, delta_expected, propagate_success);
+  }
+}
@@ -0,0 +1,257 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The division is truncated toward zero, as the operator `/` on Rust integers.
+//! The constraint is false whenever the divisor `z` is equal to zero.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use propagators::cmp::XNeqYDivZ;
+use propagators::cmp::x_eq_abs_y::{abs_bounds, abs_at_least};
+use gcollections::*;
+use concept::*;
+
+/// Bounds of the negative and of the positive parts of `dom`, when they are not empty.
+pub fn nonzero_parts<Domain, Bound>(dom: &Domain) -> Vec<(Bound, Bound)> where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  let negative = dom.clone().shrink_right(-Bound::one());
+  let positive = dom.clone().shrink_left(Bound::one());
+  vec![negative, positive].into_iter()
+    .filter(|d| !d.is_empty())
+    .map(|d| (d.lower(), d.upper()))
+    .collect()
+}
+
+fn hull<Bound: IntBound>(values: Vec<Bound>) -> (Bound, Bound) {
+  let lb = values.iter().min().expect("At least one value.").clone();
+  let ub = values.iter().max().expect("At least one value.").clone();
+  (lb, ub)
+}
+
+/// Bounds of `y / z` for `y` in `[lb..ub]` and `z` in the non-zero `parts`, the division being monotonic on each part.
+fn quotient_bounds<Bound: IntBound>(lb: Bound, ub: Bound, parts: &[(Bound, Bound)]) -> (Bound, Bound) {
+  let mut values = vec![];
+  for (c, d) in parts {
+    for y in &[lb.clone(), ub.clone()] {
+      values.push(y.clone() / c.clone());
+      values.push(y.clone() / d.clone());
+    }
+  }
+  hull(values)
+}
+
+/// Bounds of the dividends `y` such that `y / z` is in `[lb..ub]` for `z` in the non-zero `parts`.
+pub fn dividend_bounds<Bound: IntBound>(lb: Bound, ub: Bound, parts: &[(Bound, Bound)]) -> (Bound, Bound) {
+  let mut values = vec![];
+  for (c, d) in parts {
+    // `y / -w = -(y / w)`, so a negative divisor is treated as a positive one with the quotient negated.
+    let (xs, ws) =
+      if *c > Bound::zero() { ([lb.clone(), ub.clone()], [c.clone(), d.clone()]) }
+      else { ([-ub.clone(), -lb.clone()], [-d.clone(), -c.clone()]) };
+    for x in &xs {
+      for w in &ws {
+        // With `w > 0`, `y / w = x` if and only if `y` is in `[low..high]`.
+        let low = if *x > Bound::zero() { x.clone() * w.clone() } else { x.clone() * w.clone() - w.clone() + Bound::one() };
+        let high = if *x < Bound::zero() { x.clone() * w.clone() } else { x.clone() * w.clone() + w.clone() - Bound::one() };
+        values.push(low);
+        values.push(high);
+      }
+    }
+  }
+  hull(values)
+}
+
+// x = y / z
+#[derive(Debug)]
+pub struct XEqYDivZ<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>,
+  z: Var<VStore>
+}
+
+impl<VStore> XEqYDivZ<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>, z: Var<VStore>) -> Self {
+    XEqYDivZ { x: x, y: y, z: z }
+  }
+}
+
+impl<VStore> Clone for XEqYDivZ<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XEqYDivZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XEqYDivZ<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" = ");
+    self.y.display(model);
+    print!(" / ");
+    self.z.display(model);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XEqYDivZ<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XNeqYDivZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for XEqYDivZ<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: `z` can only be zero, or `x` and `y / z` do not overlap.
+    // True: `z` cannot be zero and `x` and `y / z` are singletons and equal.
+    // Unknown: Everything else.
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let z = self.z.read(store);
+    let parts = nonzero_parts(&z);
+    if parts.is_empty() {
+      return False;
+    }
+    let (lb, ub) = quotient_bounds(y.lower(), y.upper(), &parts);
+    let y_div_z = Domain::new(lb, ub);
+    if x.is_disjoint(&y_div_z) {
+      False
+    }
+    else if x.is_singleton() && y_div_z.is_singleton() && !z.contains(&Bound::zero()) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XEqYDivZ<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let z = abs_at_least(self.z.read(store), Bound::one());
+    let parts = nonzero_parts(&z);
+    if !self.z.update(store, z) {
+      return false;
+    }
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let (lb, ub) = quotient_bounds(y.lower(), y.upper(), &parts);
+    if !self.x.update(store, x.shrink_left(lb).shrink_right(ub)) {
+      return false;
+    }
+    let x = self.x.read(store);
+    let (lb, ub) = dividend_bounds(x.lower(), x.upper(), &parts);
+    if !self.y.update(store, y.shrink_left(lb).shrink_right(ub)) {
+      return false;
+    }
+    let y = self.y.read(store);
+    let mut z = self.z.read(store);
+    let (min_x, max_x) = abs_bounds(x.lower(), x.upper());
+    let (min_y, max_y) = abs_bounds(y.lower(), y.upper());
+    if min_x > Bound::zero() {
+      // `|x| * |z| <= |y|`, and `y` and `z` have the same sign if and only if `x` is positive.
+      let max_z = max_y / min_x;
+      z = z.shrink_left(-max_z.clone()).shrink_right(max_z);
+      let same_sign = x.lower() > Bound::zero();
+      if y.lower() > Bound::zero() {
+        z = if same_sign { z.shrink_left(Bound::one()) } else { z.shrink_right(-Bound::one()) };
+      }
+      else if y.upper() < Bound::zero() {
+        z = if same_sign { z.shrink_right(-Bound::one()) } else { z.shrink_left(Bound::one()) };
+      }
+    }
+    // `|y| < (max_x + 1) * |z|`.
+    let min_z = min_y / (max_x + Bound::one()) + Bound::one();
+    self.z.update(store, abs_at_least(z, min_z))
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XEqYDivZ<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = self.x.dependencies(FDEvent::Bound);
+    deps.append(&mut self.y.dependencies(FDEvent::Bound));
+    deps.append(&mut self.z.dependencies(FDEvent::Bound));
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn x_eq_y_div_z_test() {
+    let dom_10_10 = (-10,10).to_interval();
+    let dom0_10 = (0,10).to_interval();
+    let dom0_100 = (0,100).to_interval();
+    let dom_7_7 = (-7,7).to_interval();
+    let dom_2_2 = (-2,2).to_interval();
+    let dom2_3 = (2,3).to_interval();
+    let dom5_20 = (5,20).to_interval();
+    let dom_3_3 = (-3,3).to_interval();
+    let dom_3_10 = (-3,10).to_interval();
+    let dom4_6 = (4,6).to_interval();
+    let dom3_5 = (3,5).to_interval();
+    let zero = (0,0).to_interval();
+    let two = (2,2).to_interval();
+    let three = (3,3).to_interval();
+    let seven = (7,7).to_interval();
+    let minus_two = (-2,-2).to_interval();
+    let minus_three = (-3,-3).to_interval();
+    let minus_seven = (-7,-7).to_interval();
+
+    x_eq_y_div_z_test_one(1, dom_10_10, seven, two, Unknown, True, vec![(0, Assignment)], true);
+    x_eq_y_div_z_test_one(2, dom0_10, dom0_10, zero, False, False, vec![], false);
+    x_eq_y_div_z_test_one(3, dom_10_10, dom_7_7, dom_2_2, Unknown, Unknown, vec![(0, Bound)], true);
+    // `x` and `y` are positive, hence `z` too, and `z` must be larger than `5 / 4`.
+    x_eq_y_div_z_test_one(4, dom2_3, dom5_20, dom_10_10, Unknown, Unknown, vec![(2, Bound)], true);
+    x_eq_y_div_z_test_one(5, minus_three, seven, minus_two, True, True, vec![], true);
+    x_eq_y_div_z_test_one(6, three, minus_seven, dom_3_3, Unknown, True, vec![(2, Assignment)], true);
+    // `|z|` must be larger than `y`.
+    x_eq_y_div_z_test_one(7, zero, dom4_6, dom_3_10, Unknown, Unknown, vec![(2, Bound)], true);
+    x_eq_y_div_z_test_one(8, two, dom0_100, dom3_5, Unknown, Unknown, vec![(1, Bound)], true);
+  }
+
+  fn x_eq_y_div_z_test_one(test_num: u32,
+    x: Interval<i32>, y: Interval<i32>, z: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    trinary_propagator_test(test_num, XEqYDivZ::new, x, y, z, before, after, delta_expected, propagate_success);
+  }
+}
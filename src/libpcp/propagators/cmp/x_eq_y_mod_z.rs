@@ -0,0 +1,212 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The remainder has the sign of the dividend `y`, as the operator `%` on Rust integers.
+//! The constraint is false whenever the divisor `z` is equal to zero.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use propagators::cmp::XNeqYModZ;
+use propagators::cmp::x_eq_abs_y::{abs_bounds, abs_at_least};
+use propagators::cmp::x_eq_y_div_z::nonzero_parts;
+use gcollections::*;
+use concept::*;
+
+/// Bounds of `y % z` for `y` in `[lb..ub]` and `z` in the non-zero `parts`.
+fn remainder_bounds<Bound: IntBound>(lb: Bound, ub: Bound, parts: &[(Bound, Bound)]) -> (Bound, Bound) {
+  let min_z = parts.iter().map(|(c, d)| abs_bounds(c.clone(), d.clone()).0).min().expect("At least one part.");
+  let max_z = parts.iter().map(|(c, d)| abs_bounds(c.clone(), d.clone()).1).max().expect("At least one part.");
+  let (_, max_y) = abs_bounds(lb.clone(), ub.clone());
+  if max_y < min_z {
+    // `y % z = y`.
+    return (lb, ub);
+  }
+  if min_z == max_z {
+    // `y % z` is monotonic when `y / z` is constant.
+    if lb.clone() / min_z.clone() == ub.clone() / min_z.clone() {
+      return (lb % min_z.clone(), ub % min_z);
+    }
+  }
+  let max_rem = max_z - Bound::one();
+  let low = if lb >= Bound::zero() { Bound::zero() } else { ::std::cmp::max(lb, -max_rem.clone()) };
+  let high = if ub <= Bound::zero() { Bound::zero() } else { ::std::cmp::min(ub, max_rem) };
+  (low, high)
+}
+
+// x = y % z
+#[derive(Debug)]
+pub struct XEqYModZ<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>,
+  z: Var<VStore>
+}
+
+impl<VStore> XEqYModZ<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>, z: Var<VStore>) -> Self {
+    XEqYModZ { x: x, y: y, z: z }
+  }
+}
+
+impl<VStore> Clone for XEqYModZ<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XEqYModZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XEqYModZ<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" = ");
+    self.y.display(model);
+    print!(" % ");
+    self.z.display(model);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XEqYModZ<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XNeqYModZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for XEqYModZ<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: `z` can only be zero, or `x` and `y % z` do not overlap.
+    // True: `z` cannot be zero and `x` and `y % z` are singletons and equal.
+    // Unknown: Everything else.
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let z = self.z.read(store);
+    let parts = nonzero_parts(&z);
+    if parts.is_empty() {
+      return False;
+    }
+    let (lb, ub) = remainder_bounds(y.lower(), y.upper(), &parts);
+    let y_mod_z = Domain::new(lb, ub);
+    if x.is_disjoint(&y_mod_z) {
+      False
+    }
+    else if x.is_singleton() && y_mod_z.is_singleton() && !z.contains(&Bound::zero()) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XEqYModZ<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let z = abs_at_least(self.z.read(store), Bound::one());
+    let parts = nonzero_parts(&z);
+    if !self.z.update(store, z) {
+      return false;
+    }
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let (lb, ub) = remainder_bounds(y.lower(), y.upper(), &parts);
+    if !self.x.update(store, x.shrink_left(lb).shrink_right(ub)) {
+      return false;
+    }
+    // `x` has the sign of `y` and `|x| <= |y|`.
+    let x = self.x.read(store);
+    let y =
+      if x.lower() > Bound::zero() { y.shrink_left(x.lower()) }
+      else if x.upper() < Bound::zero() { y.shrink_right(x.upper()) }
+      else { y };
+    if !self.y.update(store, y) {
+      return false;
+    }
+    // `|x| < |z|`.
+    let (min_x, _) = abs_bounds(x.lower(), x.upper());
+    let z = self.z.read(store);
+    self.z.update(store, abs_at_least(z, min_x + Bound::one()))
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XEqYModZ<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = self.x.dependencies(FDEvent::Bound);
+    deps.append(&mut self.y.dependencies(FDEvent::Bound));
+    deps.append(&mut self.z.dependencies(FDEvent::Bound));
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn x_eq_y_mod_z_test() {
+    let dom_10_10 = (-10,10).to_interval();
+    let dom_20_20 = (-20,20).to_interval();
+    let dom_3_3 = (-3,3).to_interval();
+    let dom_4_6 = (-4,6).to_interval();
+    let dom5_9 = (5,9).to_interval();
+    let dom3_5 = (3,5).to_interval();
+    let dom2_4 = (2,4).to_interval();
+    let dom0_10 = (0,10).to_interval();
+    let zero = (0,0).to_interval();
+    let three = (3,3).to_interval();
+    let six = (6,6).to_interval();
+    let seven = (7,7).to_interval();
+    let minus_one = (-1,-1).to_interval();
+    let minus_three = (-3,-3).to_interval();
+    let minus_seven = (-7,-7).to_interval();
+
+    x_eq_y_mod_z_test_one(1, dom_10_10, seven, three, Unknown, True, vec![(0, Assignment)], true);
+    // `|y| < |z|` so `x = y`.
+    x_eq_y_mod_z_test_one(2, dom_10_10, dom_3_3, dom5_9, Unknown, Unknown, vec![(0, Bound)], true);
+    x_eq_y_mod_z_test_one(3, dom_10_10, dom_20_20, dom_4_6, Unknown, Unknown, vec![(0, Bound)], true);
+    x_eq_y_mod_z_test_one(4, dom3_5, dom_10_10, dom_3_3, False, False, vec![], false);
+    // `y` is positive and `|z| > 2`.
+    x_eq_y_mod_z_test_one(5, dom2_4, dom_10_10, dom0_10, Unknown, Unknown, vec![(1, Bound), (2, Bound)], true);
+    x_eq_y_mod_z_test_one(6, zero, six, zero, False, False, vec![], false);
+    x_eq_y_mod_z_test_one(7, minus_one, minus_seven, minus_three, True, True, vec![], true);
+  }
+
+  fn x_eq_y_mod_z_test_one(test_num: u32,
+    x: Interval<i32>, y: Interval<i32>, z: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    trinary_propagator_test(test_num, XEqYModZ::new, x, y, z, before, after, delta_expected, propagate_success);
+  }
+}
@@ -19,6 +19,7 @@ use model::*;
 use logic::*;
 use propagation::*;
 use propagation::events::*;
+use propagators::cmp::XNeqYMulZ;
 use gcollections::ops::*;
 use gcollections::*;
 use std::ops::*;
@@ -60,10 +61,13 @@ impl<VStore> DisplayStateful<Model> for XEqYMulZ<VStore>
   }
 }
 
-impl<VStore> NotFormula<VStore> for XEqYMulZ<VStore>
+impl<VStore, Domain, Bound> NotFormula<VStore> for XEqYMulZ<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
 {
   fn not(&self) -> Formula<VStore> {
-    unimplemented!();
+    Box::new(XNeqYMulZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()))
   }
 }
 
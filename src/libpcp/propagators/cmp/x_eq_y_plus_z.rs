@@ -66,10 +66,13 @@ impl<VStore> Subsumption<VStore> for XEqYPlusZ<VStore> where
 }
 
 impl<VStore> NotFormula<VStore> for XEqYPlusZ<VStore> where
-  VStore: Collection
+  VStore: Collection + 'static,
+  XGreaterEqYPlusZ<VStore>: NotFormula<VStore>,
+  XLessEqYPlusZ<VStore>: NotFormula<VStore>
 {
+  /// `x < y + z` or `x > y + z`.
   fn not(&self) -> Formula<VStore> {
-    unimplemented!()
+    Box::new(Disjunction::new(vec![self.geq.not(), self.leq.not()]))
   }
 }
 
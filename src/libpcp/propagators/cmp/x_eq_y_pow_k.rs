@@ -0,0 +1,328 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use propagators::cmp::XNeqYPowK;
+use propagators::cmp::x_eq_abs_y::{abs_bounds, abs_at_least};
+use gcollections::*;
+use concept::*;
+use num::pow;
+
+/// `r^k <= v` computed without overflow, with `r >= 0`.
+fn pow_leq<Bound: IntBound>(r: &Bound, k: u32, v: &Bound) -> bool {
+  let mut acc = Bound::one();
+  for _ in 0..k {
+    if r.is_zero() {
+      return Bound::zero() <= *v;
+    }
+    if acc > v.clone() / r.clone() {
+      return false;
+    }
+    acc = acc * r.clone();
+  }
+  acc <= *v
+}
+
+/// A power compared to the range `[-limit..limit]`, it is only computed when it is inside.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Power<Bound>
+{
+  Below,
+  Exact(Bound),
+  Above
+}
+
+impl<Bound: IntBound> Power<Bound>
+{
+  /// The power where `Below` and `Above` are replaced by `lower` and `upper`, which must be in the range.
+  pub fn within(self, lower: Bound, upper: Bound) -> Bound {
+    match self {
+      Power::Below => lower,
+      Power::Exact(p) => p,
+      Power::Above => upper
+    }
+  }
+}
+
+/// `r^k` computed without overflow if it is in `[-limit..limit]`, with `limit >= 0`.
+pub fn bounded_pow<Bound: IntBound>(r: Bound, k: u32, limit: &Bound) -> Power<Bound> {
+  let negative = r < Bound::zero() && k % 2 == 1;
+  let r = r.abs();
+  if pow_leq(&r, k, limit) {
+    let p = pow(r, k as usize);
+    Power::Exact(if negative { -p } else { p })
+  }
+  else if negative { Power::Below }
+  else { Power::Above }
+}
+
+/// The greatest absolute value of the bounds of `dom`.
+pub fn abs_limit<Domain, Bound>(dom: &Domain) -> Bound where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  ::std::cmp::max(dom.lower().abs(), dom.upper().abs())
+}
+
+/// Greatest `r >= 0` such that `r^k <= v`, with `v >= 0` and `k > 0`.
+pub fn floor_root<Bound: IntBound>(v: Bound, k: u32) -> Bound {
+  let mut lo = Bound::zero();
+  let mut hi = v.clone();
+  while lo < hi {
+    let mid = hi.clone() - (hi.clone() - lo.clone()) / (Bound::one() + Bound::one());
+    if pow_leq(&mid, k, &v) { lo = mid; } else { hi = mid - Bound::one(); }
+  }
+  lo
+}
+
+/// Smallest `r >= 0` such that `r^k >= v`, with `v >= 0` and `k > 0`.
+fn ceil_root<Bound: IntBound>(v: Bound, k: u32) -> Bound {
+  let r = floor_root(v.clone(), k);
+  if pow(r.clone(), k as usize) == v { r } else { r + Bound::one() }
+}
+
+/// The root of `v` rounded down, `k` is odd.
+pub fn signed_floor_root<Bound: IntBound>(v: Bound, k: u32) -> Bound {
+  if v >= Bound::zero() { floor_root(v, k) } else { -ceil_root(-v, k) }
+}
+
+/// The root of `v` rounded up, `k` is odd.
+fn signed_ceil_root<Bound: IntBound>(v: Bound, k: u32) -> Bound {
+  if v >= Bound::zero() { ceil_root(v, k) } else { -floor_root(-v, k) }
+}
+
+// x = y^k
+#[derive(Debug)]
+pub struct XEqYPowK<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>,
+  k: u32
+}
+
+impl<VStore> XEqYPowK<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>, k: u32) -> Self {
+    XEqYPowK { x: x, y: y, k: k }
+  }
+}
+
+impl<VStore> Clone for XEqYPowK<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XEqYPowK::new(self.x.bclone(), self.y.bclone(), self.k)
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XEqYPowK<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" = ");
+    self.y.display(model);
+    print!("^{}", self.k);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XEqYPowK<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XNeqYPowK::new(self.x.bclone(), self.y.bclone(), self.k))
+  }
+}
+
+impl<VStore> XEqYPowK<VStore>
+{
+  /// Bounds of `y^k` for every `y` in `[lb..ub]`, compared to `[-limit..limit]` with `bounded_pow`.
+  fn image<Bound: IntBound>(&self, lb: Bound, ub: Bound, limit: &Bound) -> (Power<Bound>, Power<Bound>) {
+    if self.k % 2 == 1 {
+      (bounded_pow(lb, self.k, limit), bounded_pow(ub, self.k, limit))
+    }
+    else {
+      let (lb, ub) = abs_bounds(lb, ub);
+      (bounded_pow(lb, self.k, limit), bounded_pow(ub, self.k, limit))
+    }
+  }
+
+  /// Bounds of `y^k` intersected with the bounds of `x`, `None` if they are disjoint.
+  fn image_within<Domain, Bound>(&self, x: &Domain, y: &Domain) -> Option<(Bound, Bound)> where
+    Domain: IntDomain<Item=Bound>,
+    Bound: IntBound
+  {
+    match self.image(y.lower(), y.upper(), &abs_limit(x)) {
+      (_, Power::Below) | (Power::Above, _) => None,
+      (lb, ub) => Some((lb.within(x.lower(), x.upper()), ub.within(x.lower(), x.upper())))
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for XEqYPowK<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: `x` and `y^k` do not overlap.
+    // True: `x` and `y^k` are singletons and equal.
+    // Unknown: Everything else.
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let (lb, ub) = match self.image_within(&x, &y) {
+      Some(bounds) => bounds,
+      None => return False
+    };
+    let y_pow_k = Domain::new(lb, ub);
+    if x.is_disjoint(&y_pow_k) {
+      False
+    }
+    // `Below` and `Above` were replaced by the bounds of `x` in `y_pow_k`, so the image is checked directly.
+    else if x.is_singleton()
+      && self.image(y.lower(), y.upper(), &abs_limit(&x)) == (Power::Exact(x.lower()), Power::Exact(x.lower()))
+    {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XEqYPowK<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let x = self.x.read(store);
+    if self.k > 0 {
+      let y = self.y.read(store);
+      let y =
+        if self.k % 2 == 1 {
+          y.shrink_left(signed_ceil_root(x.lower(), self.k))
+           .shrink_right(signed_floor_root(x.upper(), self.k))
+        }
+        else if x.upper() < Bound::zero() {
+          return false;
+        }
+        else {
+          // `|y|` is in `[low..high]`.
+          let low = ceil_root(::std::cmp::max(x.lower(), Bound::zero()), self.k);
+          let high = floor_root(x.upper(), self.k);
+          abs_at_least(y.shrink_left(-high.clone()).shrink_right(high), low)
+        };
+      if !self.y.update(store, y) {
+        return false;
+      }
+    }
+    let y = self.y.read(store);
+    match self.image_within(&x, &y) {
+      Some((lb, ub)) => self.x.update(store, x.shrink_left(lb).shrink_right(ub)),
+      None => false
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XEqYPowK<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = self.x.dependencies(FDEvent::Bound);
+    deps.append(&mut self.y.dependencies(FDEvent::Bound));
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use interval::ops::Whole;
+  use propagators::test::*;
+  use propagators::cmp::x_eq_y_square;
+
+  #[test]
+  fn roots_test() {
+    assert_eq!(floor_root(26, 3), 2);
+    assert_eq!(floor_root(27, 3), 3);
+    assert_eq!(ceil_root(26, 3), 3);
+    assert_eq!(ceil_root(0, 2), 0);
+    assert_eq!(floor_root(i32::MAX, 2), 46340);
+    assert_eq!(signed_floor_root(-26, 3), -3);
+    assert_eq!(signed_ceil_root(-26, 3), -2);
+    assert_eq!(bounded_pow(-3, 3, &30), Power::Exact(-27));
+    assert_eq!(bounded_pow(-3, 2, &30), Power::Exact(9));
+    assert_eq!(bounded_pow(50000, 2, &30), Power::Above);
+    assert_eq!(bounded_pow(-50000, 3, &i32::MAX), Power::Below);
+    assert_eq!(bounded_pow(-50000, 2, &i32::MAX), Power::Above);
+  }
+
+  #[test]
+  fn x_eq_y_pow_k_test() {
+    let dom0_100 = (0,100).to_interval();
+    let dom_3_2 = (-3,2).to_interval();
+    let dom_30_30 = (-30,30).to_interval();
+    let dom_10_10 = (-10,10).to_interval();
+    let dom2_9 = (2,9).to_interval();
+    let dom_2_3 = (-2,3).to_interval();
+    let dom_1_3 = (-1,3).to_interval();
+    let dom8_8 = (8,8).to_interval();
+    let dom_2_m2 = (-2,-2).to_interval();
+    let dom3_3 = (3,3).to_interval();
+
+    x_eq_y_pow_k_test_one(1, 3, dom_30_30, dom_10_10, Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    x_eq_y_pow_k_test_one(2, 3, dom0_100, dom_3_2, Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    x_eq_y_pow_k_test_one(3, 3, dom8_8, dom_2_3, Unknown, True, vec![(1, Assignment)], true);
+    // `|y|` is in `[2..3]`.
+    x_eq_y_pow_k_test_one(4, 2, dom2_9, dom_1_3, Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    // `8` is not a square.
+    x_eq_y_pow_k_test_one(5, 2, dom8_8, dom_10_10, Unknown, Unknown, vec![], false);
+    x_eq_y_pow_k_test_one(6, 4, dom0_100, dom_2_m2, Unknown, True, vec![(0, Assignment)], true);
+    x_eq_y_pow_k_test_one(7, 0, dom0_100, dom_10_10, Unknown, True, vec![(0, Assignment)], true);
+    x_eq_y_pow_k_test_one(8, 1, dom3_3, dom_10_10, Unknown, True, vec![(1, Assignment)], true);
+    // `50000^2` does not fit in `i32`.
+    x_eq_y_pow_k_test_one(9, 2, (0,10).to_interval(), (0,50000).to_interval(), Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    x_eq_y_pow_k_test_one(10, 3, dom_10_10, (-50000,50000).to_interval(), Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    x_eq_y_pow_k_test_one(11, 2, dom_10_10, (50000,50000).to_interval(), False, False, vec![], false);
+    // The limit of `Interval::whole()` is `i32::MAX`.
+    x_eq_y_pow_k_test_one(12, 2, Interval::whole(), (0,50000).to_interval(), Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    x_eq_y_pow_k_test_one(13, 3, Interval::whole(), (-50000,-1000).to_interval(), Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    x_eq_y_pow_k_test_one(14, 2, Interval::whole(), (50000,50000).to_interval(), False, False, vec![], false);
+  }
+
+  #[test]
+  fn x_eq_y_square_test() {
+    let dom_10_10 = (-10,10).to_interval();
+    let dom1_5 = (1,5).to_interval();
+    let dom5_9 = (5,9).to_interval();
+
+    binary_propagator_test(1, x_eq_y_square, dom5_9, dom_10_10, Unknown, Unknown, vec![(1, Bound)], true);
+    binary_propagator_test(2, x_eq_y_square, dom5_9, dom1_5, Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+  }
+
+  fn x_eq_y_pow_k_test_one(test_num: u32, k: u32, x: Interval<i32>, y: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    binary_propagator_test(test_num, |x, y| XEqYPowK::new(x, y, k), x, y, before, after, delta_expected, propagate_success);
+  }
+}
@@ -0,0 +1,138 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use trilean::SKleene;
+use model::*;
+use logic::*;
+use propagators::XEqAbsY;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+
+// x != |y|
+#[derive(Debug)]
+pub struct XNeqAbsY<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>
+}
+
+impl<VStore> XNeqAbsY<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>) -> Self {
+    XNeqAbsY { x: x, y: y }
+  }
+}
+
+impl<VStore> Clone for XNeqAbsY<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XNeqAbsY::new(self.x.bclone(), self.y.bclone())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XNeqAbsY<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" != |");
+    self.y.display(model);
+    print!("|");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XNeqAbsY<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XEqAbsY::new(self.x.bclone(), self.y.bclone()))
+  }
+}
+
+impl<VStore> Subsumption<VStore> for XNeqAbsY<VStore> where
+ VStore: Collection,
+ XEqAbsY<VStore>: Subsumption<VStore>
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    !XEqAbsY::new(self.x.bclone(), self.y.bclone()).is_subsumed(store)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XNeqAbsY<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    if y.is_singleton() {
+      self.x.update(store, x.difference(&y.lower().abs()))
+    }
+    else if x.is_singleton() && x.lower() >= Bound::zero() {
+      let y = y.difference(&x.lower()).difference(&-x.lower());
+      self.y.update(store, y)
+    }
+    else {
+      true
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XNeqAbsY<VStore> where
+ VStore: Collection,
+ XEqAbsY<VStore>: PropagatorDependencies<FDEvent>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    XEqAbsY::new(self.x.bclone(), self.y.bclone()).dependencies()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn x_neq_abs_y_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom_3_3 = (-3,3).to_interval();
+    let dom_3_5 = (-3,5).to_interval();
+    let dom3_6 = (3,6).to_interval();
+    let three = (3,3).to_interval();
+    let minus_three = (-3,-3).to_interval();
+    let minus_two = (-2,-2).to_interval();
+
+    x_neq_abs_y_test_one(1, dom0_10, dom_3_3, Unknown, Unknown, vec![], true);
+    x_neq_abs_y_test_one(2, three, dom_3_3, Unknown, True, vec![(1, Bound)], true);
+    x_neq_abs_y_test_one(3, three, dom_3_5, Unknown, Unknown, vec![(1, Bound)], true);
+    x_neq_abs_y_test_one(4, dom3_6, minus_three, Unknown, True, vec![(0, Bound)], true);
+    x_neq_abs_y_test_one(5, three, minus_three, False, False, vec![], false);
+    x_neq_abs_y_test_one(6, three, minus_two, True, True, vec![], true);
+  }
+
+  fn x_neq_abs_y_test_one(test_num: u32, x: Interval<i32>, y: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    binary_propagator_test(test_num, XNeqAbsY::new, x, y, before, after, delta_expected, propagate_success);
+  }
+}
@@ -0,0 +1,150 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint is true whenever the divisor `z` is equal to zero.
+
+use kernel::*;
+use trilean::SKleene;
+use model::*;
+use logic::*;
+use propagators::XEqYDivZ;
+use propagators::cmp::x_eq_y_div_z::dividend_bounds;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+
+// x != y / z
+#[derive(Debug)]
+pub struct XNeqYDivZ<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>,
+  z: Var<VStore>
+}
+
+impl<VStore> XNeqYDivZ<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>, z: Var<VStore>) -> Self {
+    XNeqYDivZ { x: x, y: y, z: z }
+  }
+}
+
+impl<VStore> Clone for XNeqYDivZ<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XNeqYDivZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XNeqYDivZ<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" != ");
+    self.y.display(model);
+    print!(" / ");
+    self.z.display(model);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XNeqYDivZ<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XEqYDivZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()))
+  }
+}
+
+impl<VStore> Subsumption<VStore> for XNeqYDivZ<VStore> where
+ VStore: Collection,
+ XEqYDivZ<VStore>: Subsumption<VStore>
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    !XEqYDivZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()).is_subsumed(store)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XNeqYDivZ<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let z = self.z.read(store);
+    if !z.is_singleton() || z.lower().is_zero() {
+      true
+    }
+    else if y.is_singleton() {
+      self.x.update(store, x.difference(&(y.lower() / z.lower())))
+    }
+    else if x.is_singleton() {
+      // The dividends `y` such that `y / z = x` form an interval.
+      let (lb, ub) = dividend_bounds(x.lower(), x.lower(), &[(z.lower(), z.lower())]);
+      self.y.update(store, y.difference(&Domain::new(lb, ub)))
+    }
+    else {
+      true
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XNeqYDivZ<VStore> where
+ VStore: Collection,
+ XEqYDivZ<VStore>: PropagatorDependencies<FDEvent>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    XEqYDivZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()).dependencies()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn x_neq_y_div_z_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom3_10 = (3,10).to_interval();
+    let dom6_10 = (6,10).to_interval();
+    let dom1_2 = (1,2).to_interval();
+    let zero = (0,0).to_interval();
+    let two = (2,2).to_interval();
+    let three = (3,3).to_interval();
+    let seven = (7,7).to_interval();
+
+    x_neq_y_div_z_test_one(1, dom0_10, dom0_10, dom0_10, Unknown, Unknown, vec![], true);
+    x_neq_y_div_z_test_one(2, dom3_10, seven, two, Unknown, True, vec![(0, Bound)], true);
+    x_neq_y_div_z_test_one(3, three, dom6_10, two, Unknown, True, vec![(1, Bound)], true);
+    x_neq_y_div_z_test_one(4, zero, seven, zero, True, True, vec![], true);
+    x_neq_y_div_z_test_one(5, three, seven, two, False, False, vec![], false);
+    x_neq_y_div_z_test_one(6, three, dom0_10, dom1_2, Unknown, Unknown, vec![], true);
+  }
+
+  fn x_neq_y_div_z_test_one(test_num: u32,
+    x: Interval<i32>, y: Interval<i32>, z: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    trinary_propagator_test(test_num, XNeqYDivZ::new, x, y, z, before, after, delta_expected, propagate_success);
+  }
+}
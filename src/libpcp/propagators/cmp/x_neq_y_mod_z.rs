@@ -0,0 +1,139 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint is true whenever the divisor `z` is equal to zero.
+
+use kernel::*;
+use trilean::SKleene;
+use model::*;
+use logic::*;
+use propagators::XEqYModZ;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+
+// x != y % z
+#[derive(Debug)]
+pub struct XNeqYModZ<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>,
+  z: Var<VStore>
+}
+
+impl<VStore> XNeqYModZ<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>, z: Var<VStore>) -> Self {
+    XNeqYModZ { x: x, y: y, z: z }
+  }
+}
+
+impl<VStore> Clone for XNeqYModZ<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XNeqYModZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XNeqYModZ<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" != ");
+    self.y.display(model);
+    print!(" % ");
+    self.z.display(model);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XNeqYModZ<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XEqYModZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()))
+  }
+}
+
+impl<VStore> Subsumption<VStore> for XNeqYModZ<VStore> where
+ VStore: Collection,
+ XEqYModZ<VStore>: Subsumption<VStore>
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    !XEqYModZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()).is_subsumed(store)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XNeqYModZ<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let z = self.z.read(store);
+    if y.is_singleton() && z.is_singleton() && !z.lower().is_zero() {
+      self.x.update(store, x.difference(&(y.lower() % z.lower())))
+    }
+    else {
+      true
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XNeqYModZ<VStore> where
+ VStore: Collection,
+ XEqYModZ<VStore>: PropagatorDependencies<FDEvent>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    XEqYModZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()).dependencies()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn x_neq_y_mod_z_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom1_5 = (1,5).to_interval();
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let three = (3,3).to_interval();
+    let seven = (7,7).to_interval();
+
+    x_neq_y_mod_z_test_one(1, dom0_10, dom0_10, dom1_5, Unknown, Unknown, vec![], true);
+    x_neq_y_mod_z_test_one(2, dom1_5, seven, three, Unknown, True, vec![(0, Bound)], true);
+    x_neq_y_mod_z_test_one(3, one, seven, three, False, False, vec![], false);
+    x_neq_y_mod_z_test_one(4, zero, seven, zero, True, True, vec![], true);
+    x_neq_y_mod_z_test_one(5, two, seven, three, True, True, vec![], true);
+  }
+
+  fn x_neq_y_mod_z_test_one(test_num: u32,
+    x: Interval<i32>, y: Interval<i32>, z: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    trinary_propagator_test(test_num, XNeqYModZ::new, x, y, z, before, after, delta_expected, propagate_success);
+  }
+}
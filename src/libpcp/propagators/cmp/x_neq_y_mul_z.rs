@@ -0,0 +1,164 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use trilean::SKleene;
+use model::*;
+use logic::*;
+use propagators::XEqYMulZ;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+
+/// Removes from `dom` the value `v` such that `product = factor * v`, if any.
+fn remove_factor<Domain, Bound>(dom: Domain, product: Bound, factor: Bound) -> Domain where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  if factor.is_zero() {
+    // `0 = 0 * v` holds for every `v`.
+    if product.is_zero() { Domain::empty() } else { dom }
+  }
+  else if (product.clone() % factor.clone()).is_zero() {
+    dom.difference(&(product / factor))
+  }
+  else {
+    dom
+  }
+}
+
+// x != y * z
+#[derive(Debug)]
+pub struct XNeqYMulZ<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>,
+  z: Var<VStore>
+}
+
+impl<VStore> XNeqYMulZ<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>, z: Var<VStore>) -> Self {
+    XNeqYMulZ { x: x, y: y, z: z }
+  }
+}
+
+impl<VStore> Clone for XNeqYMulZ<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XNeqYMulZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XNeqYMulZ<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" != ");
+    self.y.display(model);
+    print!(" * ");
+    self.z.display(model);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XNeqYMulZ<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XEqYMulZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()))
+  }
+}
+
+impl<VStore> Subsumption<VStore> for XNeqYMulZ<VStore> where
+ VStore: Collection,
+ XEqYMulZ<VStore>: Subsumption<VStore>
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    !XEqYMulZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()).is_subsumed(store)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XNeqYMulZ<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    let z = self.z.read(store);
+    if y.is_singleton() && z.is_singleton() {
+      self.x.update(store, x.difference(&(y.lower() * z.lower())))
+    }
+    else if x.is_singleton() && y.is_singleton() {
+      self.z.update(store, remove_factor(z, x.lower(), y.lower()))
+    }
+    else if x.is_singleton() && z.is_singleton() {
+      self.y.update(store, remove_factor(y, x.lower(), z.lower()))
+    }
+    else {
+      true
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XNeqYMulZ<VStore> where
+ VStore: Collection,
+ XEqYMulZ<VStore>: PropagatorDependencies<FDEvent>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    XEqYMulZ::new(self.x.bclone(), self.y.bclone(), self.z.bclone()).dependencies()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn x_neq_y_mul_z_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom6_10 = (6,10).to_interval();
+    let dom1_3 = (1,3).to_interval();
+    let dom3_5 = (3,5).to_interval();
+    let zero = (0,0).to_interval();
+    let two = (2,2).to_interval();
+    let three = (3,3).to_interval();
+    let six = (6,6).to_interval();
+    let seven = (7,7).to_interval();
+
+    x_neq_y_mul_z_test_one(1, dom0_10, dom0_10, dom0_10, Unknown, Unknown, vec![], true);
+    x_neq_y_mul_z_test_one(2, dom6_10, two, three, Unknown, True, vec![(0, Bound)], true);
+    x_neq_y_mul_z_test_one(3, six, two, dom3_5, Unknown, True, vec![(2, Bound)], true);
+    x_neq_y_mul_z_test_one(4, seven, two, dom1_3, True, True, vec![], true);
+    x_neq_y_mul_z_test_one(5, zero, zero, dom0_10, False, False, vec![], false);
+    x_neq_y_mul_z_test_one(6, six, two, three, False, False, vec![], false);
+    x_neq_y_mul_z_test_one(7, seven, two, three, True, True, vec![], true);
+  }
+
+  fn x_neq_y_mul_z_test_one(test_num: u32,
+    x: Interval<i32>, y: Interval<i32>, z: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    trinary_propagator_test(test_num, XNeqYMulZ::new, x, y, z, before, after, delta_expected, propagate_success);
+  }
+}
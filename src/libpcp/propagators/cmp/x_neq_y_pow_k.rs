@@ -0,0 +1,182 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use trilean::SKleene;
+use model::*;
+use logic::*;
+use propagators::XEqYPowK;
+use propagators::cmp::x_eq_y_pow_k::{floor_root, signed_floor_root, bounded_pow, abs_limit, Power};
+use num::pow;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+
+/// The integers `r` such that `r^k = v`, with `k > 0`.
+fn exact_roots<Bound: IntBound>(v: Bound, k: u32) -> Vec<Bound> {
+  let mut roots = vec![];
+  if k % 2 == 1 {
+    roots.push(signed_floor_root(v.clone(), k));
+  }
+  else if v >= Bound::zero() {
+    let r = floor_root(v.clone(), k);
+    roots.push(-r.clone());
+    roots.push(r);
+  }
+  roots.retain(|r| pow(r.clone(), k as usize) == v);
+  roots.dedup();
+  roots
+}
+
+// x != y^k
+#[derive(Debug)]
+pub struct XNeqYPowK<VStore>
+{
+  x: Var<VStore>,
+  y: Var<VStore>,
+  k: u32
+}
+
+impl<VStore> XNeqYPowK<VStore> {
+  pub fn new(x: Var<VStore>, y: Var<VStore>, k: u32) -> Self {
+    XNeqYPowK { x: x, y: y, k: k }
+  }
+}
+
+impl<VStore> Clone for XNeqYPowK<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    XNeqYPowK::new(self.x.bclone(), self.y.bclone(), self.k)
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for XNeqYPowK<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.x.display(model);
+    print!(" != ");
+    self.y.display(model);
+    print!("^{}", self.k);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for XNeqYPowK<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(XEqYPowK::new(self.x.bclone(), self.y.bclone(), self.k))
+  }
+}
+
+impl<VStore> Subsumption<VStore> for XNeqYPowK<VStore> where
+ VStore: Collection,
+ XEqYPowK<VStore>: Subsumption<VStore>
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    !XEqYPowK::new(self.x.bclone(), self.y.bclone(), self.k).is_subsumed(store)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for XNeqYPowK<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let x = self.x.read(store);
+    let y = self.y.read(store);
+    if self.k == 0 {
+      self.x.update(store, x.difference(&Bound::one()))
+    }
+    else if y.is_singleton() {
+      match bounded_pow(y.lower(), self.k, &abs_limit(&x)) {
+        Power::Exact(y_pow_k) => self.x.update(store, x.difference(&y_pow_k)),
+        _ => true
+      }
+    }
+    else if x.is_singleton() {
+      let y = exact_roots(x.lower(), self.k).into_iter()
+        .fold(y, |y, r| y.difference(&r));
+      self.y.update(store, y)
+    }
+    else {
+      true
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for XNeqYPowK<VStore> where
+ VStore: Collection,
+ XEqYPowK<VStore>: PropagatorDependencies<FDEvent>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    XEqYPowK::new(self.x.bclone(), self.y.bclone(), self.k).dependencies()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use interval::ops::Whole;
+  use propagators::test::*;
+
+  #[test]
+  fn exact_roots_test() {
+    assert_eq!(exact_roots(9, 2), vec![-3, 3]);
+    assert_eq!(exact_roots(0, 2), vec![0]);
+    assert_eq!(exact_roots(8, 2), vec![]);
+    assert_eq!(exact_roots(-8, 3), vec![-2]);
+    assert_eq!(exact_roots(-9, 2), vec![]);
+  }
+
+  #[test]
+  fn x_neq_y_pow_k_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom_3_3 = (-3,3).to_interval();
+    let dom1_8 = (1,8).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let nine = (9,9).to_interval();
+    let minus_three = (-3,-3).to_interval();
+
+    x_neq_y_pow_k_test_one(1, 2, dom0_10, dom_3_3, Unknown, Unknown, vec![], true);
+    x_neq_y_pow_k_test_one(2, 2, nine, dom_3_3, Unknown, True, vec![(1, Bound)], true);
+    x_neq_y_pow_k_test_one(3, 3, dom1_8, two, Unknown, True, vec![(0, Bound)], true);
+    x_neq_y_pow_k_test_one(4, 2, nine, minus_three, False, False, vec![], false);
+    x_neq_y_pow_k_test_one(5, 3, nine, dom_3_3, Unknown, Unknown, vec![], true);
+    x_neq_y_pow_k_test_one(6, 0, one, dom_3_3, False, False, vec![], false);
+    x_neq_y_pow_k_test_one(7, 0, dom0_10, dom_3_3, Unknown, Unknown, vec![], true);
+    // `50000^2` does not fit in `i32`.
+    x_neq_y_pow_k_test_one(8, 2, dom0_10, (0,50000).to_interval(), Unknown, Unknown, vec![], true);
+    x_neq_y_pow_k_test_one(9, 2, dom0_10, (50000,50000).to_interval(), True, True, vec![], true);
+    // The limit of `Interval::whole()` is `i32::MAX`.
+    x_neq_y_pow_k_test_one(10, 2, Interval::whole(), (0,50000).to_interval(), Unknown, Unknown, vec![], true);
+    x_neq_y_pow_k_test_one(11, 2, Interval::whole(), (50000,50000).to_interval(), True, True, vec![], true);
+    x_neq_y_pow_k_test_one(12, 3, Interval::whole(), (-50000,-50000).to_interval(), True, True, vec![], true);
+  }
+
+  fn x_neq_y_pow_k_test_one(test_num: u32, k: u32, x: Interval<i32>, y: Interval<i32>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    binary_propagator_test(test_num, |x, y| XNeqYPowK::new(x, y, k), x, y, before, after, delta_expected, propagate_success);
+  }
+}
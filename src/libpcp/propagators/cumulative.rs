@@ -12,20 +12,46 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
 use logic::*;
 use propagators::*;
+use propagation::*;
+use propagation::events::*;
 use term::*;
+use gcollections::*;
 use concept::*;
-
+use std::cmp::{min, max};
+
+/// The constraint `cumulative(s, d, r, c)` ensures that, at any time, the total amount of resources `r[i]` used by the tasks `i` running in `[s[i]..s[i] + d[i])` does not exceed the capacity `c`.
+///
+/// It can be posted as a propagator performing time-table filtering: the profile of the compulsory parts of the tasks is built and the start dates of the tasks are pushed out of the time intervals where they would overload the resource.
+/// Stronger energetic reasoning can be selected with `with_filtering` (see `CumulativeFiltering`).
+/// Alternatively, `join` decomposes the constraint into primitive propagators.
+#[derive(Debug)]
 pub struct Cumulative<VStore>
 {
   starts: Vec<Var<VStore>>,
   durations: Vec<Var<VStore>>,
   resources: Vec<Var<VStore>>,
   capacity: Var<VStore>,
+  filtering: CumulativeFiltering,
   intermediate: Vec<Vec<usize>>, // Given intermediate[j][i], if i left-overlap j, then it contains the number of resources used by i.
 }
 
+/// Filtering algorithms of the `Cumulative` propagator, each level includes the filtering of the previous ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CumulativeFiltering {
+  /// Time-table filtering on the profile of the compulsory parts, in `O(n^2)`.
+  TimeTable,
+  /// Edge-finding based on Θ-Λ-trees (see `edge_finding`), in `O(kn log n)` where `k` is the number of distinct resource requirements.
+  EdgeFinding,
+  /// Time-table edge-finding (see `time_table_edge_finding`), in `O(n^3)`.
+  TimeTableEdgeFinding
+}
+
 impl<VStore> Cumulative<VStore>
 {
   pub fn new(starts: Vec<Var<VStore>>, durations: Vec<Var<VStore>>,
@@ -39,9 +65,16 @@ impl<VStore> Cumulative<VStore>
       durations: durations,
       resources: resources,
       capacity: capacity,
+      filtering: CumulativeFiltering::TimeTable,
       intermediate: vec![]
     }
   }
+
+  /// Selects the filtering algorithms used when the constraint is posted as a propagator.
+  pub fn with_filtering(mut self, filtering: CumulativeFiltering) -> Self {
+    self.filtering = filtering;
+    self
+  }
 }
 
 impl<VStore, Domain, Bound> Cumulative<VStore> where
@@ -120,18 +153,493 @@ impl<VStore, Domain, Bound> Cumulative<VStore> where
   }
 }
 
+impl<VStore> Clone for Cumulative<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Cumulative {
+      starts: self.starts.iter().map(|v| v.bclone()).collect(),
+      durations: self.durations.iter().map(|v| v.bclone()).collect(),
+      resources: self.resources.iter().map(|v| v.bclone()).collect(),
+      capacity: self.capacity.bclone(),
+      filtering: self.filtering,
+      intermediate: self.intermediate.clone()
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Cumulative<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("cumulative(");
+    model.display_global("s", &self.starts);
+    print!(", ");
+    model.display_global("d", &self.durations);
+    print!(", ");
+    model.display_global("r", &self.resources);
+    print!(", ");
+    self.capacity.display(model);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Cumulative<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// An overload is a sum of the resources of the reified tasks running at a given time, `Reified` would need Boolean variables which `not` cannot allocate, so the negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Negation::new(Box::new(self.clone())))
+  }
+}
+
+/// Bounds of a task read from the variables store: the earliest and latest start dates, and the minimal duration and resource.
+#[derive(Clone, Debug)]
+pub struct TaskBounds<Bound>
+{
+  pub est: Bound,
+  pub lst: Bound,
+  pub dur: Bound,
+  pub res: Bound
+}
+
+impl<Bound> TaskBounds<Bound> where
+  Bound: IntBound
+{
+  /// Earliest completion time.
+  pub fn ect(&self) -> Bound {
+    self.est.clone() + self.dur.clone()
+  }
+
+  /// Latest completion time.
+  pub fn lct(&self) -> Bound {
+    self.lst.clone() + self.dur.clone()
+  }
+
+  /// The compulsory part `[lst..ect)` is the time interval in which the task is running in every schedule.
+  pub fn has_compulsory_part(&self) -> bool {
+    self.lst < self.ect() && self.res > Bound::zero()
+  }
+
+  /// Mirrors the task around the origin: the earliest start date of the mirrored task is the opposite of the latest completion time.
+  /// Filtering the earliest start date of the mirrored task filters the latest completion time of the task.
+  pub fn mirror(&self) -> Self {
+    TaskBounds {
+      est: -self.lct(),
+      lst: -self.ect(),
+      dur: self.dur.clone(),
+      res: self.res.clone()
+    }
+  }
+}
+
+/// A segment `[start..end)` of the resource profile where `height` resources are used.
+#[derive(Clone, Debug)]
+pub struct ProfileSegment<Bound>
+{
+  pub start: Bound,
+  pub end: Bound,
+  pub height: Bound
+}
+
+/// Profile of the compulsory parts of the tasks, the segments are sorted and disjoint.
+/// The boundaries of the compulsory parts are boundaries of segments, so a segment is either included in the compulsory part of a task or disjoint from it.
+pub fn compulsory_profile<Bound>(tasks: &[TaskBounds<Bound>]) -> Vec<ProfileSegment<Bound>> where
+  Bound: IntBound
+{
+  let mut events = vec![];
+  for t in tasks.iter().filter(|t| t.has_compulsory_part()) {
+    events.push((t.lst.clone(), t.res.clone()));
+    events.push((t.ect(), -t.res.clone()));
+  }
+  events.sort();
+  let mut profile = vec![];
+  let mut height = Bound::zero();
+  let mut i = 0;
+  while i < events.len() {
+    let start = events[i].0.clone();
+    while i < events.len() && events[i].0 == start {
+      height = height + events[i].1.clone();
+      i += 1;
+    }
+    if i < events.len() && height > Bound::zero() {
+      profile.push(ProfileSegment {
+        start: start,
+        end: events[i].0.clone(),
+        height: height.clone()
+      });
+    }
+  }
+  profile
+}
+
+/// Tasks with a positive energy, the other ones do not interact with the resource.
+fn energetic_tasks<Bound>(tasks: &[TaskBounds<Bound>]) -> Vec<usize> where
+  Bound: IntBound
+{
+  (0..tasks.len())
+    .filter(|&i| tasks[i].dur > Bound::zero() && tasks[i].res > Bound::zero())
+    .collect()
+}
+
+fn exceeds<Bound: IntBound>(value: Option<Bound>, bound: &Bound) -> bool {
+  match value {
+    Some(v) => v > *bound,
+    None => false
+  }
+}
+
+fn ceil_div<Bound: IntBound>(a: Bound, b: Bound) -> Bound {
+  -(-a).div_floor(&b)
+}
+
+/// Length of the intersection of `[s..e)` and `[a..b)`.
+fn overlap<Bound: IntBound>(s: &Bound, e: &Bound, a: &Bound, b: &Bound) -> Bound {
+  let len = min(e.clone(), b.clone()) - max(s.clone(), a.clone());
+  max(len, Bound::zero())
+}
+
+/// Edge-finding algorithm described in `Edge Finding Filtering Algorithm for Discrete Cumulative Resources in O(kn log n)`, Vilím, 2009.
+/// The detection phase finds, with a Θ-Λ-tree, the tasks `i` ending after all the tasks of a set `Θ` because `Θ ∪ {i}` cannot be scheduled before `lct(Θ)`.
+/// The adjustment phase computes, for each distinct resource requirement `c`, the earliest start date of a task using `c` resources after such a set.
+/// It returns the new earliest start dates of the tasks, or `None` if the resource is overloaded.
+pub fn edge_finding<Bound>(tasks: &[TaskBounds<Bound>], capacity: &Bound) -> Option<Vec<Bound>> where
+  Bound: IntBound
+{
+  let mut est: Vec<Bound> = tasks.iter().map(|t| t.est.clone()).collect();
+  let active = energetic_tasks(tasks);
+  if active.iter().any(|&i| tasks[i].res > *capacity) {
+    return None;
+  }
+  let energy = |i: usize| tasks[i].dur.clone() * tasks[i].res.clone();
+  let mut by_est = active.clone();
+  by_est.sort_by_key(|&i| tasks[i].est.clone());
+  let mut pos = vec![0; tasks.len()];
+  for (p, &i) in by_est.iter().enumerate() {
+    pos[i] = p;
+  }
+  let mut by_lct = active.clone();
+  by_lct.sort_by_key(|&i| tasks[i].lct());
+
+  // Detection: `prec[i] = Some(k)` if `i` ends after all the tasks in `by_lct[0..k+1]`.
+  let mut tree = ThetaLambdaTree::new(active.len());
+  for &i in &active {
+    tree.insert(pos[i], energy(i), capacity.clone() * tasks[i].est.clone() + energy(i));
+  }
+  let mut prec = vec![None; tasks.len()];
+  for (k, &j) in by_lct.iter().enumerate().rev() {
+    let bound = capacity.clone() * tasks[j].lct();
+    if exceeds(tree.envelope(), &bound) {
+      return None;
+    }
+    while exceeds(tree.gray_envelope(), &bound) {
+      let i = by_est[tree.responsible_gray()];
+      prec[i] = Some(k);
+      tree.remove(pos[i]);
+    }
+    tree.gray(pos[j]);
+  }
+
+  // Adjustment: `updates[k]` is the earliest start date of a task using `c` resources and ending after all the tasks in `by_lct[0..k+1]`.
+  let mut requirements: Vec<Bound> = active.iter()
+    .filter(|&&i| prec[i].is_some())
+    .map(|&i| tasks[i].res.clone())
+    .collect();
+  requirements.sort();
+  requirements.dedup();
+  for c in requirements {
+    let slack = capacity.clone() - c.clone();
+    let mut theta = ThetaLambdaTree::new(active.len());
+    let mut theta_c = ThetaLambdaTree::new(active.len());
+    let mut update: Option<Bound> = None;
+    let mut updates = vec![];
+    for &j in &by_lct {
+      theta.insert(pos[j], energy(j), capacity.clone() * tasks[j].est.clone() + energy(j));
+      theta_c.insert(pos[j], energy(j), slack.clone() * tasks[j].est.clone() + energy(j));
+      let bound = slack.clone() * tasks[j].lct();
+      // The tasks starting after `l` use more than `C - c` resources on average until `lct(j)`.
+      if let Some(l) = theta_c.rightmost_above(&bound) {
+        let envelope = theta.envelope_upto(l).unwrap();
+        let diff = ceil_div(envelope - bound, c.clone());
+        update = Some(update.map_or(diff.clone(), |u| max(u, diff)));
+      }
+      updates.push(update.clone());
+    }
+    for &i in &active {
+      if tasks[i].res == c {
+        if let Some(u) = prec[i].and_then(|k| updates[k].clone()) {
+          est[i] = max(est[i].clone(), u);
+        }
+      }
+    }
+  }
+  Some(est)
+}
+
+/// Energy of the profile in the time window `[a..b)`.
+fn profile_energy<Bound>(profile: &[ProfileSegment<Bound>], a: &Bound, b: &Bound) -> Bound where
+  Bound: IntBound
+{
+  profile.iter()
+    .map(|seg| seg.height.clone() * overlap(&seg.start, &seg.end, a, b))
+    .fold(Bound::zero(), |acc, e| acc + e)
+}
+
+/// Time-table edge-finding described in `Explaining Time-Table-Edge-Finding Propagation for the Cumulative Resource Constraint`, Schutt et al., 2013.
+/// For each time window `[a..b)` delimited by the earliest start date and the latest completion time of two tasks, the energy required in the window is the energy of the compulsory parts in `profile` plus the energy of the free parts of the tasks included in the window.
+/// A task `i` that would use more than the remaining energy when starting at its earliest start date is pushed to the right, its own compulsory part being removed from the profile.
+/// It returns the new earliest start dates of the tasks, or `None` if a window is overloaded.
+pub fn time_table_edge_finding<Bound>(tasks: &[TaskBounds<Bound>], profile: &[ProfileSegment<Bound>],
+  capacity: &Bound) -> Option<Vec<Bound>> where
+  Bound: IntBound
+{
+  let mut est: Vec<Bound> = tasks.iter().map(|t| t.est.clone()).collect();
+  let active = energetic_tasks(tasks);
+  let mut begins: Vec<Bound> = active.iter().map(|&i| tasks[i].est.clone()).collect();
+  begins.sort();
+  begins.dedup();
+  let mut ends: Vec<Bound> = active.iter().map(|&i| tasks[i].lct()).collect();
+  ends.sort();
+  ends.dedup();
+  for a in &begins {
+    for b in ends.iter().filter(|&b| b > a) {
+      let inside = |t: &TaskBounds<Bound>| t.est >= *a && t.lct() <= *b;
+      let window = capacity.clone() * (b.clone() - a.clone());
+      let tt = profile_energy(profile, a, b);
+      let free = active.iter()
+        .map(|&i| &tasks[i])
+        .filter(|t| inside(t))
+        .map(|t| t.res.clone() * (t.dur.clone() - overlap(&t.lst, &t.ect(), &t.est, &t.lct())))
+        .fold(Bound::zero(), |acc, e| acc + e);
+      if tt.clone() + free.clone() > window {
+        return None;
+      }
+      for &i in &active {
+        let t = &tasks[i];
+        if inside(t) {
+          continue;
+        }
+        let required = overlap(&t.est, &t.ect(), a, b);
+        let own_tt = t.res.clone() * overlap(&t.lst, &t.ect(), a, b);
+        let reserve = window.clone() - (tt.clone() - own_tt) - free.clone();
+        let avail = reserve.div_floor(&t.res);
+        if required > avail {
+          est[i] = max(est[i].clone(), b.clone() - avail);
+        }
+      }
+    }
+  }
+  Some(est)
+}
+
+impl<VStore, Domain, Bound> Cumulative<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn task_bounds(&self, vstore: &VStore) -> Vec<TaskBounds<Bound>> {
+    (0..self.starts.len()).map(|i| {
+      let s = self.starts[i].read(vstore);
+      TaskBounds {
+        est: s.lower(),
+        lst: s.upper(),
+        dur: self.durations[i].read(vstore).lower(),
+        res: self.resources[i].read(vstore).lower()
+      }
+    }).collect()
+  }
+
+  fn all_assigned(&self, vstore: &VStore) -> bool {
+    self.starts.iter()
+      .chain(self.durations.iter())
+      .chain(self.resources.iter())
+      .all(|v| v.read(vstore).is_singleton())
+  }
+
+  /// Height of the segment `seg` without the task `t`.
+  fn height_without(seg: &ProfileSegment<Bound>, t: &TaskBounds<Bound>) -> Bound {
+    if t.has_compulsory_part() && t.lst <= seg.start && seg.end <= t.ect() {
+      seg.height.clone() - t.res.clone()
+    }
+    else {
+      seg.height.clone()
+    }
+  }
+
+  /// Push the earliest start date of `t` after the segments in which it would overload the resource.
+  fn filter_est(t: &TaskBounds<Bound>, profile: &[ProfileSegment<Bound>], capacity: &Bound) -> Bound {
+    let mut est = t.est.clone();
+    for seg in profile {
+      if seg.start >= est.clone() + t.dur.clone() {
+        break;
+      }
+      if seg.end > est && Self::height_without(seg, t) + t.res.clone() > *capacity {
+        est = seg.end.clone();
+      }
+    }
+    est
+  }
+
+  /// Symmetric to `filter_est`, it pulls the latest start date of `t` before the overloaded segments.
+  fn filter_lst(t: &TaskBounds<Bound>, profile: &[ProfileSegment<Bound>], capacity: &Bound) -> Bound {
+    let mut lct = t.lct();
+    for seg in profile.iter().rev() {
+      if seg.end <= lct.clone() - t.dur.clone() {
+        break;
+      }
+      if seg.start < lct && Self::height_without(seg, t) + t.res.clone() > *capacity {
+        lct = seg.start.clone();
+      }
+    }
+    lct - t.dur.clone()
+  }
+
+  /// The resources of the tasks that necessarily last, a task with a null duration does not use its resource.
+  fn required_resources(tasks: &[TaskBounds<Bound>]) -> Vec<Bound> {
+    tasks.iter()
+      .filter(|t| t.dur > Bound::zero())
+      .map(|t| t.res.clone())
+      .collect()
+  }
+
+  /// Maximal amount of resources the task `t` can use without overloading the profile in `[lst..ect)`.
+  fn filter_resource(t: &TaskBounds<Bound>, profile: &[ProfileSegment<Bound>], capacity: &Bound) -> Bound {
+    let mut max_height = Bound::zero();
+    for seg in profile {
+      if t.dur > Bound::zero() && seg.start < t.ect() && t.lst < seg.end {
+        max_height = max(max_height, Self::height_without(seg, t));
+      }
+    }
+    capacity.clone() - max_height
+  }
+
+  /// New earliest start dates of the tasks obtained by the energetic reasoning selected.
+  fn energetic_est(&self, tasks: &[TaskBounds<Bound>], capacity: &Bound) -> Option<Vec<Bound>> {
+    let mut est = edge_finding(tasks, capacity)?;
+    if self.filtering == CumulativeFiltering::TimeTableEdgeFinding {
+      let tt_est = time_table_edge_finding(tasks, &compulsory_profile(tasks), capacity)?;
+      est = est.into_iter().zip(tt_est).map(|(e1, e2)| max(e1, e2)).collect();
+    }
+    Some(est)
+  }
+
+  fn propagate_energetic(&mut self, vstore: &mut VStore) -> bool {
+    let tasks = self.task_bounds(vstore);
+    let capacity = self.capacity.read(vstore).upper();
+    let est = match self.energetic_est(&tasks, &capacity) {
+      Some(est) => est,
+      None => return false
+    };
+    let mirrored: Vec<_> = tasks.iter().map(TaskBounds::mirror).collect();
+    let mirrored_est = match self.energetic_est(&mirrored, &capacity) {
+      Some(est) => est,
+      None => return false
+    };
+    for (i, t) in tasks.iter().enumerate() {
+      let s = self.starts[i].read(vstore);
+      let lst = -mirrored_est[i].clone() - t.dur.clone();
+      if !self.starts[i].update(vstore, s.shrink_left(est[i].clone()).shrink_right(lst)) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Cumulative<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, vstore: &VStore) -> SKleene {
+    // False: the compulsory parts overload the resource.
+    // True: all the tasks are assigned and do not overload the resource.
+    // Unknown: Everything else.
+    let tasks = self.task_bounds(vstore);
+    let capacity = self.capacity.read(vstore);
+    let max_height = compulsory_profile(&tasks).into_iter()
+      .map(|seg| seg.height)
+      .chain(Self::required_resources(&tasks))
+      .fold(Bound::zero(), max);
+    if max_height > capacity.upper() {
+      False
+    }
+    else if max_height <= capacity.lower() && self.all_assigned(vstore) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Cumulative<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, vstore: &mut VStore) -> bool {
+    let tasks = self.task_bounds(vstore);
+    let profile = compulsory_profile(&tasks);
+    let c = self.capacity.read(vstore);
+    let max_height = profile.iter()
+      .map(|seg| seg.height.clone())
+      .chain(Self::required_resources(&tasks))
+      .fold(Bound::zero(), max);
+    if !self.capacity.update(vstore, c.shrink_left(max_height)) {
+      return false;
+    }
+    let capacity = self.capacity.read(vstore).upper();
+    for (i, t) in tasks.iter().enumerate() {
+      if t.dur <= Bound::zero() {
+        continue;
+      }
+      let r = self.resources[i].read(vstore);
+      let r_ub = Self::filter_resource(t, &profile, &capacity);
+      if !self.resources[i].update(vstore, r.shrink_right(r_ub)) {
+        return false;
+      }
+      if t.res > Bound::zero() {
+        let s = self.starts[i].read(vstore);
+        let est = Self::filter_est(t, &profile, &capacity);
+        let lst = Self::filter_lst(t, &profile, &capacity);
+        if !self.starts[i].update(vstore, s.shrink_left(est).shrink_right(lst)) {
+          return false;
+        }
+      }
+    }
+    match self.filtering {
+      CumulativeFiltering::TimeTable => true,
+      _ => self.propagate_energetic(vstore)
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Cumulative<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.starts.iter()
+      .chain(self.durations.iter())
+      .chain(self.resources.iter())
+      .chain(Some(&self.capacity))
+      .flat_map(|v| v.dependencies(FDEvent::Bound))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
-  use kernel::*;
   use trilean::SKleene;
-  use trilean::SKleene::*;
   use variable::VStoreCopy;
   use propagation::CStoreFD;
   use interval::interval::*;
   use interval::ops::Range;
   use gcollections::ops::*;
-  use model::*;
   use propagation::ops::Subsumption;
 
   type Dom = Interval<i32>;
@@ -179,7 +687,7 @@ mod test {
     }
 
     fn instantiate(self, model: &mut Model, vstore: &mut VStoreFD,
-      cstore: &mut CStoreFD<VStoreFD>, constant: bool)
+      cstore: &mut CStoreFD<VStoreFD>, constant: bool, filtering: Option<CumulativeFiltering>)
     {
       model.open_group("s");
       let starts = self.starts.into_iter()
@@ -197,26 +705,48 @@ mod test {
       model.register_var(capacity.index(), String::from("c"));
 
       let mut cumulative = Cumulative::new(starts, durations, resources, capacity);
-      cumulative.join(vstore, cstore);
+      match filtering {
+        None => cumulative.join(vstore, cstore),
+        Some(filtering) => { cstore.alloc(Box::new(cumulative.with_filtering(filtering))); }
+      }
     }
 
     // The boolean "constant" indicates if we transform the singleton domains into constant terms or not.
     fn test(self, test_num: usize, before: SKleene, after: SKleene, constant: bool) {
+      self.test_with(test_num, before, after, constant, None);
+    }
+
+    // Same as `test` but with the time-table propagator instead of the decomposition.
+    fn test_propagator(self, test_num: usize, before: SKleene, after: SKleene, constant: bool) {
+      self.test_with(test_num, before, after, constant, Some(CumulativeFiltering::TimeTable));
+    }
+
+    // Returns the variables store after propagation, the start dates are the first variables.
+    fn test_with(self, test_num: usize, before: SKleene, after: SKleene,
+      constant: bool, filtering: Option<CumulativeFiltering>) -> VStoreFD
+    {
       println!("Test number {}", test_num);
       let mut vstore = VStoreFD::empty();
       let mut cstore = CStoreFD::empty();
       let mut model = Model::new();
-      self.instantiate(&mut model, &mut vstore, &mut cstore, constant);
+      self.instantiate(&mut model, &mut vstore, &mut cstore, constant, filtering);
       cstore.display(&(model, vstore.clone()));
       assert_eq!(cstore.is_subsumed(&vstore), before);
       assert_eq!(cstore.consistency(&mut vstore), after);
       assert_eq!(cstore.is_subsumed(&vstore), after);
+      vstore
     }
 
     fn test_assignment(self, test_num: usize, expected: SKleene, constant: bool) {
       // Unknown because cumulative introduces new variables not fixed.
       self.test(test_num, Unknown, expected, constant);
     }
+
+    fn task_bounds(tasks: Vec<(i32, i32, i32, i32)>) -> Vec<TaskBounds<i32>> {
+      tasks.into_iter()
+        .map(|(est, lst, dur, res)| TaskBounds { est: est, lst: lst, dur: dur, res: res })
+        .collect()
+    }
   }
 
   #[test]
@@ -295,4 +825,123 @@ mod test {
     test.starts[2] = Interval::new(4,5);
     test.test(3, Unknown, Unknown, constant);
   }
+
+  #[test]
+  fn time_table_assignment_test() {
+    for &constant in &[false, true] {
+      CumulativeTest::new_assignment(vec![0,0], vec![0,0], vec![1,1], 1)
+        .test_propagator(1, True, True, constant);
+      CumulativeTest::new_assignment(vec![0,1,4], vec![3,4,2], vec![1,2,2], 3)
+        .test_propagator(2, False, False, constant);
+      CumulativeTest::new_assignment(vec![0,1,5], vec![3,4,2], vec![1,2,2], 3)
+        .test_propagator(3, True, True, constant);
+      CumulativeTest::new_assignment(vec![0,1,4], vec![3,4,2], vec![1,2,2], 4)
+        .test_propagator(4, True, True, constant);
+      CumulativeTest::new_assignment(vec![0], vec![1], vec![2], 1)
+        .test_propagator(5, False, False, constant);
+      // A task with a null duration does not use its resource.
+      CumulativeTest::new_assignment(vec![0], vec![0], vec![2], 1)
+        .test_propagator(6, True, True, constant);
+      CumulativeTest::new_assignment(vec![0,0], vec![0,2], vec![3,1], 1)
+        .test_propagator(7, True, True, constant);
+    }
+  }
+
+  #[test]
+  fn time_table_test() {
+    let mut test = CumulativeTest::new_assignment(
+      vec![0,1,4], vec![3,4,2], vec![1,2,2], 3);
+    test.starts[0] = Interval::new(0,4);
+    test.test_propagator(1, False, False, false);
+
+    let mut test = CumulativeTest::new_assignment(
+      vec![0,1,4], vec![3,4,2], vec![1,2,2], 3);
+    test.starts[1] = Interval::new(0,1);
+    test.test_propagator(2, Unknown, True, false);
+
+    // The task 3 is pushed after the task 2, contrarily to the decomposition.
+    let mut test = CumulativeTest::new_assignment(
+      vec![0,1,4], vec![3,4,2], vec![1,2,2], 3);
+    test.starts[2] = Interval::new(4,5);
+    test.test_propagator(3, Unknown, True, false);
+
+    // The task 2 must be scheduled before or after the task 1 which is fixed in [2..5).
+    let mut test = CumulativeTest::new_assignment(
+      vec![2,0], vec![3,2], vec![1,1], 1);
+    test.starts[1] = Interval::new(1,8);
+    test.test_propagator(4, Unknown, Unknown, false);
+
+    let mut test = CumulativeTest::new_assignment(
+      vec![2,0], vec![3,2], vec![1,1], 1);
+    test.starts[1] = Interval::new(1,5);
+    test.test_propagator(5, Unknown, True, false);
+
+    // The capacity is at least the height of the profile.
+    let mut test = CumulativeTest::new_assignment(
+      vec![0,1], vec![3,3], vec![1,2], 3);
+    test.capacity = Interval::new(0,3);
+    test.test_propagator(6, Unknown, True, false);
+
+    // The resource of a task is bounded by the remaining capacity.
+    let mut test = CumulativeTest::new_assignment(
+      vec![0,1], vec![3,3], vec![1,2], 3);
+    test.resources[1] = Interval::new(2,5);
+    test.test_propagator(7, Unknown, True, false);
+  }
+
+  #[test]
+  fn edge_finding_test() {
+    // The tasks 1 and 2 fill the resource in [0..10).
+    let tasks = CumulativeTest::task_bounds(vec![(0,5,5,2), (0,5,5,2), (0,18,2,1)]);
+    assert_eq!(edge_finding(&tasks, &2), Some(vec![0,0,10]));
+    let mirrored: Vec<_> = tasks.iter().map(TaskBounds::mirror).collect();
+    assert_eq!(edge_finding(&mirrored, &2), Some(vec![-10,-10,-20]));
+
+    // Three tasks of duration 2 cannot be scheduled in [0..4).
+    let tasks = CumulativeTest::task_bounds(vec![(0,2,2,1), (0,2,2,1), (0,2,2,1)]);
+    assert_eq!(edge_finding(&tasks, &1), None);
+    assert_eq!(edge_finding(&tasks, &2), Some(vec![0,0,0]));
+
+    // The tasks with no energy are ignored.
+    let tasks = CumulativeTest::task_bounds(vec![(0,5,5,2), (0,5,5,2), (0,18,0,1), (0,18,2,0)]);
+    assert_eq!(edge_finding(&tasks, &2), Some(vec![0,0,0,0]));
+  }
+
+  #[test]
+  fn time_table_edge_finding_test() {
+    // The task 2 has a compulsory part in [2..5), the task 1 is pushed after the window [0..4).
+    let tasks = CumulativeTest::task_bounds(vec![(0,3,3,2), (1,2,4,1), (2,3,1,2)]);
+    assert_eq!(edge_finding(&tasks, &2), Some(vec![0,1,2]));
+    let profile = compulsory_profile(&tasks);
+    assert_eq!(time_table_edge_finding(&tasks, &profile, &2), Some(vec![4,1,2]));
+
+    let tasks = CumulativeTest::task_bounds(vec![(0,2,2,1), (0,2,2,1), (0,2,2,1)]);
+    let profile = compulsory_profile(&tasks);
+    assert_eq!(time_table_edge_finding(&tasks, &profile, &1), None);
+  }
+
+  #[test]
+  fn energetic_propagator_test() {
+    // The overload of [0..4) is not detected by the time-table filtering.
+    let overload = || {
+      let mut test = CumulativeTest::new_assignment(vec![0,0,0], vec![2,2,2], vec![1,1,1], 1);
+      test.starts = vec![Interval::new(0,2); 3];
+      test
+    };
+    overload().test_propagator(1, Unknown, Unknown, true);
+    let mut vstore = VStoreFD::empty();
+    let mut cstore = CStoreFD::empty();
+    overload().instantiate(&mut Model::new(), &mut vstore, &mut cstore, true, Some(CumulativeFiltering::EdgeFinding));
+    assert_eq!(cstore.consistency(&mut vstore), False);
+
+    // The task 3 is pushed after the tasks 1 and 2.
+    for &filtering in &[CumulativeFiltering::EdgeFinding, CumulativeFiltering::TimeTableEdgeFinding] {
+      let mut test = CumulativeTest::new_assignment(vec![0,0,0], vec![5,5,2], vec![2,2,1], 2);
+      test.starts[0] = Interval::new(0,5);
+      test.starts[1] = Interval::new(0,5);
+      test.starts[2] = Interval::new(0,18);
+      let vstore = test.test_with(3, Unknown, Unknown, true, Some(filtering));
+      assert_eq!(vstore[2], Interval::new(10,18));
+    }
+  }
 }
@@ -0,0 +1,468 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint `diffn(x, y, w, h)` ensures that the rectangles `i` with origin `(x[i], y[i])`, width `w[i]` and height `h[i]` do not overlap.
+//! The rectangles with a width or a height smaller or equal to zero do not overlap any other rectangle.
+//!
+//! Two kinds of filtering are performed on the bounds of the variables:
+//!  * Pairwise disjunctive reasoning: a rectangle is on the left, on the right, below or above another one, when only one of these relations is still possible it is enforced.
+//!  * Sweep, as described in `Sweep as a Generic Pruning Technique Applied to the Non-Overlapping Rectangles Constraint`, Beldiceanu and Carlsson, 2001.
+//!    The compulsory part of a rectangle `j` forbids a region to the origin of a rectangle `i`, and the bounds of the origin of `i` are moved to the first positions, in each direction, that are not covered by the forbidden regions.
+//!
+//! The constraint can also be posted with `with_cumulative` to run a `Cumulative` propagator as a redundant projection on each axis.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagators::cumulative::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use term::Constant;
+use propagators::{XLessYPlusZ, x_greater_y};
+
+/// A region of the plane given by an inclusive interval on each axis.
+type Region<Bound> = ((Bound, Bound), (Bound, Bound));
+
+/// The bounds of the origin and the minimal sizes of a rectangle.
+#[derive(Clone, Debug)]
+struct RectBounds<Bound>
+{
+  x: (Bound, Bound),
+  y: (Bound, Bound),
+  w: Bound,
+  h: Bound
+}
+
+impl<Bound: IntBound> RectBounds<Bound>
+{
+  fn has_area(&self) -> bool {
+    self.w > Bound::zero() && self.h > Bound::zero()
+  }
+
+  /// The region, as two inclusive intervals, where the origin of `self` cannot be without overlapping the compulsory part of `other`.
+  fn forbidden_by(&self, other: &RectBounds<Bound>) -> Option<Region<Bound>> {
+    if !self.has_area() || !other.has_area() {
+      return None;
+    }
+    let x = (other.x.1.clone() - self.w.clone() + Bound::one(), other.x.0.clone() + other.w.clone() - Bound::one());
+    let y = (other.y.1.clone() - self.h.clone() + Bound::one(), other.y.0.clone() + other.h.clone() - Bound::one());
+    if x.0 <= x.1 && y.0 <= y.1 { Some((x, y)) } else { None }
+  }
+
+  fn transpose(&self) -> Self {
+    RectBounds { x: self.y.clone(), y: self.x.clone(), w: self.h.clone(), h: self.w.clone() }
+  }
+
+  fn mirror(&self) -> Self {
+    RectBounds { x: (-self.x.1.clone() - self.w.clone(), -self.x.0.clone() - self.w.clone()), y: self.y.clone(), w: self.w.clone(), h: self.h.clone() }
+  }
+}
+
+/// `true` if the intervals `regions` cover `[lb..ub]`.
+fn covers<Bound: IntBound>(mut regions: Vec<(Bound, Bound)>, lb: Bound, ub: Bound) -> bool {
+  regions.sort();
+  let mut next = lb;
+  for (a, b) in regions {
+    if a > next {
+      break;
+    }
+    if b >= next {
+      next = b + Bound::one();
+    }
+  }
+  next > ub
+}
+
+/// The smallest `x` in the bounds of the origin of `rect` such that there is a `y` in its bounds where the origin is not in one of the `forbidden` regions.
+fn sweep_min<Bound: IntBound>(rect: &RectBounds<Bound>, forbidden: &[Region<Bound>]) -> Option<Bound> {
+  // The feasibility of a position can only change at the beginning of the sweep or after the end of a region.
+  let mut candidates: Vec<Bound> = forbidden.iter()
+    .map(|r| (r.0).1.clone() + Bound::one())
+    .filter(|x| *x > rect.x.0 && *x <= rect.x.1)
+    .collect();
+  candidates.push(rect.x.0.clone());
+  candidates.sort();
+  candidates.dedup();
+  for x in candidates {
+    let covering = forbidden.iter()
+      .filter(|r| (r.0).0 <= x && x <= (r.0).1)
+      .map(|r| r.1.clone())
+      .collect();
+    if !covers(covering, rect.y.0.clone(), rect.y.1.clone()) {
+      return Some(x);
+    }
+  }
+  None
+}
+
+/// The smallest and largest positions of the origins on the first axis, `None` if a rectangle cannot be placed.
+fn sweep<Bound: IntBound>(rects: &[RectBounds<Bound>]) -> Option<Vec<(Bound, Bound)>> {
+  let mirrored: Vec<_> = rects.iter().map(RectBounds::mirror).collect();
+  let mut bounds = vec![];
+  for i in 0..rects.len() {
+    let forbidden = |rects: &[RectBounds<Bound>]| (0..rects.len())
+      .filter(|&j| j != i)
+      .filter_map(|j| rects[i].forbidden_by(&rects[j]))
+      .collect::<Vec<_>>();
+    let lb = sweep_min(&rects[i], &forbidden(rects))?;
+    let ub = -sweep_min(&mirrored[i], &forbidden(&mirrored))? - rects[i].w.clone();
+    bounds.push((lb, ub));
+  }
+  Some(bounds)
+}
+
+#[derive(Debug)]
+pub struct Diffn<VStore>
+{
+  x: Vec<Var<VStore>>,
+  y: Vec<Var<VStore>>,
+  w: Vec<Var<VStore>>,
+  h: Vec<Var<VStore>>,
+  projections: Vec<Cumulative<VStore>>
+}
+
+impl<VStore> Diffn<VStore> where
+  VStore: Collection
+{
+  pub fn new(x: Vec<Var<VStore>>, y: Vec<Var<VStore>>, w: Vec<Var<VStore>>, h: Vec<Var<VStore>>) -> Self {
+    let n = x.len();
+    assert!(y.len() == n && w.len() == n && h.len() == n, "Every rectangle of `Diffn` must have an origin and a size.");
+    Diffn {
+      x: x,
+      y: y,
+      w: w,
+      h: h,
+      projections: vec![]
+    }
+  }
+
+  /// Adds the projections `cumulative(x, w, h, height)` and `cumulative(y, h, w, width)`.
+  /// Precondition: the rectangles are placed in an area of size `width` times `height`, otherwise the projections are not implied by the constraint.
+  pub fn with_cumulative(mut self, width: Var<VStore>, height: Var<VStore>) -> Self {
+    let clone_all = |vars: &Vec<Var<VStore>>| vars.iter().map(|v| v.bclone()).collect();
+    self.projections = vec![
+      Cumulative::new(clone_all(&self.x), clone_all(&self.w), clone_all(&self.h), height),
+      Cumulative::new(clone_all(&self.y), clone_all(&self.h), clone_all(&self.w), width)
+    ];
+    self
+  }
+}
+
+impl<VStore> Clone for Diffn<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    let clone_all = |vars: &Vec<Var<VStore>>| vars.iter().map(|v| v.bclone()).collect();
+    Diffn {
+      x: clone_all(&self.x),
+      y: clone_all(&self.y),
+      w: clone_all(&self.w),
+      h: clone_all(&self.h),
+      projections: self.projections.clone()
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Diffn<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("diffn(");
+    model.display_global("x", &self.x);
+    print!(", ");
+    model.display_global("y", &self.y);
+    print!(", ");
+    model.display_global("w", &self.w);
+    print!(", ");
+    model.display_global("h", &self.h);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Diffn<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// Two rectangles of positive sizes overlap on both axes, for some `i < j`:
+  /// `w[i] > 0 /\ h[i] > 0 /\ w[j] > 0 /\ h[j] > 0 /\ x[j] < x[i] + w[i] /\ x[i] < x[j] + w[j] /\ y[j] < y[i] + h[i] /\ y[i] < y[j] + h[j]`.
+  /// Without two rectangles, the constraint is always entailed and its negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    let n = self.x.len();
+    if n < 2 {
+      return Box::new(Negation::new(Box::new(self.clone())));
+    }
+    let positive = |v: &Var<VStore>| Box::new(x_greater_y(v.bclone(), Box::new(Constant::new(Bound::zero())))) as Formula<VStore>;
+    let before = |a: &Var<VStore>, b: &Var<VStore>, size: &Var<VStore>|
+      Box::new(XLessYPlusZ::new(a.bclone(), b.bclone(), size.bclone())) as Formula<VStore>;
+    let overlaps = (0..n).flat_map(|i| (i+1..n).map(move |j| (i, j)))
+      .map(|(i, j)| Box::new(Conjunction::new(vec![
+        positive(&self.w[i]), positive(&self.h[i]), positive(&self.w[j]), positive(&self.h[j]),
+        before(&self.x[j], &self.x[i], &self.w[i]), before(&self.x[i], &self.x[j], &self.w[j]),
+        before(&self.y[j], &self.y[i], &self.h[i]), before(&self.y[i], &self.y[j], &self.h[j])
+      ])) as Formula<VStore>)
+      .collect();
+    Box::new(Disjunction::new(overlaps))
+  }
+}
+
+/// The relative positions of two rectangles `i` and `j`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Placement {
+  Left,
+  Right,
+  Below,
+  Above
+}
+
+impl<VStore, Domain, Bound> Diffn<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn rect_bounds(&self, store: &VStore, i: usize) -> RectBounds<Bound> {
+    let x = self.x[i].read(store);
+    let y = self.y[i].read(store);
+    RectBounds {
+      x: (x.lower(), x.upper()),
+      y: (y.lower(), y.upper()),
+      w: self.w[i].read(store).lower(),
+      h: self.h[i].read(store).lower()
+    }
+  }
+
+  fn all_rect_bounds(&self, store: &VStore) -> Vec<RectBounds<Bound>> {
+    (0..self.x.len()).map(|i| self.rect_bounds(store, i)).collect()
+  }
+
+  /// The placements of `i` with respect to `j` that are still possible, none if one of them can have no area.
+  fn possible_placements(&self, store: &VStore, i: usize, j: usize) -> Option<Vec<Placement>> {
+    let ri = self.rect_bounds(store, i);
+    let rj = self.rect_bounds(store, j);
+    if !ri.has_area() || !rj.has_area() {
+      return None;
+    }
+    let mut placements = vec![];
+    if ri.x.0.clone() + ri.w.clone() <= rj.x.1 { placements.push(Placement::Left); }
+    if rj.x.0.clone() + rj.w.clone() <= ri.x.1 { placements.push(Placement::Right); }
+    if ri.y.0.clone() + ri.h.clone() <= rj.y.1 { placements.push(Placement::Below); }
+    if rj.y.0.clone() + rj.h.clone() <= ri.y.1 { placements.push(Placement::Above); }
+    Some(placements)
+  }
+
+  /// `true` if the rectangles `i` and `j` cannot overlap.
+  fn entailed_pair(&self, store: &VStore, i: usize, j: usize) -> bool {
+    let ends_before = |x: &Var<VStore>, w: &Var<VStore>, y: &Var<VStore>| x.read(store).upper() + w.read(store).upper() <= y.read(store).lower();
+    [&self.w[i], &self.h[i], &self.w[j], &self.h[j]].iter().any(|s| s.read(store).upper() <= Bound::zero())
+      || ends_before(&self.x[i], &self.w[i], &self.x[j])
+      || ends_before(&self.x[j], &self.w[j], &self.x[i])
+      || ends_before(&self.y[i], &self.h[i], &self.y[j])
+      || ends_before(&self.y[j], &self.h[j], &self.y[i])
+  }
+
+  /// `first + size <= second`.
+  fn precede(store: &mut VStore, first: &mut Var<VStore>, size: &mut Var<VStore>, second: &mut Var<VStore>) -> bool {
+    let f = first.read(store);
+    let s = size.read(store);
+    let g = second.read(store);
+    second.update(store, g.clone().shrink_left(f.lower() + s.lower()))
+      && first.update(store, f.clone().shrink_right(g.upper() - s.lower()))
+      && size.update(store, s.shrink_right(g.upper() - f.lower()))
+  }
+
+  fn place(&mut self, store: &mut VStore, i: usize, j: usize, placement: Placement) -> bool {
+    let (first, second, axis) = match placement {
+      Placement::Left => (i, j, 0),
+      Placement::Right => (j, i, 0),
+      Placement::Below => (i, j, 1),
+      Placement::Above => (j, i, 1)
+    };
+    let (pos, size) = if axis == 0 { (&mut self.x, &mut self.w) } else { (&mut self.y, &mut self.h) };
+    let mut first_pos = pos[first].bclone();
+    let mut second_pos = pos[second].bclone();
+    Self::precede(store, &mut first_pos, &mut size[first], &mut second_pos)
+  }
+
+  fn propagate_pairs(&mut self, store: &mut VStore) -> bool {
+    let n = self.x.len();
+    for i in 0..n {
+      for j in i+1..n {
+        if let Some(placements) = self.possible_placements(store, i, j) {
+          match placements.len() {
+            0 => return false,
+            1 if !self.place(store, i, j, placements[0]) => return false,
+            _ => ()
+          }
+        }
+      }
+    }
+    true
+  }
+
+  fn propagate_sweep(&mut self, store: &mut VStore) -> bool {
+    let rects = self.all_rect_bounds(store);
+    let x_bounds = match sweep(&rects) {
+      Some(b) => b,
+      None => return false
+    };
+    let transposed: Vec<_> = rects.iter().map(RectBounds::transpose).collect();
+    let y_bounds = match sweep(&transposed) {
+      Some(b) => b,
+      None => return false
+    };
+    for (i, ((x_lb, x_ub), (y_lb, y_ub))) in x_bounds.into_iter().zip(y_bounds).enumerate() {
+      let x = self.x[i].read(store).shrink_left(x_lb).shrink_right(x_ub);
+      let y = self.y[i].read(store).shrink_left(y_lb).shrink_right(y_ub);
+      if !self.x[i].update(store, x) || !self.y[i].update(store, y) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Diffn<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: two rectangles necessarily overlap, or a projection is not satisfiable.
+    // True: every pair of rectangles cannot overlap and the projections are entailed.
+    // Unknown: Everything else.
+    let n = self.x.len();
+    let mut entailed = True;
+    for i in 0..n {
+      for j in i+1..n {
+        if let Some(0) = self.possible_placements(store, i, j).map(|p| p.len()) {
+          return False;
+        }
+        if !self.entailed_pair(store, i, j) {
+          entailed = Unknown;
+        }
+      }
+    }
+    self.projections.iter().fold(entailed, |acc, p| acc.and(p.is_subsumed(store)))
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Diffn<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    self.propagate_pairs(store)
+      && self.propagate_sweep(store)
+      && self.projections.iter_mut().all(|p| p.propagate(store))
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Diffn<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.x.iter()
+      .chain(self.y.iter())
+      .chain(self.w.iter())
+      .chain(self.h.iter())
+      .flat_map(|v| v.dependencies(FDEvent::Bound))
+      .collect();
+    deps.extend(self.projections.iter().flat_map(|p| p.dependencies()));
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+  use term::Constant;
+  use variable::VStoreFD;
+
+  type Rect = (Interval<i32>, Interval<i32>, Interval<i32>, Interval<i32>);
+
+  /// The rectangles are given as `(x, y, w, h)`, the variables are numbered `x[0], y[0], w[0], h[0], x[1], ...`.
+  fn diffn_test_one(test_num: u32, rects: Vec<Rect>,
+    area: Option<(i32, i32)>, before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    let doms = rects.into_iter().flat_map(|(x, y, w, h)| vec![x, y, w, h]).collect();
+    nary_propagator_test(test_num, |vars: Vec<FDVar>| {
+        let var = |k: usize| vars.iter().skip(k).step_by(4).map(|v| v.bclone()).collect();
+        let diffn = Diffn::new(var(0), var(1), var(2), var(3));
+        match area {
+          Some((width, height)) => diffn.with_cumulative(
+            Box::new(Constant::new(width)) as Var<VStoreFD>,
+            Box::new(Constant::new(height)) as Var<VStoreFD>),
+          None => diffn
+        }
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn diffn_test() {
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let dom0_1 = (0,1).to_interval();
+    let dom0_2 = (0,2).to_interval();
+    let dom1_2 = (1,2).to_interval();
+    let dom1_3 = (1,3).to_interval();
+    let dom0_3 = (0,3).to_interval();
+    let dom0_5 = (0,5).to_interval();
+
+    diffn_test_one(1, vec![(zero, zero, two, two), (dom0_5, dom0_3, two, two)], None,
+      Unknown, Unknown, vec![], true);
+    // The rectangle `1` can only be on the right of the rectangle `0`.
+    diffn_test_one(2, vec![(zero, zero, two, two), (dom0_5, dom0_1, two, two)], None,
+      Unknown, True, vec![(4, Bound)], true);
+    // Pairwise, the rectangle `2` can be on the right of or above/below the other ones, but the sweep shows that it is on their right.
+    diffn_test_one(3, vec![(zero, zero, two, two), (zero, two, two, two), (dom0_5, dom0_2, two, two)], None,
+      Unknown, True, vec![(8, Bound)], true);
+    // The width of the rectangle `0` is bounded by the position of the rectangle `1`.
+    diffn_test_one(4, vec![(zero, zero, dom1_3, two), (two, dom0_1, two, two)], None,
+      Unknown, True, vec![(2, Bound)], true);
+    diffn_test_one(5, vec![(zero, zero, two, two), (one, one, two, two)], None,
+      False, False, vec![], false);
+    diffn_test_one(6, vec![(zero, zero, two, two), (one, one, zero, two)], None,
+      True, True, vec![], true);
+    diffn_test_one(7, vec![(zero, zero, two, two), (two, zero, two, two)], None,
+      True, True, vec![], true);
+    // In an area of height `2`, the rectangles `0` and `1` fill the column `1`, which is only detected by the projection.
+    let rects = vec![(dom0_1, dom0_1, two, one), (dom0_1, dom0_1, two, one), (dom1_2, dom0_1, one, one)];
+    diffn_test_one(8, rects.clone(), None, Unknown, Unknown, vec![], true);
+    diffn_test_one(9, rects, Some((3, 2)), Unknown, Unknown, vec![(8, Assignment)], true);
+  }
+
+  #[test]
+  fn sweep_test() {
+    let rect = |x: (i32, i32), y: (i32, i32), w, h| RectBounds { x: x, y: y, w: w, h: h };
+    let rects = vec![
+      rect((0, 0), (0, 0), 2, 2),
+      rect((0, 0), (2, 2), 2, 2),
+      rect((0, 5), (0, 2), 2, 2)];
+    assert_eq!(sweep(&rects), Some(vec![(0, 0), (0, 0), (2, 5)]));
+    let transposed: Vec<_> = rects.iter().map(RectBounds::transpose).collect();
+    assert_eq!(sweep(&transposed), Some(vec![(0, 0), (2, 2), (0, 2)]));
+    assert_eq!(sweep(&[rect((0, 0), (0, 0), 2, 2), rect((1, 1), (0, 1), 2, 2)]), None);
+  }
+}
@@ -0,0 +1,437 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagators::cumulative::*;
+use propagators::theta_lambda_tree::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use term::Constant;
+use propagators::{XLessYPlusZ, x_greater_y};
+use std::cmp::{min, max};
+
+/// The constraint `disjunctive(s, d)` ensures that the tasks `i` running in `[s[i]..s[i] + d[i])` do not overlap: they share a unary resource.
+///
+/// The propagator implements the filtering algorithms described in `Filtering Algorithms for the Unary Resource Constraint`, Vilím, 2008: overload checking, detectable precedences, not-first/not-last and edge-finding.
+/// They all run in `O(n log n)` with a Θ-Λ-tree. The tasks of duration zero do not use the resource.
+#[derive(Debug)]
+pub struct Disjunctive<VStore>
+{
+  starts: Vec<Var<VStore>>,
+  durations: Vec<Var<VStore>>
+}
+
+impl<VStore> Disjunctive<VStore>
+{
+  pub fn new(starts: Vec<Var<VStore>>, durations: Vec<Var<VStore>>) -> Self {
+    assert_eq!(starts.len(), durations.len());
+    Disjunctive {
+      starts: starts,
+      durations: durations
+    }
+  }
+}
+
+impl<VStore> Clone for Disjunctive<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Disjunctive::new(
+      self.starts.iter().map(|v| v.bclone()).collect(),
+      self.durations.iter().map(|v| v.bclone()).collect())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Disjunctive<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("disjunctive(");
+    model.display_global("s", &self.starts);
+    print!(", ");
+    model.display_global("d", &self.durations);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Disjunctive<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// Two tasks of positive duration overlap: `d[i] > 0 /\ d[j] > 0 /\ s[j] < s[i] + d[i] /\ s[i] < s[j] + d[j]` for some `i < j`.
+  /// Without two tasks, the constraint is always entailed and its negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    let n = self.starts.len();
+    if n < 2 {
+      return Box::new(Negation::new(Box::new(self.clone())));
+    }
+    let (s, d) = (&self.starts, &self.durations);
+    let positive = |v: &Var<VStore>| Box::new(x_greater_y(v.bclone(), Box::new(Constant::new(Bound::zero())))) as Formula<VStore>;
+    let overlaps = (0..n).flat_map(|i| (i+1..n).map(move |j| (i, j)))
+      .map(|(i, j)| Box::new(Conjunction::new(vec![
+        positive(&d[i]),
+        positive(&d[j]),
+        Box::new(XLessYPlusZ::new(s[j].bclone(), s[i].bclone(), d[i].bclone())),
+        Box::new(XLessYPlusZ::new(s[i].bclone(), s[j].bclone(), d[j].bclone()))
+      ])) as Formula<VStore>)
+      .collect();
+    Box::new(Disjunction::new(overlaps))
+  }
+}
+
+/// Tasks with a positive duration sorted by `key`.
+fn sorted_tasks<Bound, K, F>(tasks: &[TaskBounds<Bound>], key: F) -> Vec<usize> where
+  Bound: IntBound,
+  K: Ord,
+  F: Fn(&TaskBounds<Bound>) -> K
+{
+  let mut sorted: Vec<usize> = (0..tasks.len())
+    .filter(|&i| tasks[i].dur > Bound::zero())
+    .collect();
+  sorted.sort_by_key(|&i| key(&tasks[i]));
+  sorted
+}
+
+/// Tasks with a positive duration in a Θ-Λ-tree where the envelope is the earliest completion time.
+struct UnaryTree<'a, Bound: 'a>
+{
+  tasks: &'a [TaskBounds<Bound>],
+  by_est: Vec<usize>,
+  pos: Vec<usize>,
+  tree: ThetaLambdaTree<Bound>
+}
+
+impl<'a, Bound> UnaryTree<'a, Bound> where
+  Bound: IntBound
+{
+  fn empty(tasks: &'a [TaskBounds<Bound>]) -> Self {
+    let by_est = sorted_tasks(tasks, |t| t.est.clone());
+    let mut pos = vec![0; tasks.len()];
+    for (p, &i) in by_est.iter().enumerate() {
+      pos[i] = p;
+    }
+    let tree = ThetaLambdaTree::new(by_est.len());
+    UnaryTree {
+      tasks: tasks,
+      by_est: by_est,
+      pos: pos,
+      tree: tree
+    }
+  }
+
+  fn full(tasks: &'a [TaskBounds<Bound>]) -> Self {
+    let mut tree = UnaryTree::empty(tasks);
+    for i in tree.by_est.clone() {
+      tree.insert(i);
+    }
+    tree
+  }
+
+  fn insert(&mut self, i: usize) {
+    let t = &self.tasks[i];
+    self.tree.insert(self.pos[i], t.dur.clone(), t.ect());
+  }
+
+  fn remove(&mut self, i: usize) {
+    self.tree.remove(self.pos[i]);
+  }
+
+  fn gray(&mut self, i: usize) {
+    self.tree.gray(self.pos[i]);
+  }
+
+  fn ect(&self) -> Option<Bound> {
+    self.tree.envelope()
+  }
+
+  /// Earliest completion time of Θ without the task `i`.
+  fn ect_without(&mut self, i: usize, in_theta: bool) -> Option<Bound> {
+    if in_theta {
+      self.remove(i);
+      let ect = self.ect();
+      self.insert(i);
+      ect
+    }
+    else {
+      self.ect()
+    }
+  }
+}
+
+/// Overload checking: fails if a set of tasks `Θ` cannot be completed before `lct(Θ)`.
+pub fn overload_checking<Bound>(tasks: &[TaskBounds<Bound>]) -> bool where
+  Bound: IntBound
+{
+  let mut theta = UnaryTree::empty(tasks);
+  for j in sorted_tasks(tasks, |t| t.lct()) {
+    theta.insert(j);
+    if theta.ect() > Some(tasks[j].lct()) {
+      return false;
+    }
+  }
+  true
+}
+
+/// Detectable precedences: if `ect(i) > lst(j)` then `j` must precede `i`, the earliest start date of `i` is pushed after the earliest completion time of all the tasks preceding it.
+/// It returns the new earliest start dates of the tasks.
+pub fn detectable_precedences<Bound>(tasks: &[TaskBounds<Bound>]) -> Vec<Bound> where
+  Bound: IntBound
+{
+  let mut est: Vec<Bound> = tasks.iter().map(|t| t.est.clone()).collect();
+  let mut theta = UnaryTree::empty(tasks);
+  let mut in_theta = vec![false; tasks.len()];
+  let by_lst = sorted_tasks(tasks, |t| t.lst.clone());
+  let mut next = 0;
+  for i in sorted_tasks(tasks, |t| t.ect()) {
+    while next < by_lst.len() && tasks[i].ect() > tasks[by_lst[next]].lst {
+      let j = by_lst[next];
+      theta.insert(j);
+      in_theta[j] = true;
+      next += 1;
+    }
+    if let Some(ect) = theta.ect_without(i, in_theta[i]) {
+      est[i] = max(est[i].clone(), ect);
+    }
+  }
+  est
+}
+
+/// Not-last: if the tasks `Ω` starting before `lct(i)` cannot be completed before `lst(i)`, then `i` is not the last task of `Ω ∪ {i}` and it must be completed before the latest start date of a task of `Ω`.
+/// It returns the new latest completion times of the tasks, not-first is obtained on the mirrored tasks.
+pub fn not_last<Bound>(tasks: &[TaskBounds<Bound>]) -> Vec<Bound> where
+  Bound: IntBound
+{
+  let mut lct: Vec<Bound> = tasks.iter().map(|t| t.lct()).collect();
+  let mut theta = UnaryTree::empty(tasks);
+  let mut in_theta = vec![false; tasks.len()];
+  let by_lst = sorted_tasks(tasks, |t| t.lst.clone());
+  let mut next = 0;
+  for i in sorted_tasks(tasks, |t| t.lct()) {
+    while next < by_lst.len() && tasks[i].lct() > tasks[by_lst[next]].lst {
+      let j = by_lst[next];
+      theta.insert(j);
+      in_theta[j] = true;
+      next += 1;
+    }
+    if theta.ect_without(i, in_theta[i]) > Some(tasks[i].lst.clone()) {
+      // The task of `Ω` with the largest latest start date.
+      let j = by_lst[..next].iter().rev()
+        .find(|&&j| j != i)
+        .expect("Ω is not empty since it has an earliest completion time.");
+      lct[i] = min(lct[i].clone(), tasks[*j].lst.clone());
+    }
+  }
+  lct
+}
+
+/// Edge-finding: if the tasks `Θ ∪ {i}` cannot be completed before `lct(Θ)`, then `i` must be scheduled after all the tasks of `Θ`.
+/// It returns the new earliest start dates of the tasks, or `None` if the resource is overloaded.
+pub fn edge_finding<Bound>(tasks: &[TaskBounds<Bound>]) -> Option<Vec<Bound>> where
+  Bound: IntBound
+{
+  let mut est: Vec<Bound> = tasks.iter().map(|t| t.est.clone()).collect();
+  let mut theta = UnaryTree::full(tasks);
+  let by_lct = sorted_tasks(tasks, |t| t.lct());
+  for k in (0..by_lct.len()).rev() {
+    let j = by_lct[k];
+    if theta.ect() > Some(tasks[j].lct()) {
+      return None;
+    }
+    if k == 0 {
+      break;
+    }
+    theta.gray(j);
+    let lct_theta = tasks[by_lct[k-1]].lct();
+    while theta.tree.gray_envelope() > Some(lct_theta.clone()) {
+      let i = theta.by_est[theta.tree.responsible_gray()];
+      let ect = theta.ect().expect("Θ is not empty.");
+      est[i] = max(est[i].clone(), ect);
+      theta.remove(i);
+    }
+  }
+  Some(est)
+}
+
+impl<VStore, Domain, Bound> Disjunctive<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn task_bounds(&self, vstore: &VStore) -> Vec<TaskBounds<Bound>> {
+    self.starts.iter().zip(self.durations.iter()).map(|(s, d)| {
+      let s = s.read(vstore);
+      TaskBounds {
+        est: s.lower(),
+        lst: s.upper(),
+        dur: d.read(vstore).lower(),
+        res: Bound::one()
+      }
+    }).collect()
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Disjunctive<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, vstore: &VStore) -> SKleene {
+    // False: two compulsory parts overlap.
+    // True: all the tasks are assigned and their compulsory parts, which are the tasks themselves, do not overlap.
+    // Unknown: Everything else.
+    let tasks = self.task_bounds(vstore);
+    if compulsory_profile(&tasks).iter().any(|seg| seg.height > Bound::one()) {
+      False
+    }
+    else if self.starts.iter()
+      .chain(self.durations.iter())
+      .all(|v| v.read(vstore).is_singleton())
+    {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Disjunctive<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, vstore: &mut VStore) -> bool {
+    let tasks = self.task_bounds(vstore);
+    let mirrored: Vec<_> = tasks.iter().map(TaskBounds::mirror).collect();
+    if !overload_checking(&tasks) {
+      return false;
+    }
+    let (ef_est, ef_mirrored) = match (edge_finding(&tasks), edge_finding(&mirrored)) {
+      (Some(est), Some(mirrored_est)) => (est, mirrored_est),
+      _ => return false
+    };
+    let dp_est = detectable_precedences(&tasks);
+    let dp_mirrored = detectable_precedences(&mirrored);
+    let nl_lct = not_last(&tasks);
+    let nf_mirrored = not_last(&mirrored);
+    for (i, t) in tasks.iter().enumerate() {
+      let est = max(max(ef_est[i].clone(), dp_est[i].clone()), -nf_mirrored[i].clone());
+      let lct = min(min(-ef_mirrored[i].clone(), -dp_mirrored[i].clone()), nl_lct[i].clone());
+      let s = self.starts[i].read(vstore);
+      if !self.starts[i].update(vstore, s.shrink_left(est).shrink_right(lct - t.dur.clone())) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Disjunctive<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.starts.iter()
+      .chain(self.durations.iter())
+      .flat_map(|v| v.dependencies(FDEvent::Bound))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use propagators::test::*;
+  use interval::interval::*;
+  use interval::ops::Range;
+
+  fn tasks(bounds: Vec<(i32, i32, i32)>) -> Vec<TaskBounds<i32>> {
+    bounds.into_iter()
+      .map(|(est, lst, dur)| TaskBounds { est: est, lst: lst, dur: dur, res: 1 })
+      .collect()
+  }
+
+  #[test]
+  fn overload_checking_test() {
+    assert!(overload_checking(&tasks(vec![(0,3,2), (0,3,2), (1,5,1)])));
+    assert!(!overload_checking(&tasks(vec![(0,2,2), (0,2,2), (1,3,1)])));
+    // The tasks of duration zero do not use the resource.
+    assert!(overload_checking(&tasks(vec![(0,2,2), (0,2,2), (1,1,0)])));
+  }
+
+  #[test]
+  fn detectable_precedences_test() {
+    // `ect(3) > lst(1)` and `ect(3) > lst(2)`, so the tasks 1 and 2 precede the task 3.
+    let ts = tasks(vec![(0,14,11), (1,17,10), (14,30,5)]);
+    assert_eq!(detectable_precedences(&ts), vec![0,1,21]);
+    // Not detected: `ect(1) = 8` but `lst(2) = lst(3) = 10`.
+    let ts = tasks(vec![(4,26,4), (5,10,3), (5,10,3)]);
+    assert_eq!(detectable_precedences(&ts), vec![4,5,5]);
+  }
+
+  #[test]
+  fn edge_finding_test() {
+    // The task 1 cannot be scheduled before the tasks 2 and 3 which must end before 13.
+    let ts = tasks(vec![(4,26,4), (5,10,3), (5,10,3)]);
+    assert_eq!(edge_finding(&ts), Some(vec![11,5,5]));
+    let ts = tasks(vec![(0,2,2), (0,2,2), (1,3,1)]);
+    assert_eq!(edge_finding(&ts), None);
+  }
+
+  #[test]
+  fn not_last_test() {
+    // The task 3 cannot be the last one, it must end before `lst(2) = 17`.
+    let ts = tasks(vec![(0,14,11), (1,17,10), (14,17,3)]);
+    assert_eq!(not_last(&ts)[2], 17);
+    // Not-first: the task 1 cannot start before `ect(2) = 3`.
+    let ts = tasks(vec![(0,10,4), (0,1,3), (1,2,2)]);
+    let mirrored: Vec<_> = ts.iter().map(TaskBounds::mirror).collect();
+    assert_eq!(-not_last(&mirrored)[0], 3);
+  }
+
+  fn disjunctive_test_one(test_num: u32, starts: Vec<Interval<i32>>, durations: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    let n = starts.len();
+    let doms = starts.into_iter().chain(durations.into_iter()).collect();
+    nary_propagator_test(test_num, move |vars: Vec<FDVar>| {
+      let mut vars = vars;
+      let durations = vars.split_off(n);
+      Disjunctive::new(vars, durations)
+    }, doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn disjunctive_test() {
+    let fixed = |v: i32| Interval::new(v, v);
+    disjunctive_test_one(1, vec![fixed(0), fixed(2)], vec![fixed(2), fixed(3)], True, True, vec![], true);
+    disjunctive_test_one(2, vec![fixed(0), fixed(1)], vec![fixed(2), fixed(3)], False, False, vec![], false);
+    disjunctive_test_one(3, vec![Interval::new(4,26), Interval::new(5,10), Interval::new(5,10)],
+      vec![fixed(4), fixed(3), fixed(3)], Unknown, Unknown, vec![(0, Bound)], true);
+    disjunctive_test_one(4, vec![Interval::new(0,1), Interval::new(0,10)],
+      vec![fixed(3), fixed(2)], Unknown, Unknown, vec![(1, Bound)], true);
+    disjunctive_test_one(5, vec![Interval::new(0,1), Interval::new(0,3)],
+      vec![fixed(3), fixed(2)], Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+    disjunctive_test_one(6, vec![Interval::new(0,2), Interval::new(0,2), Interval::new(0,2)],
+      vec![fixed(2), fixed(2), fixed(1)], Unknown, Unknown, vec![], false);
+  }
+}
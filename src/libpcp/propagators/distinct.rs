@@ -14,9 +14,11 @@
 
 use kernel::*;
 use trilean::SKleene;
+use trilean::SKleene::*;
 use model::*;
 use logic::*;
 use propagators::cmp::x_neq_y::*;
+use propagators::cmp::x_eq_y::*;
 use propagation::events::*;
 use propagation::*;
 use gcollections::*;
@@ -39,39 +41,41 @@ pub fn join_distinct<VStore, CStore, Domain, Bound>(
   }
 }
 
+/// Bounds consistent propagator of the constraint `distinct(x1,...,xN)` (also called `alldifferent`).
+///
+/// The propagation is performed in two steps. First, the value of every assigned variable is removed from the domains of the other variables. Then, the Hall intervals are computed with the algorithm described in `A fast and simple algorithm for bounds consistency of the alldifferent constraint`, López-Ortiz and al., 2003.
+/// The pairwise decomposition into `XNeqY` propagators is still available with `join_distinct`.
 #[derive(Debug)]
 pub struct Distinct<VStore>
 {
-  conj: Conjunction<VStore>,
   vars: Vec<Var<VStore>>
 }
 
-impl<VStore> NotFormula<VStore> for Distinct<VStore> where
- VStore: Collection + 'static
+impl<VStore, Domain, Bound> NotFormula<VStore> for Distinct<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
 {
+  /// At least two variables are equal.
   fn not(&self) -> Formula<VStore> {
-    self.conj.not()
+    let mut props = vec![];
+    for i in 0..self.vars.len()-1 {
+      for j in i+1..self.vars.len() {
+        let i_eq_j = Box::new(XEqY::new(self.vars[i].bclone(), self.vars[j].bclone())) as Formula<VStore>;
+        props.push(i_eq_j);
+      }
+    }
+    Box::new(Disjunction::new(props))
   }
 }
 
-impl<VStore, Domain, Bound> Distinct<VStore> where
-  VStore: VStoreConcept<Item=Domain> + 'static,
-  Domain: IntDomain<Item=Bound> + 'static,
-  Bound: IntBound + 'static,
+impl<VStore> Distinct<VStore>
 {
   /// Precondition: `vars.len() > 1`.
   pub fn new(vars: Vec<Var<VStore>>) -> Self {
     assert!(vars.len() > 0,
       "Variable array in `Distinct` must be non-empty.");
-    let mut props = vec![];
-    for i in 0..vars.len()-1 {
-      for j in i+1..vars.len() {
-        let i_neq_j = Box::new(XNeqY::new(vars[i].bclone(), vars[j].bclone())) as Formula<VStore>;
-        props.push(i_neq_j);
-      }
-    }
     Distinct {
-      conj: Conjunction::new(props),
       vars: vars
     }
   }
@@ -82,7 +86,6 @@ impl<VStore> Clone for Distinct<VStore> where
 {
   fn clone(&self) -> Self {
     Distinct {
-      conj: self.conj.clone(),
       vars: self.vars.iter().map(|v| v.bclone()).collect()
     }
   }
@@ -95,31 +98,289 @@ impl<VStore> DisplayStateful<Model> for Distinct<VStore>
   }
 }
 
-impl<VStore> Subsumption<VStore> for Distinct<VStore>
+impl<VStore, Domain, Bound> Subsumption<VStore> for Distinct<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
 {
   fn is_subsumed(&self, vstore: &VStore) -> SKleene {
-    self.conj.is_subsumed(vstore)
+    // False: two variables are assigned to the same value.
+    // True: the bounds of the variables are pairwise disjoint.
+    // Unknown: Everything else.
+    let mut doms: Vec<_> = self.vars.iter()
+      .map(|v| v.read(vstore))
+      .map(|d| (d.lower(), d.upper()))
+      .collect();
+    doms.sort();
+    let mut all_disjoint = true;
+    for i in 1..doms.len() {
+      if doms[i-1].1 >= doms[i].0 {
+        all_disjoint = false;
+      }
+    }
+    if all_disjoint {
+      return True;
+    }
+    let mut values: Vec<_> = doms.into_iter()
+      .filter(|(l, u)| l == u)
+      .map(|(l, _)| l)
+      .collect();
+    let num_assigned = values.len();
+    values.dedup();
+    if values.len() != num_assigned { False }
+    else { Unknown }
   }
 }
 
-impl<VStore> Propagator<VStore> for Distinct<VStore>
+impl<VStore, Domain, Bound> Propagator<VStore> for Distinct<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
 {
   fn propagate(&mut self, vstore: &mut VStore) -> bool {
-    self.conj.propagate(vstore)
+    self.propagate_assigned(vstore) &&
+    self.propagate_bounds(vstore)
+  }
+}
+
+impl<VStore, Domain, Bound> Distinct<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// Remove the value of the assigned variables from the domains of the other variables.
+  /// A variable becoming assigned is in turn removed from the others.
+  fn propagate_assigned(&mut self, vstore: &mut VStore) -> bool {
+    let mut removed = vec![false; self.vars.len()];
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for (i, done) in removed.iter_mut().enumerate() {
+        if *done { continue; }
+        let x = self.vars[i].read(vstore);
+        if x.is_singleton() {
+          *done = true;
+          changed = true;
+          let value = x.lower();
+          for (j, y) in self.vars.iter_mut().enumerate() {
+            if j != i {
+              let dom = y.read(vstore);
+              if !y.update(vstore, dom.difference(&value)) {
+                return false;
+              }
+            }
+          }
+        }
+      }
+    }
+    true
+  }
+
+  /// Filter the bounds of the variables with respect to the Hall intervals.
+  fn propagate_bounds(&mut self, vstore: &mut VStore) -> bool {
+    let doms: Vec<_> = self.vars.iter().map(|v| v.read(vstore)).collect();
+    let mut hall = HallIntervals::new(
+      doms.iter().map(|d| d.lower()).collect(),
+      doms.iter().map(|d| d.upper()).collect());
+    if !hall.filter_lower() || !hall.filter_upper() {
+      return false;
+    }
+    for (i, dom) in doms.into_iter().enumerate() {
+      let lb = hall.lb[i].clone();
+      let ub = hall.ub[i].clone();
+      if (lb != dom.lower() || ub != dom.upper()) &&
+         !self.vars[i].update(vstore, dom.shrink_left(lb).shrink_right(ub))
+      {
+        return false;
+      }
+    }
+    true
   }
 }
 
 impl<VStore> PropagatorDependencies<FDEvent> for Distinct<VStore>
 {
   fn dependencies(&self) -> Vec<(usize, FDEvent)> {
-    self.vars.iter().flat_map(|v| v.dependencies(FDEvent::Inner)).collect()
+    self.vars.iter().flat_map(|v| v.dependencies(FDEvent::Bound)).collect()
+  }
+}
+
+/// Bounds `[lb[i]..ub[i]]` of the variables, ranked in the sorted array of the bounds `bounds` (where the upper bounds are stored as `ub[i] + 1`).
+/// The indices `t`, `h` and the capacities `d` are the union-find structures of the algorithm of López-Ortiz and al.
+struct HallIntervals<Bound>
+{
+  lb: Vec<Bound>,
+  ub: Vec<Bound>,
+  min_sorted: Vec<usize>,
+  max_sorted: Vec<usize>,
+  min_rank: Vec<usize>,
+  max_rank: Vec<usize>,
+  bounds: Vec<Bound>,
+  t: Vec<usize>,
+  h: Vec<usize>,
+  d: Vec<Bound>
+}
+
+impl<Bound> HallIntervals<Bound> where
+  Bound: IntBound
+{
+  fn new(lb: Vec<Bound>, ub: Vec<Bound>) -> Self {
+    let n = lb.len();
+    let mut min_sorted: Vec<usize> = (0..n).collect();
+    min_sorted.sort_by(|&i, &j| lb[i].cmp(&lb[j]));
+    let mut max_sorted: Vec<usize> = (0..n).collect();
+    max_sorted.sort_by(|&i, &j| ub[i].cmp(&ub[j]));
+    let one = Bound::one();
+    let two = one.clone() + one.clone();
+
+    let mut min_rank = vec![0; n];
+    let mut max_rank = vec![0; n];
+    let mut min = lb[min_sorted[0]].clone();
+    let mut max = ub[max_sorted[0]].clone() + one.clone();
+    let mut last = min.clone() - two.clone();
+    let mut bounds = vec![last.clone()];
+    let (mut i, mut j) = (0, 0);
+    loop {
+      if i < n && min <= max {
+        if min != last {
+          last = min.clone();
+          bounds.push(last.clone());
+        }
+        min_rank[min_sorted[i]] = bounds.len() - 1;
+        i += 1;
+        if i < n {
+          min = lb[min_sorted[i]].clone();
+        }
+      }
+      else {
+        if max != last {
+          last = max.clone();
+          bounds.push(last.clone());
+        }
+        max_rank[max_sorted[j]] = bounds.len() - 1;
+        j += 1;
+        if j == n { break; }
+        max = ub[max_sorted[j]].clone() + one.clone();
+      }
+    }
+    bounds.push(last + two);
+    let size = bounds.len();
+    HallIntervals {
+      lb: lb,
+      ub: ub,
+      min_sorted: min_sorted,
+      max_sorted: max_sorted,
+      min_rank: min_rank,
+      max_rank: max_rank,
+      bounds: bounds,
+      t: vec![0; size],
+      h: vec![0; size],
+      d: vec![Bound::zero(); size]
+    }
+  }
+
+  fn path_max(t: &[usize], mut i: usize) -> usize {
+    while t[i] > i { i = t[i]; }
+    i
+  }
+
+  fn path_min(t: &[usize], mut i: usize) -> usize {
+    while t[i] < i { i = t[i]; }
+    i
+  }
+
+  fn path_set(t: &mut [usize], start: usize, end: usize, to: usize) {
+    let mut l = start;
+    while l != end {
+      let k = l;
+      l = t[k];
+      t[k] = to;
+    }
+  }
+
+  /// Update the lower bounds, the variables are visited by increasing upper bounds.
+  /// Returns `false` if a Hall interval contains more variables than values.
+  fn filter_lower(&mut self) -> bool {
+    let nb = self.bounds.len() - 2;
+    for i in 1..nb+2 {
+      self.t[i] = i - 1;
+      self.h[i] = i - 1;
+      self.d[i] = self.bounds[i].clone() - self.bounds[i-1].clone();
+    }
+    for i in 0..self.max_sorted.len() {
+      let v = self.max_sorted[i];
+      let x = self.min_rank[v];
+      let y = self.max_rank[v];
+      let mut z = Self::path_max(&self.t, x+1);
+      let j = self.t[z];
+      self.d[z] = self.d[z].clone() - Bound::one();
+      if self.d[z] == Bound::zero() {
+        self.t[z] = z + 1;
+        z = Self::path_max(&self.t, self.t[z]);
+        self.t[z] = j;
+      }
+      Self::path_set(&mut self.t, x+1, z, z);
+      let width = self.bounds[z].clone() - self.bounds[y].clone();
+      if self.d[z] < width {
+        return false;
+      }
+      if self.h[x] > x {
+        let w = Self::path_max(&self.h, self.h[x]);
+        self.lb[v] = self.bounds[w].clone();
+        Self::path_set(&mut self.h, x, w, w);
+      }
+      if self.d[z] == width {
+        let hy = self.h[y];
+        Self::path_set(&mut self.h, hy, j-1, y);
+        self.h[y] = j - 1;
+      }
+    }
+    true
+  }
+
+  /// Update the upper bounds, the variables are visited by decreasing lower bounds.
+  fn filter_upper(&mut self) -> bool {
+    let nb = self.bounds.len() - 2;
+    for i in 0..nb+1 {
+      self.t[i] = i + 1;
+      self.h[i] = i + 1;
+      self.d[i] = self.bounds[i+1].clone() - self.bounds[i].clone();
+    }
+    for i in (0..self.min_sorted.len()).rev() {
+      let v = self.min_sorted[i];
+      let x = self.max_rank[v];
+      let y = self.min_rank[v];
+      let mut z = Self::path_min(&self.t, x-1);
+      let j = self.t[z];
+      self.d[z] = self.d[z].clone() - Bound::one();
+      if self.d[z] == Bound::zero() {
+        self.t[z] = z - 1;
+        z = Self::path_min(&self.t, self.t[z]);
+        self.t[z] = j;
+      }
+      Self::path_set(&mut self.t, x-1, z, z);
+      let width = self.bounds[y].clone() - self.bounds[z].clone();
+      if self.d[z] < width {
+        return false;
+      }
+      if self.h[x] < x {
+        let w = Self::path_min(&self.h, self.h[x]);
+        self.ub[v] = self.bounds[w].clone() - Bound::one();
+        Self::path_set(&mut self.h, x, w, w);
+      }
+      if self.d[z] == width {
+        let hy = self.h[y];
+        Self::path_set(&mut self.h, hy, j+1, y);
+        self.h[y] = j + 1;
+      }
+    }
+    true
   }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
-  use trilean::SKleene::*;
   use propagation::events::FDEvent::*;
   use interval::interval::*;
   use propagators::test::*;
@@ -142,6 +403,20 @@ mod test {
     distinct_test_one(7, vec![dom0_3], True, True, vec![], true);
   }
 
+  #[test]
+  fn distinct_hall_interval_test() {
+    let dom1_2 = (1,2).to_interval();
+    let dom1_3 = (1,3).to_interval();
+    let dom1_4 = (1,4).to_interval();
+    let dom2_5 = (2,5).to_interval();
+
+    distinct_test_one(1, vec![dom1_2,dom1_2,dom1_3], Unknown, Unknown, vec![(2, Assignment)], true);
+    distinct_test_one(2, vec![dom1_2,dom1_2,dom1_2], Unknown, Unknown, vec![], false);
+    distinct_test_one(3, vec![dom1_4,dom1_2,dom1_2], Unknown, Unknown, vec![(0, Bound)], true);
+    distinct_test_one(4, vec![dom1_3,dom1_2,dom2_5,dom1_3], Unknown, Unknown, vec![(2, Bound)], true);
+    distinct_test_one(5, vec![dom1_3,dom1_3,dom1_3,dom1_4], Unknown, Unknown, vec![(3, Assignment)], true);
+  }
+
   fn distinct_test_one(test_num: u32, doms: Vec<Interval<i32>>,
     before: SKleene, after: SKleene,
     delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
@@ -149,3 +424,4 @@ mod test {
     nary_propagator_test(test_num, Distinct::new, doms, before, after, delta_expected, propagate_success);
   }
 }
+
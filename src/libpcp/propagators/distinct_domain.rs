@@ -0,0 +1,364 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use trilean::SKleene;
+use model::*;
+use logic::*;
+use propagators::distinct::*;
+use propagators::domain_values;
+use propagation::events::*;
+use propagation::*;
+use gcollections::*;
+use concept::*;
+
+/// Domain consistent propagator of the constraint `distinct(x1,...,xN)`.
+///
+/// It is the algorithm described in `A filtering algorithm for constraints of difference in CSPs`, Régin, 1994.
+/// A maximum matching between the variables and the values is computed, and every value that does not belong to any maximum matching is removed.
+/// Such a value can be in the middle of a domain, it is only removed if the domain supports holes (e.g. `IntervalSet`).
+#[derive(Debug)]
+pub struct DistinctDomain<VStore, Bound>
+{
+  vars: Vec<Var<VStore>>,
+  // The matching of the last propagation, used as a starting point for the next one.
+  matching: Vec<Option<Bound>>
+}
+
+impl<VStore, Bound> DistinctDomain<VStore, Bound>
+{
+  /// Precondition: `vars.len() > 1`.
+  pub fn new(vars: Vec<Var<VStore>>) -> Self {
+    assert!(!vars.is_empty(),
+      "Variable array in `DistinctDomain` must be non-empty.");
+    let matching = vars.iter().map(|_| None).collect();
+    DistinctDomain {
+      vars: vars,
+      matching: matching
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for DistinctDomain<VStore, Bound> where
+ VStore: Collection,
+ Bound: Clone
+{
+  fn clone(&self) -> Self {
+    DistinctDomain {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      matching: self.matching.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for DistinctDomain<VStore, Bound>
+{
+  fn display(&self, model: &Model) {
+    model.display_global("distinct_domain", &self.vars);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for DistinctDomain<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Distinct::new(self.vars.iter().map(|v| v.bclone()).collect()).not()
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for DistinctDomain<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, vstore: &VStore) -> SKleene {
+    Distinct::new(self.vars.iter().map(|v| v.bclone()).collect()).is_subsumed(vstore)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for DistinctDomain<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, vstore: &mut VStore) -> bool {
+    let doms: Vec<_> = self.vars.iter().map(|v| v.read(vstore)).collect();
+    let mut graph = ValueGraph::new(&doms);
+    if graph.values.len() < self.vars.len() {
+      return false;
+    }
+    graph.init_matching(&self.matching);
+    if !graph.maximum_matching() {
+      return false;
+    }
+    self.matching = graph.var_match.iter()
+      .map(|m| m.map(|v| graph.values[v].clone()))
+      .collect();
+    let vital = graph.vital_edges();
+    for (x, dom) in doms.into_iter().enumerate() {
+      let mut new_dom = dom.clone();
+      for (i, &v) in graph.adj[x].iter().enumerate() {
+        if !vital[x][i] {
+          new_dom = new_dom.difference(&graph.values[v]);
+        }
+      }
+      if new_dom.size() != dom.size() && !self.vars[x].update(vstore, new_dom) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for DistinctDomain<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    self.vars.iter().flat_map(|v| v.dependencies(FDEvent::Inner)).collect()
+  }
+}
+
+/// Bipartite graph between the variables and the values of their domains.
+/// The values are indexed by their position in `values`, and `adj[x]` contains the values of the variable `x`.
+struct ValueGraph<Bound>
+{
+  values: Vec<Bound>,
+  adj: Vec<Vec<usize>>,
+  var_match: Vec<Option<usize>>,
+  val_match: Vec<Option<usize>>
+}
+
+impl<Bound> ValueGraph<Bound> where
+  Bound: IntBound
+{
+  fn new<Domain>(doms: &[Domain]) -> Self where
+    Domain: IntDomain<Item=Bound>
+  {
+    let dom_values: Vec<Vec<Bound>> = doms.iter().map(domain_values).collect();
+    let mut values: Vec<Bound> = dom_values.iter().flat_map(|vs| vs.iter().cloned()).collect();
+    values.sort();
+    values.dedup();
+    let adj = dom_values.into_iter()
+      .map(|vs| vs.into_iter()
+        .map(|v| values.binary_search(&v).unwrap())
+        .collect())
+      .collect();
+    let num_values = values.len();
+    ValueGraph {
+      values: values,
+      adj: adj,
+      var_match: vec![None; doms.len()],
+      val_match: vec![None; num_values]
+    }
+  }
+
+  /// Keep the edges of the previous matching that are still in the graph.
+  fn init_matching(&mut self, matching: &[Option<Bound>]) {
+    for (x, m) in matching.iter().enumerate() {
+      if let Some(ref v) = *m {
+        if let Ok(v) = self.values.binary_search(v) {
+          if self.val_match[v].is_none() && self.adj[x].contains(&v) {
+            self.var_match[x] = Some(v);
+            self.val_match[v] = Some(x);
+          }
+        }
+      }
+    }
+  }
+
+  /// Complete the current matching with augmenting paths.
+  /// Returns `false` if some variables cannot be matched.
+  fn maximum_matching(&mut self) -> bool {
+    for x in 0..self.adj.len() {
+      if self.var_match[x].is_none() {
+        let mut visited = vec![false; self.values.len()];
+        if !self.augment(x, &mut visited) {
+          return false;
+        }
+      }
+    }
+    true
+  }
+
+  fn augment(&mut self, x: usize, visited: &mut Vec<bool>) -> bool {
+    for i in 0..self.adj[x].len() {
+      let v = self.adj[x][i];
+      if !visited[v] {
+        visited[v] = true;
+        let free = match self.val_match[v] {
+          None => true,
+          Some(y) => self.augment(y, visited)
+        };
+        if free {
+          self.var_match[x] = Some(v);
+          self.val_match[v] = Some(x);
+          return true;
+        }
+      }
+    }
+    false
+  }
+
+  /// The nodes `0..n` are the variables and `n..n+m` are the values.
+  /// A matched edge is oriented from the variable to the value, and the other edges from the value to the variable.
+  fn successors(&self) -> Vec<Vec<usize>> {
+    let n = self.adj.len();
+    let mut succ = vec![vec![]; n + self.values.len()];
+    for x in 0..n {
+      for &v in &self.adj[x] {
+        if self.var_match[x] == Some(v) {
+          succ[x].push(n + v);
+        }
+        else {
+          succ[n + v].push(x);
+        }
+      }
+    }
+    succ
+  }
+
+  /// `vital[x][i]` is `true` if the edge between `x` and the value `adj[x][i]` belongs to some maximum matching.
+  /// It is the case if the edge is in the current matching, if it is in a strongly connected component or if it lies on an alternating path starting from a free value.
+  fn vital_edges(&self) -> Vec<Vec<bool>> {
+    let n = self.adj.len();
+    let succ = self.successors();
+    let scc = strongly_connected_components(&succ);
+    let mut reached = vec![false; succ.len()];
+    let mut stack: Vec<usize> = (0..self.values.len())
+      .filter(|&v| self.val_match[v].is_none())
+      .map(|v| n + v)
+      .collect();
+    for &u in &stack {
+      reached[u] = true;
+    }
+    while let Some(u) = stack.pop() {
+      for &w in &succ[u] {
+        if !reached[w] {
+          reached[w] = true;
+          stack.push(w);
+        }
+      }
+    }
+    (0..n).map(|x| self.adj[x].iter()
+      .map(|&v| self.var_match[x] == Some(v) || reached[n + v] || scc[x] == scc[n + v])
+      .collect())
+    .collect()
+  }
+}
+
+/// Tarjan's algorithm, returns the component of every node of the graph.
+pub fn strongly_connected_components(succ: &[Vec<usize>]) -> Vec<usize> {
+  let num_nodes = succ.len();
+  let mut index = vec![None; num_nodes];
+  let mut low_link = vec![0; num_nodes];
+  let mut on_stack = vec![false; num_nodes];
+  let mut stack = vec![];
+  let mut component = vec![0; num_nodes];
+  let mut next_index = 0;
+  let mut next_component = 0;
+  for root in 0..num_nodes {
+    if index[root].is_some() {
+      continue;
+    }
+    // Each frame is a node and the position of the next successor to visit.
+    let mut frames = vec![(root, 0)];
+    index[root] = Some(next_index);
+    low_link[root] = next_index;
+    next_index += 1;
+    stack.push(root);
+    on_stack[root] = true;
+    while let Some(&mut (u, ref mut i)) = frames.last_mut() {
+      if *i < succ[u].len() {
+        let w = succ[u][*i];
+        *i += 1;
+        match index[w] {
+          None => {
+            index[w] = Some(next_index);
+            low_link[w] = next_index;
+            next_index += 1;
+            stack.push(w);
+            on_stack[w] = true;
+            frames.push((w, 0));
+          }
+          Some(w_index) if on_stack[w] => {
+            low_link[u] = ::std::cmp::min(low_link[u], w_index);
+          }
+          _ => ()
+        }
+      }
+      else {
+        frames.pop();
+        if let Some(&(parent, _)) = frames.last() {
+          low_link[parent] = ::std::cmp::min(low_link[parent], low_link[u]);
+        }
+        if Some(low_link[u]) == index[u] {
+          loop {
+            let w = stack.pop().unwrap();
+            on_stack[w] = false;
+            component[w] = next_component;
+            if w == u { break; }
+          }
+          next_component += 1;
+        }
+      }
+    }
+  }
+  component
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+
+  #[test]
+  fn distinct_domain_test() {
+    let zero = (0,0).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let dom0_1 = (0,1).to_interval_set();
+    let dom0_3 = (0,3).to_interval_set();
+
+    distinct_domain_test_one(1, vec![zero.clone(),one.clone(),two.clone()], True, True, vec![], true);
+    distinct_domain_test_one(2, vec![zero.clone(),zero.clone(),two.clone()], False, False, vec![], false);
+    distinct_domain_test_one(3, vec![zero.clone(),one.clone(),dom0_3.clone()], Unknown, True, vec![(2, Bound)], true);
+    distinct_domain_test_one(4, vec![zero.clone(),one.clone(),dom0_1.clone()], Unknown, Unknown, vec![], false);
+    distinct_domain_test_one(5, vec![dom0_3.clone()], True, True, vec![], true);
+  }
+
+  #[test]
+  fn distinct_domain_holes_test() {
+    let one_three = vec![(1,1),(3,3)].to_interval_set();
+    let dom1_3 = (1,3).to_interval_set();
+    let dom1_2 = (1,2).to_interval_set();
+    let dom0_4 = (0,4).to_interval_set();
+
+    // Not detected with bounds consistency.
+    distinct_domain_test_one(1, vec![one_three.clone(),one_three.clone(),dom1_3.clone()], Unknown, Unknown, vec![(2, Assignment)], true);
+    distinct_domain_test_one(2, vec![dom1_2.clone(),dom1_2.clone(),dom0_4.clone()], Unknown, Unknown, vec![(2, Inner)], true);
+    distinct_domain_test_one(3, vec![one_three.clone(),one_three.clone(),one_three.clone()], Unknown, Unknown, vec![], false);
+    distinct_domain_test_one(4, vec![dom1_2.clone(),dom0_4.clone(),dom0_4.clone()], Unknown, Unknown, vec![], true);
+  }
+
+  fn distinct_domain_test_one(test_num: u32, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, DistinctDomain::new, doms, before, after, delta_expected, propagate_success);
+  }
+}
@@ -0,0 +1,420 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The element constraint `y = array[index]` where the positions of `array` start at `0`.
+//!
+//! `ElementConst` is the element over an array of integer constants and `ElementVar` over an array of variables.
+//! In both cases, the index is restricted to the positions of the array.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use term::Constant;
+use propagators::{restrict_to_values, positions};
+use propagators::cmp::XNeqY;
+use propagators::table::Table;
+
+/// True if `index` only contains positions of an array of length `n` and `supported` of them are supported.
+fn only_positions<Domain, Bound>(index: &Domain, n: usize, supported: usize) -> bool where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  let positions = positions(n);
+  index.lower() >= positions[0]
+  && index.upper() <= positions[n-1]
+  && positions.iter().filter(|i| index.contains(i)).count() == supported
+}
+
+/// `y = array[index]` where `array` is an array of constants.
+#[derive(Debug)]
+pub struct ElementConst<VStore, Bound>
+{
+  index: Var<VStore>,
+  array: Vec<Bound>,
+  y: Var<VStore>
+}
+
+impl<VStore, Bound> ElementConst<VStore, Bound>
+{
+  /// Precondition: `array.len() > 0`.
+  pub fn new(index: Var<VStore>, array: Vec<Bound>, y: Var<VStore>) -> Self {
+    assert!(!array.is_empty(), "The array of `ElementConst` must be non-empty.");
+    ElementConst {
+      index: index,
+      array: array,
+      y: y
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for ElementConst<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    ElementConst {
+      index: self.index.bclone(),
+      array: self.array.clone(),
+      y: self.y.bclone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for ElementConst<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    self.y.display(model);
+    print!(" = {:?}[", self.array);
+    self.index.display(model);
+    print!("]");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for ElementConst<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// `(index, y)` is none of the pairs `(i, array[i])`.
+  fn not(&self) -> Formula<VStore> {
+    let tuples = positions(self.array.len()).into_iter()
+      .zip(self.array.iter())
+      .map(|(i, v)| vec![i, v.clone()])
+      .collect();
+    Box::new(Table::forbidden(vec![self.index.bclone(), self.y.bclone()], tuples))
+  }
+}
+
+impl<VStore, Domain, Bound> ElementConst<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// Positions `i` in the domain of the index such that `array[i]` is in the domain of `y`.
+  fn supports(&self, store: &VStore) -> Vec<Bound> {
+    let index = self.index.read(store);
+    let y = self.y.read(store);
+    positions(self.array.len()).into_iter()
+      .zip(self.array.iter())
+      .filter(|(i, v)| index.contains(i) && y.contains(v))
+      .map(|(i, _)| i)
+      .collect()
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for ElementConst<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: no position of the index is compatible with `y`.
+    // True: `y` is assigned and equal to `array[i]` for every position `i` of the index.
+    // Unknown: Everything else.
+    let supports = self.supports(store);
+    if supports.is_empty() {
+      False
+    }
+    else if self.y.read(store).is_singleton()
+         && only_positions(&self.index.read(store), self.array.len(), supports.len()) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for ElementConst<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let supports = self.supports(store);
+    let mut values: Vec<Bound> = positions(self.array.len()).into_iter()
+      .zip(self.array.iter())
+      .filter(|(i, _)| supports.contains(i))
+      .map(|(_, v)| v.clone())
+      .collect();
+    values.sort();
+    values.dedup();
+    let index = restrict_to_values(self.index.read(store), &supports);
+    let y = restrict_to_values(self.y.read(store), &values);
+    self.index.update(store, index) && self.y.update(store, y)
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for ElementConst<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = self.index.dependencies(FDEvent::Inner);
+    deps.extend(self.y.dependencies(FDEvent::Inner));
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+/// `y = array[index]` where `array` is an array of variables.
+///
+/// The index is domain consistent: a position `i` is removed when `array[i]` and `y` have no common value.
+/// The bounds of `y` are restricted to the values of the variables `array[i]` where `i` is in the domain of the index, and `array[i] = y` is enforced once the index is assigned.
+#[derive(Debug)]
+pub struct ElementVar<VStore>
+{
+  index: Var<VStore>,
+  array: Vec<Var<VStore>>,
+  y: Var<VStore>
+}
+
+impl<VStore> ElementVar<VStore>
+{
+  /// Precondition: `array.len() > 0`.
+  pub fn new(index: Var<VStore>, array: Vec<Var<VStore>>, y: Var<VStore>) -> Self {
+    assert!(!array.is_empty(), "The array of `ElementVar` must be non-empty.");
+    ElementVar {
+      index: index,
+      array: array,
+      y: y
+    }
+  }
+}
+
+impl<VStore> Clone for ElementVar<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    ElementVar {
+      index: self.index.bclone(),
+      array: self.array.iter().map(|v| v.bclone()).collect(),
+      y: self.y.bclone()
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for ElementVar<VStore>
+{
+  fn display(&self, model: &Model) {
+    self.y.display(model);
+    print!(" = ");
+    model.display_global("array", &self.array);
+    print!("[");
+    self.index.display(model);
+    print!("]");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for ElementVar<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// `index != i` or `y != array[i]` for every position `i`.
+  fn not(&self) -> Formula<VStore> {
+    let clauses = positions(self.array.len()).into_iter()
+      .zip(self.array.iter())
+      .map(|(i, v): (Bound, _)| {
+        let index_neq_i = Box::new(XNeqY::new(self.index.bclone(), Box::new(Constant::new(i)))) as Formula<VStore>;
+        let y_neq_v = Box::new(XNeqY::new(self.y.bclone(), v.bclone())) as Formula<VStore>;
+        Box::new(Disjunction::new(vec![index_neq_i, y_neq_v])) as Formula<VStore>
+      })
+      .collect();
+    Box::new(Conjunction::new(clauses))
+  }
+}
+
+impl<VStore, Domain, Bound> ElementVar<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// Positions `i` in the domain of the index such that `array[i]` and `y` share a value, with the position as a bound.
+  fn supports(&self, store: &VStore) -> Vec<(usize, Bound)> {
+    let index = self.index.read(store);
+    let y = self.y.read(store);
+    positions(self.array.len()).into_iter()
+      .enumerate()
+      .filter(|&(i, ref pos)| index.contains(pos) && self.array[i].read(store).overlap(&y))
+      .collect()
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for ElementVar<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: no position of the index is compatible with `y`.
+    // True: `y` and `array[i]` are assigned to the same value for every position `i` of the index.
+    // Unknown: Everything else.
+    let supports = self.supports(store);
+    if supports.is_empty() {
+      False
+    }
+    else if self.y.read(store).is_singleton()
+         && only_positions(&self.index.read(store), self.array.len(), supports.len())
+         && supports.iter().all(|&(i, _)| self.array[i].read(store).is_singleton()) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for ElementVar<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let supports = self.supports(store);
+    let positions: Vec<Bound> = supports.iter().map(|s| s.1.clone()).collect();
+    let index = restrict_to_values(self.index.read(store), &positions);
+    if !self.index.update(store, index) {
+      return false;
+    }
+    let y = self.y.read(store);
+    if supports.len() == 1 {
+      let i = supports[0].0;
+      let y = y.intersection(&self.array[i].read(store));
+      self.y.update(store, y.clone()) && self.array[i].update(store, y)
+    }
+    else {
+      let common: Vec<Domain> = supports.iter()
+        .map(|&(i, _)| y.intersection(&self.array[i].read(store)))
+        .collect();
+      let lb = common.iter().map(|d| d.lower()).min().unwrap();
+      let ub = common.iter().map(|d| d.upper()).max().unwrap();
+      self.y.update(store, y.shrink_left(lb).shrink_right(ub))
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for ElementVar<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps = self.index.dependencies(FDEvent::Inner);
+    deps.extend(self.y.dependencies(FDEvent::Inner));
+    for v in &self.array {
+      deps.extend(v.dependencies(FDEvent::Inner));
+    }
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+
+  fn element_const_test_one(test_num: u32, array: Vec<i32>, index: Interval<i32>, y: Interval<i32>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_propagator_test(test_num, move |mut vars| {
+        let y = vars.pop().unwrap();
+        let index = vars.pop().unwrap();
+        ElementConst::new(index, array, y)
+      },
+      vec![index, y], before, after, delta_expected, propagate_success);
+  }
+
+  fn element_const_set_test_one(test_num: u32, array: Vec<i32>, index: IntervalSet<i32>, y: IntervalSet<i32>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, move |mut vars| {
+        let y = vars.pop().unwrap();
+        let index = vars.pop().unwrap();
+        ElementConst::new(index, array, y)
+      },
+      vec![index, y], before, after, delta_expected, propagate_success);
+  }
+
+  fn element_var_test_one(test_num: u32, mut doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    // The index and `y` are the first two variables, the array starts at the variable `2`.
+    let y = doms.remove(1);
+    let index = doms.remove(0);
+    doms.insert(0, y);
+    doms.insert(0, index);
+    nary_propagator_test(test_num, |mut vars| {
+        let array = vars.split_off(2);
+        let y = vars.pop().unwrap();
+        let index = vars.pop().unwrap();
+        ElementVar::new(index, array, y)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn element_const_test() {
+    let array = vec![5, 1, 3, 1];
+
+    element_const_test_one(1, array.clone(), (0,3).to_interval(), (0,10).to_interval(), Unknown, Unknown, vec![(1, Bound)], true);
+    element_const_test_one(2, array.clone(), (-5,10).to_interval(), (1,5).to_interval(), Unknown, Unknown, vec![(0, Bound)], true);
+    element_const_test_one(3, array.clone(), (0,3).to_interval(), (2,4).to_interval(), Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+    element_const_test_one(4, array.clone(), (1,3).to_interval(), (0,2).to_interval(), Unknown, Unknown, vec![(1, Assignment)], true);
+    element_const_test_one(5, array.clone(), (1,1).to_interval(), (1,1).to_interval(), True, True, vec![], true);
+    element_const_test_one(6, array.clone(), (0,2).to_interval(), (6,10).to_interval(), False, False, vec![], false);
+    element_const_test_one(7, array.clone(), (4,10).to_interval(), (0,10).to_interval(), False, False, vec![], false);
+  }
+
+  #[test]
+  fn element_const_set_test() {
+    let array = vec![5, 1, 3, 1];
+
+    element_const_set_test_one(1, array.clone(), (0,3).to_interval_set(), (0,10).to_interval_set(),
+      Unknown, Unknown, vec![(1, Bound)], true);
+    element_const_set_test_one(2, array.clone(), (0,3).to_interval_set(), (4,5).to_interval_set(),
+      Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+    element_const_set_test_one(3, array.clone(), vec![(0,0),(2,2)].to_interval_set(), (1,5).to_interval_set(),
+      Unknown, Unknown, vec![(1, Bound)], true);
+    element_const_set_test_one(4, array.clone(), (0,3).to_interval_set(), vec![(0,2),(4,10)].to_interval_set(),
+      Unknown, Unknown, vec![(0, Inner), (1, Bound)], true);
+  }
+
+  #[test]
+  fn element_var_test() {
+    let dom0_1 = (0,1).to_interval();
+    let dom0_2 = (0,2).to_interval();
+    let dom0_10 = (0,10).to_interval();
+    let dom1_1 = (1,1).to_interval();
+    let dom3_5 = (3,5).to_interval();
+    let dom6_8 = (6,8).to_interval();
+
+    // Variables: index, y, array[0], ...
+    element_var_test_one(1, vec![dom0_2, dom0_10, dom3_5, dom6_8, dom0_10], Unknown, Unknown, vec![], true);
+    element_var_test_one(2, vec![dom0_1, dom0_10, dom3_5, dom6_8, dom0_10], Unknown, Unknown, vec![(1, Bound)], true);
+    element_var_test_one(3, vec![dom0_2, dom0_2, dom3_5, dom6_8, dom1_1], Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+    element_var_test_one(4, vec![dom1_1, dom0_10, dom3_5, dom6_8], Unknown, Unknown, vec![(1, Bound)], true);
+    element_var_test_one(5, vec![dom0_10, (7,7).to_interval(), dom3_5, dom6_8], Unknown, True, vec![(0, Assignment), (3, Assignment)], true);
+    element_var_test_one(6, vec![dom0_1, dom0_2, dom3_5, dom6_8], False, False, vec![], false);
+    element_var_test_one(7, vec![dom1_1, dom1_1, dom1_1, dom1_1], True, True, vec![], true);
+  }
+}
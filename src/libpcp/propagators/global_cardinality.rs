@@ -0,0 +1,519 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The global cardinality constraint bounds the number of occurrences of some values in an array of variables.
+//!
+//! It is the flow-based algorithm described in `Generalized Arc Consistency for Global Cardinality Constraint`, Régin, 1996.
+//! A variable sends one unit of flow to a value of its domain and a value `v` receives between `l(v)` and `u(v)` units, where `[l(v)..u(v)]` are the bounds of the number of occurrences of `v`.
+//! A feasible flow is computed, and a value is removed from the domain of a variable if the edge between them does not belong to any feasible flow, which is checked with the strongly connected components of the residual graph.
+//! When the numbers of occurrences are variables, their bounds are restricted to the minimal and maximal flow that can go through their value.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use term::Constant;
+use propagators::{domain_values, positions, strongly_connected_components, count, at_most, at_least};
+use std::collections::VecDeque;
+
+/// The number of occurrences of `values[j]` in `vars` is in `bounds[j]`, and is equal to `counts[j]` if the counts are variables.
+/// The values not in `values` can occur any number of times.
+#[derive(Debug)]
+pub struct GlobalCardinality<VStore, Bound>
+{
+  vars: Vec<Var<VStore>>,
+  values: Vec<Bound>,
+  bounds: Vec<(Bound, Bound)>,
+  counts: Option<Vec<Var<VStore>>>,
+  // The assignment of the last feasible flow, used as a starting point for the next one.
+  assignment: Vec<Option<Bound>>
+}
+
+impl<VStore, Bound> GlobalCardinality<VStore, Bound> where
+  Bound: IntBound
+{
+  /// The number of occurrences of `values[j]` is in `[bounds[j].0..bounds[j].1]`.
+  /// Precondition: the values are distinct and there is one pair of bounds per value.
+  pub fn new(vars: Vec<Var<VStore>>, values: Vec<Bound>, bounds: Vec<(Bound, Bound)>) -> Self {
+    assert_eq!(values.len(), bounds.len(), "Every value of `GlobalCardinality` must have bounds.");
+    GlobalCardinality::make(vars, values, bounds, None)
+  }
+
+  /// The number of occurrences of `values[j]` is equal to `counts[j]`.
+  /// Precondition: the values are distinct and there is one count per value.
+  pub fn with_counts(vars: Vec<Var<VStore>>, values: Vec<Bound>, counts: Vec<Var<VStore>>) -> Self {
+    assert_eq!(values.len(), counts.len(), "Every value of `GlobalCardinality` must have a count.");
+    let n: Bound = positions(vars.len() + 1).pop().unwrap();
+    let bounds = values.iter().map(|_| (Bound::zero(), n.clone())).collect();
+    GlobalCardinality::make(vars, values, bounds, Some(counts))
+  }
+
+  fn make(vars: Vec<Var<VStore>>, values: Vec<Bound>, bounds: Vec<(Bound, Bound)>,
+    counts: Option<Vec<Var<VStore>>>) -> Self
+  {
+    let mut sorted = values.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(sorted.len(), values.len(), "The values of `GlobalCardinality` must be distinct.");
+    let assignment = vars.iter().map(|_| None).collect();
+    GlobalCardinality {
+      vars: vars,
+      values: values,
+      bounds: bounds,
+      counts: counts,
+      assignment: assignment
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for GlobalCardinality<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    GlobalCardinality {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      values: self.values.clone(),
+      bounds: self.bounds.clone(),
+      counts: self.counts.as_ref().map(|counts| counts.iter().map(|c| c.bclone()).collect()),
+      assignment: self.assignment.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for GlobalCardinality<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    print!("global_cardinality(");
+    if !self.vars.is_empty() {
+      model.display_global("x", &self.vars);
+    }
+    print!(", {:?}, ", self.values);
+    match self.counts {
+      Some(ref counts) if !counts.is_empty() => model.display_global("count", counts),
+      _ => print!("{:?}", self.bounds)
+    }
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for GlobalCardinality<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// The number of occurrences of a value `values[j]` is not `counts[j]`, or is out of `bounds[j]`: `at_most(l - 1) \/ at_least(u + 1)`.
+  /// Without values, the constraint is always entailed and its negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    if self.values.is_empty() {
+      return Box::new(Negation::new(Box::new(self.clone())));
+    }
+    let vars = || self.vars.iter().map(|v| v.bclone()).collect();
+    let constant = |v: Bound| Box::new(Constant::new(v)) as Var<VStore>;
+    let mut fs = vec![];
+    for (j, v) in self.values.iter().enumerate() {
+      match self.counts {
+        Some(ref counts) => fs.push(count(counts[j].bclone(), vars(), v.clone()).not()),
+        None => {
+          let (ref l, ref u) = self.bounds[j];
+          fs.push(Box::new(at_most(constant(l.clone() - Bound::one()), vars(), v.clone())) as Formula<VStore>);
+          fs.push(Box::new(at_least(constant(u.clone() + Bound::one()), vars(), v.clone())));
+        }
+      }
+    }
+    Box::new(Disjunction::new(fs))
+  }
+}
+
+impl<VStore, Domain, Bound> GlobalCardinality<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The network of the current domains, `None` if the bounds of a value are empty.
+  fn network(&self, store: &VStore) -> Option<FlowNetwork<Bound>> {
+    let n = self.vars.len();
+    let numbers: Vec<Bound> = positions(n + 1);
+    let doms: Vec<Domain> = self.vars.iter().map(|v| v.read(store)).collect();
+    let mut values: Vec<Bound> = doms.iter().flat_map(domain_values).collect();
+    values.extend(self.values.iter().cloned());
+    values.sort();
+    values.dedup();
+    let mut lower = vec![0; values.len()];
+    let mut upper = vec![n; values.len()];
+    for (j, value) in self.values.iter().enumerate() {
+      let v = values.binary_search(value).unwrap();
+      let (mut lb, mut ub) = self.bounds[j].clone();
+      if let Some(ref counts) = self.counts {
+        let count = counts[j].read(store);
+        if count.is_empty() {
+          return None;
+        }
+        lb = ::std::cmp::max(lb, count.lower());
+        ub = ::std::cmp::min(ub, count.upper());
+      }
+      // `lower[v]` is the smallest number greater or equal to `lb`, and `upper[v]` the greatest number smaller or equal to `ub`.
+      lower[v] = numbers.iter().take_while(|&k| k < &lb).count();
+      let below_ub = numbers.iter().take_while(|&k| k <= &ub).count();
+      if below_ub == 0 || lower[v] > below_ub - 1 {
+        return None;
+      }
+      upper[v] = below_ub - 1;
+    }
+    let adj = doms.iter()
+      .map(|d| domain_values(d).into_iter()
+        .map(|a| values.binary_search(&a).unwrap())
+        .collect())
+      .collect();
+    Some(FlowNetwork::new(values, adj, lower, upper))
+  }
+
+  fn feasible_network(&self, store: &VStore) -> Option<FlowNetwork<Bound>> {
+    self.network(store).and_then(|mut net| {
+      net.init(&self.assignment);
+      if net.feasible_flow() { Some(net) } else { None }
+    })
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for GlobalCardinality<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: there is no feasible flow.
+    // True: the variables and the counts are assigned, and there is a feasible flow.
+    // Unknown: Everything else.
+    if self.feasible_network(store).is_none() {
+      False
+    }
+    else if self.vars.iter().all(|v| v.read(store).is_singleton())
+         && self.counts.iter().flat_map(|c| c.iter()).all(|c| c.read(store).is_singleton()) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for GlobalCardinality<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let net = match self.feasible_network(store) {
+      Some(net) => net,
+      None => return false
+    };
+    self.assignment = net.assigned.iter()
+      .map(|a| a.map(|v| net.values[v].clone()))
+      .collect();
+    let component = net.residual_components();
+    let n = self.vars.len();
+    for x in 0..n {
+      let mut dom = self.vars[x].read(store);
+      for &v in &net.adj[x] {
+        if net.assigned[x] != Some(v) && component[x] != component[n + v] {
+          dom = dom.difference(&net.values[v]);
+        }
+      }
+      if !self.vars[x].update(store, dom) {
+        return false;
+      }
+    }
+    if let Some(ref mut counts) = self.counts {
+      let numbers: Vec<Bound> = positions(n + 1);
+      for (j, value) in self.values.iter().enumerate() {
+        let v = net.values.binary_search(value).unwrap();
+        let (min, max) = (net.min_flow(v), net.max_flow(v));
+        let count = counts[j].read(store)
+          .shrink_left(numbers[min].clone())
+          .shrink_right(numbers[max].clone());
+        if !counts[j].update(store, count) {
+          return false;
+        }
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for GlobalCardinality<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    if let Some(ref counts) = self.counts {
+      deps.extend(counts.iter().flat_map(|c| c.dependencies(FDEvent::Bound)));
+    }
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+/// Flow network between the variables and the values, the values are indexed by their position in `values` and `adj[x]` contains the values of the variable `x`.
+/// The flow is represented by the value `assigned[x]` receiving the unit of flow of `x`, and `flow[v]` is the number of variables assigned to `v`.
+#[derive(Clone)]
+struct FlowNetwork<Bound>
+{
+  values: Vec<Bound>,
+  adj: Vec<Vec<usize>>,
+  // `vars_of[v]` contains the variables having `v` in their domain.
+  vars_of: Vec<Vec<usize>>,
+  lower: Vec<usize>,
+  upper: Vec<usize>,
+  assigned: Vec<Option<usize>>,
+  flow: Vec<usize>
+}
+
+impl<Bound> FlowNetwork<Bound> where
+  Bound: IntBound
+{
+  fn new(values: Vec<Bound>, adj: Vec<Vec<usize>>, lower: Vec<usize>, upper: Vec<usize>) -> Self {
+    let mut vars_of = vec![vec![]; values.len()];
+    for (x, vals) in adj.iter().enumerate() {
+      for &v in vals {
+        vars_of[v].push(x);
+      }
+    }
+    FlowNetwork {
+      flow: vec![0; values.len()],
+      assigned: vec![None; adj.len()],
+      values: values,
+      adj: adj,
+      vars_of: vars_of,
+      lower: lower,
+      upper: upper
+    }
+  }
+
+  /// Keeps the assignments of `hint` that are still in the domains and respect the upper bounds.
+  fn init(&mut self, hint: &[Option<Bound>]) {
+    for (x, h) in hint.iter().enumerate() {
+      if let Some(ref value) = *h {
+        if let Ok(v) = self.values.binary_search(value) {
+          if self.adj[x].contains(&v) && self.flow[v] < self.upper[v] {
+            self.assigned[x] = Some(v);
+            self.flow[v] += 1;
+          }
+        }
+      }
+    }
+  }
+
+  /// Assigns every variable while respecting the upper bounds, and then increases the flow of the values below their lower bounds.
+  fn feasible_flow(&mut self) -> bool {
+    for x in 0..self.adj.len() {
+      if self.assigned[x].is_none() && !self.push(vec![x], None) {
+        return false;
+      }
+    }
+    for v in 0..self.values.len() {
+      while self.flow[v] < self.lower[v] {
+        if !self.pull(v) {
+          return false;
+        }
+      }
+    }
+    true
+  }
+
+  /// Moves one of the variables `start` to another value along an alternating path ending on a value `w` with `flow[w] < upper[w]`.
+  /// The flow of `from`, the value of the variables `start` if any, is decreased and the flow of `w` increased.
+  fn push(&mut self, start: Vec<usize>, from: Option<usize>) -> bool {
+    let mut visited = vec![false; self.values.len()];
+    if let Some(v) = from {
+      visited[v] = true;
+    }
+    // `parent[v]` is the variable moving to `v`.
+    let mut parent = vec![0; self.values.len()];
+    let mut queue: VecDeque<usize> = start.iter().cloned().collect();
+    while let Some(y) = queue.pop_front() {
+      for &v in &self.adj[y] {
+        if visited[v] {
+          continue;
+        }
+        visited[v] = true;
+        parent[v] = y;
+        if self.flow[v] < self.upper[v] {
+          self.flow[v] += 1;
+          let mut v = v;
+          loop {
+            let y = parent[v];
+            let old = self.assigned[y].replace(v);
+            match old {
+              Some(w) if Some(w) != from => v = w,
+              Some(w) => { self.flow[w] -= 1; return true; }
+              None => return true
+            }
+          }
+        }
+        queue.extend(self.assigned.iter().enumerate()
+          .filter(|&(_, a)| *a == Some(v))
+          .map(|(z, _)| z));
+      }
+    }
+    false
+  }
+
+  /// Increases the flow of `v` by moving a variable of another value to `v`, along an alternating path starting from a value `w` with `flow[w] > lower[w]`.
+  fn pull(&mut self, v: usize) -> bool {
+    let mut visited = vec![false; self.values.len()];
+    visited[v] = true;
+    // `parent[w] = (y, u)` if the variable `y` moves from `w` to `u`.
+    let mut parent = vec![(0, 0); self.values.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back(v);
+    while let Some(u) = queue.pop_front() {
+      for &y in &self.vars_of[u] {
+        let w = match self.assigned[y] {
+          Some(w) if !visited[w] => w,
+          _ => continue
+        };
+        visited[w] = true;
+        parent[w] = (y, u);
+        if self.flow[w] > self.lower[w] {
+          self.flow[w] -= 1;
+          self.flow[v] += 1;
+          let mut w = w;
+          while w != v {
+            let (y, u) = parent[w];
+            self.assigned[y] = Some(u);
+            w = u;
+          }
+          return true;
+        }
+        queue.push_back(w);
+      }
+    }
+    false
+  }
+
+  /// Maximal flow through `v` among the feasible flows.
+  fn max_flow(&self, v: usize) -> usize {
+    let mut net = self.clone();
+    while net.flow[v] < net.upper[v] && net.pull(v) {}
+    net.flow[v]
+  }
+
+  /// Minimal flow through `v` among the feasible flows.
+  fn min_flow(&self, v: usize) -> usize {
+    let mut net = self.clone();
+    while net.flow[v] > net.lower[v] {
+      let start = (0..net.assigned.len()).filter(|&x| net.assigned[x] == Some(v)).collect();
+      if !net.push(start, Some(v)) {
+        break;
+      }
+    }
+    net.flow[v]
+  }
+
+  /// Strongly connected components of the residual graph, the nodes are the variables, followed by the values and the node merging the source and the sink.
+  fn residual_components(&self) -> Vec<usize> {
+    let n = self.adj.len();
+    let m = self.values.len();
+    let st = n + m;
+    let mut succ = vec![vec![]; n + m + 1];
+    for x in 0..n {
+      for &v in &self.adj[x] {
+        if self.assigned[x] == Some(v) {
+          succ[n + v].push(x);
+        }
+        else {
+          succ[x].push(n + v);
+        }
+      }
+    }
+    for v in 0..m {
+      if self.flow[v] < self.upper[v] {
+        succ[n + v].push(st);
+      }
+      if self.flow[v] > self.lower[v] {
+        succ[st].push(n + v);
+      }
+    }
+    strongly_connected_components(&succ)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+
+  fn gcc_test_one(test_num: u32, values: Vec<i32>, bounds: Vec<(i32, i32)>, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, |vars| GlobalCardinality::new(vars, values, bounds),
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  /// The counts are the last variables.
+  fn gcc_counts_test_one(test_num: u32, values: Vec<i32>, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, |mut vars| {
+        let counts = vars.split_off(vars.len() - values.len());
+        GlobalCardinality::with_counts(vars, values, counts)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn global_cardinality_test() {
+    let dom1_2 = (1,2).to_interval_set();
+    let dom1_3 = (1,3).to_interval_set();
+    let dom1_1 = (1,1).to_interval_set();
+    let dom2_2 = (2,2).to_interval_set();
+
+    gcc_test_one(1, vec![1, 2], vec![(0, 1), (0, 2)], vec![dom1_3.clone(); 3], Unknown, Unknown, vec![], true);
+    // The value `3` is not constrained.
+    gcc_test_one(2, vec![1, 2], vec![(1, 1), (2, 2)], vec![dom1_2.clone(), dom1_3.clone(), dom1_3.clone()], Unknown, Unknown, vec![(1, Bound), (2, Bound)], true);
+    gcc_test_one(3, vec![1, 2], vec![(1, 1), (1, 1)], vec![dom1_1.clone(), dom1_3.clone(), dom1_3.clone()], Unknown, Unknown, vec![(1, Bound), (2, Bound)], true);
+    gcc_test_one(4, vec![1], vec![(0, 1)], vec![dom1_1.clone(), dom1_3.clone(), dom1_1.clone()], False, False, vec![], false);
+    gcc_test_one(5, vec![1, 2], vec![(2, 3), (1, 1)], vec![dom1_2.clone(), dom2_2.clone(), dom1_3.clone()], Unknown, True, vec![(0, Assignment), (2, Assignment)], true);
+    gcc_test_one(6, vec![1, 2, 3], vec![(1, 1), (1, 1), (1, 1)], vec![dom1_2.clone(), dom1_2.clone(), dom1_3.clone()], Unknown, Unknown, vec![(2, Assignment)], true);
+    gcc_test_one(7, vec![4], vec![(1, 3)], vec![dom1_3.clone(); 3], False, False, vec![], false);
+    gcc_test_one(8, vec![1], vec![(3, 2)], vec![dom1_3.clone(); 3], False, False, vec![], false);
+  }
+
+  #[test]
+  fn global_cardinality_counts_test() {
+    let dom0_3 = (0,3).to_interval_set();
+    let dom1_2 = (1,2).to_interval_set();
+    let dom1_3 = (1,3).to_interval_set();
+    let dom1_1 = (1,1).to_interval_set();
+    let dom2_2 = (2,2).to_interval_set();
+
+    gcc_counts_test_one(1, vec![1, 2], vec![dom1_3.clone(), dom1_3.clone(), dom0_3.clone(), dom0_3.clone()], Unknown, Unknown, vec![(2, Bound), (3, Bound)], true);
+    gcc_counts_test_one(2, vec![1, 2], vec![dom1_1.clone(), dom1_2.clone(), dom0_3.clone(), dom0_3.clone()], Unknown, Unknown, vec![(2, Bound), (3, Bound)], true);
+    gcc_counts_test_one(3, vec![1, 2], vec![dom1_2.clone(), dom1_2.clone(), dom2_2.clone(), dom0_3.clone()], Unknown, True, vec![(0, Assignment), (1, Assignment), (3, Assignment)], true);
+    gcc_counts_test_one(4, vec![1, 2], vec![dom1_2.clone(), dom1_2.clone(), dom1_1.clone(), dom1_1.clone()], Unknown, Unknown, vec![], true);
+    gcc_counts_test_one(5, vec![3], vec![dom1_2.clone(), dom1_2.clone(), dom1_1.clone()], False, False, vec![], false);
+    gcc_counts_test_one(6, vec![1], vec![dom1_1.clone(), dom1_1.clone(), dom2_2.clone()], True, True, vec![], true);
+  }
+}
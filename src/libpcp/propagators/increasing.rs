@@ -0,0 +1,261 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ordering constraints on a chain of variables: `x[0] <= x[1] <= ... <= x[n-1]` and its strict and decreasing variants.
+//!
+//! It replaces the `n-1` propagators `x[i] <= x[i+1]` by a single one.
+//! The lower bounds are pushed from left to right and the upper bounds from right to left, so two linear passes are enough to obtain bounds consistency.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use propagators::cmp::{x_greater_y, x_geq_y};
+use std::cmp::max;
+
+/// `x[0] <= x[1] <= ... <= x[n-1]`, the variants are built with `strictly_increasing`, `decreasing` and `strictly_decreasing`.
+#[derive(Debug)]
+pub struct Increasing<VStore>
+{
+  /// The variables in the increasing order, they are reversed for the decreasing variants.
+  vars: Vec<Var<VStore>>,
+  strict: bool,
+  decreasing: bool
+}
+
+pub type StrictlyIncreasing<VStore> = Increasing<VStore>;
+pub type Decreasing<VStore> = Increasing<VStore>;
+pub type StrictlyDecreasing<VStore> = Increasing<VStore>;
+
+impl<VStore> Increasing<VStore>
+{
+  pub fn new(vars: Vec<Var<VStore>>) -> Self {
+    Increasing::make(vars, false, false)
+  }
+
+  fn make(mut vars: Vec<Var<VStore>>, strict: bool, decreasing: bool) -> Self {
+    if decreasing {
+      vars.reverse();
+    }
+    Increasing {
+      vars: vars,
+      strict: strict,
+      decreasing: decreasing
+    }
+  }
+}
+
+/// `x[0] < x[1] < ... < x[n-1]`.
+pub fn strictly_increasing<VStore>(vars: Vec<Var<VStore>>) -> StrictlyIncreasing<VStore> {
+  Increasing::make(vars, true, false)
+}
+
+/// `x[0] >= x[1] >= ... >= x[n-1]`.
+pub fn decreasing<VStore>(vars: Vec<Var<VStore>>) -> Decreasing<VStore> {
+  Increasing::make(vars, false, true)
+}
+
+/// `x[0] > x[1] > ... > x[n-1]`.
+pub fn strictly_decreasing<VStore>(vars: Vec<Var<VStore>>) -> StrictlyDecreasing<VStore> {
+  Increasing::make(vars, true, true)
+}
+
+impl<VStore> Clone for Increasing<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Increasing {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      strict: self.strict,
+      decreasing: self.decreasing
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Increasing<VStore> where
+  VStore: Collection
+{
+  fn display(&self, model: &Model) {
+    let name = match (self.strict, self.decreasing) {
+      (false, false) => "increasing",
+      (true, false) => "strictly_increasing",
+      (false, true) => "decreasing",
+      (true, true) => "strictly_decreasing"
+    };
+    print!("{}(", name);
+    let mut vars: Vec<_> = self.vars.iter().map(|v| v.bclone()).collect();
+    if self.decreasing {
+      vars.reverse();
+    }
+    if !vars.is_empty() {
+      model.display_global("x", &vars);
+    }
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Increasing<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// Two consecutive variables are not ordered: `x[i] > x[i+1]`, or `x[i] >= x[i+1]` for the strict variants.
+  /// Without two variables, the constraint is always entailed and its negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    if self.vars.len() < 2 {
+      return Box::new(Negation::new(Box::new(self.clone())));
+    }
+    Box::new(Disjunction::new(self.vars.windows(2)
+      .map(|w| {
+        let (x, y) = (w[0].bclone(), w[1].bclone());
+        if self.strict { Box::new(x_geq_y(x, y)) as Formula<VStore> }
+        else { Box::new(x_greater_y(x, y)) }
+      })
+      .collect()))
+  }
+}
+
+impl<VStore, Domain, Bound> Increasing<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The minimal distance between two consecutive variables.
+  fn gap(&self) -> Bound {
+    if self.strict { Bound::one() } else { Bound::zero() }
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Increasing<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: the lower bounds pushed from the left exceed an upper bound.
+    // True: every `x[i]` is necessarily smaller than `x[i+1]`.
+    // Unknown: Everything else.
+    let gap = self.gap();
+    let mut lb: Option<Bound> = None;
+    for x in &self.vars {
+      let x = x.read(store);
+      let x_lb = match lb {
+        Some(lb) => max(lb + gap.clone(), x.lower()),
+        None => x.lower()
+      };
+      if x_lb > x.upper() {
+        return False;
+      }
+      lb = Some(x_lb);
+    }
+    let entailed = self.vars.windows(2).all(|w|
+      w[0].read(store).upper() + gap.clone() <= w[1].read(store).lower());
+    if entailed { True } else { Unknown }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Increasing<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let gap = self.gap();
+    let n = self.vars.len();
+    for i in 1..n {
+      let lb = self.vars[i-1].read(store).lower() + gap.clone();
+      let x = self.vars[i].read(store).shrink_left(lb);
+      if !self.vars[i].update(store, x) {
+        return false;
+      }
+    }
+    for i in (1..n).rev() {
+      let ub = self.vars[i].read(store).upper() - gap.clone();
+      let x = self.vars[i-1].read(store).shrink_right(ub);
+      if !self.vars[i-1].update(store, x) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Increasing<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Bound))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  #[test]
+  fn increasing_test() {
+    let dom0_3 = (0,3).to_interval();
+    let dom1_2 = (1,2).to_interval();
+    let dom2_4 = (2,4).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let three = (3,3).to_interval();
+
+    nary_propagator_test(1, Increasing::new, vec![dom0_3, dom1_2, dom0_3], Unknown, Unknown, vec![(0, Bound), (2, Bound)], true);
+    nary_propagator_test(2, Increasing::new, vec![one, one, two], True, True, vec![], true);
+    nary_propagator_test(3, Increasing::new, vec![two, dom0_3, one], False, False, vec![], false);
+    // The lower bound of `x[0]` is pushed through the whole chain.
+    nary_propagator_test(4, Increasing::new, vec![three, dom0_3, dom2_4], Unknown, True, vec![(1, Assignment), (2, Bound)], true);
+    nary_propagator_test(5, Increasing::new, vec![dom1_2, dom2_4], True, True, vec![], true);
+  }
+
+  #[test]
+  fn strictly_increasing_test() {
+    let dom0_3 = (0,3).to_interval();
+    let dom1_2 = (1,2).to_interval();
+    let one = (1,1).to_interval();
+
+    nary_propagator_test(1, strictly_increasing, vec![dom0_3, dom0_3, dom0_3], Unknown, Unknown, vec![(0, Bound), (1, Bound), (2, Bound)], true);
+    // No pair is violated but there are not enough values for the whole chain.
+    nary_propagator_test(2, strictly_increasing, vec![dom1_2, dom1_2, dom1_2], False, False, vec![], false);
+    nary_propagator_test(3, strictly_increasing, vec![one, one], False, False, vec![], false);
+    nary_propagator_test(4, strictly_increasing, vec![dom0_3, dom1_2, dom0_3], Unknown, Unknown, vec![(0, Bound), (2, Bound)], true);
+  }
+
+  #[test]
+  fn decreasing_test() {
+    let dom0_3 = (0,3).to_interval();
+    let dom1_2 = (1,2).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+
+    nary_propagator_test(1, decreasing, vec![dom0_3, dom1_2, dom0_3], Unknown, Unknown, vec![(0, Bound), (2, Bound)], true);
+    nary_propagator_test(2, decreasing, vec![two, two, one], True, True, vec![], true);
+    nary_propagator_test(3, strictly_decreasing, vec![dom0_3, dom1_2, dom0_3], Unknown, Unknown, vec![(0, Bound), (2, Bound)], true);
+    nary_propagator_test(4, strictly_decreasing, vec![two, two, one], False, False, vec![], false);
+    nary_propagator_test(5, strictly_decreasing, vec![one, dom0_3], Unknown, True, vec![(1, Assignment)], true);
+  }
+}
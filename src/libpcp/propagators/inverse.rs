@@ -0,0 +1,226 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint `Inverse` channels two arrays of variables such that `x[i] = j <=> y[j] = i`.
+//!
+//! The value `j` is kept in `x[i]` only if `i` is in `y[j]`, and conversely.
+//! The constraint implies that `x` and `y` are permutations of `0..n`, and the domain consistent propagator `DistinctDomain` is applied on `x` between two channeling steps.
+//! The filtering is domain consistent: a value is kept if it belongs to a perfect matching between the positions and the values.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use term::Constant;
+use propagators::distinct_domain::*;
+use propagators::element::ElementVar;
+use propagators::{domain_values, positions, restrict_to_values};
+
+/// `x[i] = j <=> y[j] = i` for all `i` and `j` in `0..n`.
+#[derive(Debug)]
+pub struct Inverse<VStore, Bound>
+{
+  x: Vec<Var<VStore>>,
+  y: Vec<Var<VStore>>,
+  distinct: DistinctDomain<VStore, Bound>
+}
+
+impl<VStore, Bound> Inverse<VStore, Bound> where
+  VStore: Collection
+{
+  /// Precondition: `x.len() == y.len()` and `x.len() > 0`.
+  pub fn new(x: Vec<Var<VStore>>, y: Vec<Var<VStore>>) -> Self {
+    assert_eq!(x.len(), y.len(), "The arrays of `Inverse` must have the same length.");
+    let distinct = DistinctDomain::new(x.iter().map(|v| v.bclone()).collect());
+    Inverse {
+      x: x,
+      y: y,
+      distinct: distinct
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for Inverse<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    Inverse {
+      x: self.x.iter().map(|v| v.bclone()).collect(),
+      y: self.y.iter().map(|v| v.bclone()).collect(),
+      distinct: self.distinct.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for Inverse<VStore, Bound>
+{
+  fn display(&self, model: &Model) {
+    print!("inverse(");
+    model.display_global("x", &self.x);
+    print!(", ");
+    model.display_global("y", &self.y);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Inverse<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// `y[x[i]] != i` for some `i`, with `x[i]` possibly out of the positions of `y`.
+  /// Indeed, if `y[x[i]] = i` for every `i`, then `x` is a permutation of `0..n` and `y` is its inverse.
+  fn not(&self) -> Formula<VStore> {
+    let fs = positions(self.x.len()).into_iter()
+      .zip(self.x.iter())
+      .map(|(i, x): (Bound, _)| ElementVar::new(
+        x.bclone(),
+        self.y.iter().map(|v| v.bclone()).collect(),
+        Box::new(Constant::new(i))).not())
+      .collect();
+    Box::new(Disjunction::new(fs))
+  }
+}
+
+/// Restricts `x[i]` to the values `j` in `0..n` such that `i` is in `y[j]`.
+fn channel<VStore, Domain, Bound>(x: &mut [Var<VStore>], y: &[Var<VStore>], nodes: &[Bound], vstore: &mut VStore) -> bool where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  let y_doms: Vec<Domain> = y.iter().map(|v| v.read(vstore)).collect();
+  for (i, xi) in x.iter_mut().enumerate() {
+    let dom = xi.read(vstore);
+    let values: Vec<Bound> = domain_values(&dom).into_iter()
+      .filter(|j| match nodes.binary_search(j) {
+        Ok(j) => y_doms[j].contains(&nodes[i]),
+        Err(_) => false
+      })
+      .collect();
+    if !xi.update(vstore, restrict_to_values(dom, &values)) {
+      return false;
+    }
+  }
+  true
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Inverse<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, vstore: &VStore) -> SKleene {
+    // False: `x[i]` is assigned to `j` and `i` is not in `y[j]`, or conversely.
+    // True: `x[i]` is assigned to `j` and `y[j]` to `i`, for every `i`.
+    // Unknown: Everything else.
+    let nodes: Vec<Bound> = positions(self.x.len());
+    let x_doms: Vec<Domain> = self.x.iter().map(|v| v.read(vstore)).collect();
+    let y_doms: Vec<Domain> = self.y.iter().map(|v| v.read(vstore)).collect();
+    let mut assigned = true;
+    for &(a, b) in &[(&x_doms, &y_doms), (&y_doms, &x_doms)] {
+      for (i, dom) in a.iter().enumerate() {
+        if dom.is_empty() {
+          return False;
+        }
+        if !dom.is_singleton() {
+          assigned = false;
+          continue;
+        }
+        match nodes.binary_search(&dom.lower()) {
+          Ok(j) if b[j].contains(&nodes[i]) => (),
+          _ => return False
+        }
+      }
+    }
+    if assigned { True } else { Unknown }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Inverse<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, vstore: &mut VStore) -> bool {
+    let nodes: Vec<Bound> = positions(self.x.len());
+    channel(&mut self.x, &self.y, &nodes, vstore)
+    && self.distinct.propagate(vstore)
+    && channel(&mut self.y, &self.x, &nodes, vstore)
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for Inverse<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.x.iter().chain(self.y.iter())
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+
+  /// The first half of the variables is `x` and the second half is `y`.
+  fn inverse_test_one(test_num: u32, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, |mut x| {
+        let y = x.split_off(x.len() / 2);
+        Inverse::new(x, y)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn inverse_test() {
+    let zero = (0,0).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let dom0_1 = (0,1).to_interval_set();
+    let dom0_2 = (0,2).to_interval_set();
+    let dom1_2 = (1,2).to_interval_set();
+    let dom0_5 = (0,5).to_interval_set();
+    let zero_two = vec![(0,0),(2,2)].to_interval_set();
+
+    inverse_test_one(1, vec![one.clone(), two.clone(), zero.clone(), two.clone(), zero.clone(), one.clone()], True, True, vec![], true);
+    inverse_test_one(2, vec![one.clone(), two.clone(), zero.clone(), one.clone(), zero.clone(), two.clone()], False, False, vec![], false);
+    inverse_test_one(3, vec![dom0_2.clone(); 6], Unknown, Unknown, vec![], true);
+    // Channeling: `x[0] = 1` implies `y[1] = 0`, which is removed from `y[0]` and `y[2]`.
+    inverse_test_one(4, vec![one.clone(), dom0_2.clone(), dom0_2.clone(), dom0_2.clone(), dom0_2.clone(), dom0_2.clone()],
+      Unknown, Unknown, vec![(1, Inner), (2, Inner), (3, Bound), (4, Assignment), (5, Bound)], true);
+    // Bounds out of the positions.
+    inverse_test_one(5, vec![dom0_5.clone(), dom0_5.clone(), dom0_1.clone(), dom0_1.clone()],
+      Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    // Matching: `x[1]` and `x[2]` take `1` and `2`, hence `x[0] = 0`.
+    inverse_test_one(6, vec![dom0_2.clone(), dom1_2.clone(), dom1_2.clone(), dom0_2.clone(), dom0_2.clone(), dom0_2.clone()],
+      Unknown, Unknown, vec![(0, Assignment), (3, Assignment), (4, Bound), (5, Bound)], true);
+    // `y[1] = 0` but `1` is not in `x[0]`.
+    inverse_test_one(7, vec![zero_two.clone(), dom0_2.clone(), zero_two.clone(), dom0_2.clone(), zero.clone(), dom0_2.clone()],
+      False, False, vec![], false);
+  }
+}
@@ -0,0 +1,376 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The knapsack constraint `knapsack(weights, profits, x, capacity, profit)` ensures that `capacity = sum(weights[i] * x[i])` and `profit = sum(profits[i] * x[i])`.
+//! The variables `x` are the number of copies of each item, they are often `0/1` but they can take any integer value.
+//!
+//! It is the dynamic programming approach of `A Dynamic Programming Approach for Consistency and Propagation for Knapsack Constraints`, Trick, 2003.
+//! The layer `i` of the graph contains the weights reachable with the first `i` items, every value of `x[i]` is an arc from a weight of the layer `i` to one of the layer `i+1`.
+//! The weights that cannot reach the bounds of `capacity` with the remaining items are discarded, so the graph is a MDD over the capacities.
+//! Every node keeps the minimal and maximal profits of the paths going through it, computed in a forward and a backward pass.
+//! A value of `x[i]` is kept if one of its arcs is on a path ending in `capacity` with a profit that can be in the bounds of `profit`.
+//!
+//! With respect to `capacity` alone, it is domain consistent on `x` and `capacity`.
+//! The profit is only filtered by its bounds: the minimal and maximal profits of a path are checked separately, which is a relaxation.
+//! A multi-dimensional knapsack is modelled with one `Knapsack` per dimension sharing the variables `x`.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use propagators::{domain_values, restrict_to_values, LinearNeq};
+use std::collections::BTreeMap;
+use std::cmp::{min, max};
+
+/// The minimal and maximal profits of the paths, indexed by the weights of the nodes of a layer.
+type Layer<Bound> = BTreeMap<Bound, (Bound, Bound)>;
+
+#[derive(Debug)]
+pub struct Knapsack<VStore, Bound>
+{
+  weights: Vec<Bound>,
+  profits: Vec<Bound>,
+  vars: Vec<Var<VStore>>,
+  capacity: Var<VStore>,
+  profit: Var<VStore>
+}
+
+impl<VStore, Bound> Knapsack<VStore, Bound>
+{
+  /// Precondition: there is one weight and one profit per variable.
+  pub fn new(weights: Vec<Bound>, profits: Vec<Bound>, vars: Vec<Var<VStore>>,
+    capacity: Var<VStore>, profit: Var<VStore>) -> Self
+  {
+    assert_eq!(weights.len(), vars.len(), "Every item of `Knapsack` must have a weight.");
+    assert_eq!(profits.len(), vars.len(), "Every item of `Knapsack` must have a profit.");
+    Knapsack {
+      weights: weights,
+      profits: profits,
+      vars: vars,
+      capacity: capacity,
+      profit: profit
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for Knapsack<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    Knapsack {
+      weights: self.weights.clone(),
+      profits: self.profits.clone(),
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      capacity: self.capacity.bclone(),
+      profit: self.profit.bclone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for Knapsack<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    print!("knapsack({:?}, {:?}, ", self.weights, self.profits);
+    if !self.vars.is_empty() {
+      model.display_global("x", &self.vars);
+    }
+    print!(", ");
+    self.capacity.display(model);
+    print!(", ");
+    self.profit.display(model);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Knapsack<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// `capacity != sum(weights[i] * x[i]) \/ profit != sum(profits[i] * x[i])`.
+  fn not(&self) -> Formula<VStore> {
+    let differs = |coefs: &[Bound], total: &Var<VStore>| {
+      let mut coefs = coefs.to_vec();
+      coefs.push(-Bound::one());
+      let mut vars: Vec<_> = self.vars.iter().map(|v| v.bclone()).collect();
+      vars.push(total.bclone());
+      Box::new(LinearNeq::new(coefs, vars, Bound::zero())) as Formula<VStore>
+    };
+    Box::new(Disjunction::new(vec![
+      differs(&self.weights, &self.capacity),
+      differs(&self.profits, &self.profit)]))
+  }
+}
+
+/// The bounds of `c * x` where `x` is in `[lb..ub]`.
+fn product_bounds<Bound: IntBound>(c: &Bound, lb: Bound, ub: Bound) -> (Bound, Bound) {
+  let (l, u) = (c.clone() * lb, c.clone() * ub);
+  if l <= u { (l, u) } else { (u, l) }
+}
+
+/// Widens the profits `(lb, ub)` of the node `w` of `layer`.
+fn merge<Bound: IntBound>(layer: &mut Layer<Bound>, w: Bound, lb: Bound, ub: Bound) {
+  let profits = layer.entry(w).or_insert((lb.clone(), ub.clone()));
+  profits.0 = min(profits.0.clone(), lb);
+  profits.1 = max(profits.1.clone(), ub);
+}
+
+impl<VStore, Domain, Bound> Knapsack<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The bounds of the weighted sums of the variables with `coefs`.
+  fn sum_bounds(&self, store: &VStore, coefs: &[Bound]) -> (Bound, Bound) {
+    self.vars.iter().zip(coefs.iter())
+      .map(|(x, c)| { let x = x.read(store); product_bounds(c, x.lower(), x.upper()) })
+      .fold((Bound::zero(), Bound::zero()), |(l, u), (cl, cu)| (l + cl, u + cu))
+  }
+
+  /// The bounds of the weights of the items `i..n`, for every `i` in `0..n+1`.
+  fn remaining_weights(&self, store: &VStore) -> Vec<(Bound, Bound)> {
+    let mut remaining = vec![(Bound::zero(), Bound::zero())];
+    for (x, w) in self.vars.iter().zip(self.weights.iter()).rev() {
+      let x = x.read(store);
+      let (l, u) = product_bounds(w, x.lower(), x.upper());
+      let (rl, ru) = remaining.last().unwrap().clone();
+      remaining.push((rl + l, ru + u));
+    }
+    remaining.reverse();
+    remaining
+  }
+
+  /// The layers of the weights reachable from `0` with the items `0..i`, which can still be completed into a weight in the bounds of `capacity`.
+  fn forward(&self, store: &VStore, values: &[Vec<Bound>]) -> Vec<Layer<Bound>> {
+    let capacity = self.capacity.read(store);
+    let remaining = self.remaining_weights(store);
+    let mut first = Layer::new();
+    first.insert(Bound::zero(), (Bound::zero(), Bound::zero()));
+    let mut layers = vec![first];
+    for i in 0..self.vars.len() {
+      let (rl, ru) = remaining[i+1].clone();
+      let mut next = Layer::new();
+      for (w, (lb, ub)) in &layers[i] {
+        for v in &values[i] {
+          let next_w = w.clone() + self.weights[i].clone() * v.clone();
+          if next_w.clone() + rl.clone() <= capacity.upper()
+           && next_w.clone() + ru.clone() >= capacity.lower()
+          {
+            let p = self.profits[i].clone() * v.clone();
+            merge(&mut next, next_w, lb.clone() + p.clone(), ub.clone() + p);
+          }
+        }
+      }
+      layers.push(next);
+    }
+    layers
+  }
+
+  /// `true` if a path with the profits `(lb, ub)` can have a profit in the bounds of `profit`.
+  fn profitable(profit: &Domain, lb: &Bound, ub: &Bound) -> bool {
+    *ub >= profit.lower() && *lb <= profit.upper()
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Knapsack<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: the bounds of one of the sums are disjoint from the domain of `capacity` or `profit`.
+    // True: the variables are assigned and the sums are equal to `capacity` and `profit`.
+    // Unknown: Everything else.
+    let capacity = self.capacity.read(store);
+    let profit = self.profit.read(store);
+    let (wl, wu) = self.sum_bounds(store, &self.weights);
+    let (pl, pu) = self.sum_bounds(store, &self.profits);
+    if capacity.is_empty() || profit.is_empty()
+     || wu < capacity.lower() || wl > capacity.upper()
+     || pu < profit.lower() || pl > profit.upper()
+    {
+      False
+    }
+    else if wl == wu && pl == pu && capacity.is_singleton() && profit.is_singleton() {
+      if capacity.lower() == wl && profit.lower() == pl { True } else { False }
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Knapsack<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let n = self.vars.len();
+    let values: Vec<Vec<Bound>> = self.vars.iter()
+      .map(|x| domain_values(&x.read(store)))
+      .collect();
+    let forward = self.forward(store, &values);
+    let capacity = self.capacity.read(store);
+    let profit = self.profit.read(store);
+    // The backward pass starts from the weights in `capacity` with a possible profit, their profit to the end is `0`.
+    let mut last = Layer::new();
+    let mut total = None;
+    for (w, (lb, ub)) in &forward[n] {
+      if capacity.contains(w) && Self::profitable(&profit, lb, ub) {
+        last.insert(w.clone(), (Bound::zero(), Bound::zero()));
+        total = Some(match total {
+          None => (lb.clone(), ub.clone()),
+          Some((tl, tu)) => (min(tl, lb.clone()), max(tu, ub.clone()))
+        });
+      }
+    }
+    let (total_lb, total_ub) = match total {
+      None => return false,
+      Some(total) => total
+    };
+    let weights: Vec<Bound> = last.keys().cloned().collect();
+    let mut supported = vec![vec![]; n];
+    for i in (0..n).rev() {
+      let mut layer = Layer::new();
+      for (w, (f_lb, f_ub)) in &forward[i] {
+        for v in &values[i] {
+          let next_w = w.clone() + self.weights[i].clone() * v.clone();
+          if let Some((b_lb, b_ub)) = last.get(&next_w) {
+            let p = self.profits[i].clone() * v.clone();
+            let (lb, ub) = (p.clone() + b_lb.clone(), p + b_ub.clone());
+            if Self::profitable(&profit, &(f_lb.clone() + lb.clone()), &(f_ub.clone() + ub.clone())) {
+              supported[i].push(v.clone());
+            }
+            merge(&mut layer, w.clone(), lb, ub);
+          }
+        }
+      }
+      last = layer;
+    }
+    for (x, mut values) in self.vars.iter_mut().zip(supported) {
+      values.sort();
+      values.dedup();
+      let dom = restrict_to_values(x.read(store), &values);
+      if !x.update(store, dom) {
+        return false;
+      }
+    }
+    let capacity = restrict_to_values(capacity, &weights);
+    let profit = profit.shrink_left(total_lb).shrink_right(total_ub);
+    self.capacity.update(store, capacity) && self.profit.update(store, profit)
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for Knapsack<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.extend(self.capacity.dependencies(FDEvent::Inner));
+    deps.extend(self.profit.dependencies(FDEvent::Bound));
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+  use variable::VStoreSet;
+  use gcollections::ops::*;
+
+  /// The capacity and the profit are the two last variables.
+  fn knapsack_test_one(test_num: u32, weights: Vec<i32>, profits: Vec<i32>, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num, |mut vars: Vec<SetFDVar>| {
+        let profit = vars.pop().unwrap();
+        let capacity = vars.pop().unwrap();
+        Knapsack::new(weights, profits, vars, capacity, profit)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn knapsack_test() {
+    let dom0_1 = (0,1).to_interval_set();
+    let dom0_2 = (0,2).to_interval_set();
+    let dom0_4 = (0,4).to_interval_set();
+    let dom0_5 = (0,5).to_interval_set();
+    let dom0_10 = (0,10).to_interval_set();
+    let dom1_3 = (1,3).to_interval_set();
+    let dom1_2 = (1,2).to_interval_set();
+    let dom4_10 = (4,10).to_interval_set();
+    let dom5_10 = (5,10).to_interval_set();
+    let zero = (0,0).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let three = (3,3).to_interval_set();
+    let four = (4,4).to_interval_set();
+
+    knapsack_test_one(1, vec![2, 3], vec![3, 4], vec![dom0_1.clone(), dom0_1.clone(), dom0_10.clone(), dom0_10.clone()],
+      Unknown, Unknown, vec![(2, Bound), (3, Bound)], true);
+    // The capacity excludes the two items together and the profit requires the second one.
+    knapsack_test_one(2, vec![2, 3], vec![3, 4], vec![dom0_1.clone(), dom0_1.clone(), dom0_4.clone(), dom4_10.clone()],
+      Unknown, True, vec![(0, Assignment), (1, Assignment), (2, Assignment), (3, Assignment)], true);
+    // No subset of the weights has a sum in `{1, 3}`, which is not detected on the bounds of the sum.
+    knapsack_test_one(3, vec![2, 2], vec![1, 3], vec![dom0_1.clone(), dom0_1.clone(), dom1_3.clone(), dom0_5.clone()],
+      Unknown, Unknown, vec![(2, Assignment), (3, Bound)], true);
+    knapsack_test_one(4, vec![3, 5], vec![1, 1], vec![dom0_1.clone(), dom0_1.clone(), dom1_2.clone(), dom0_10.clone()],
+      Unknown, Unknown, vec![], false);
+    // Both items fit but the best profit is `4`.
+    knapsack_test_one(5, vec![2, 3], vec![3, 1], vec![dom0_1.clone(), dom0_1.clone(), dom0_10.clone(), dom5_10.clone()],
+      False, False, vec![], false);
+    knapsack_test_one(6, vec![2, 3], vec![3, 4], vec![one.clone(), zero.clone(), (2,2).to_interval_set(), three.clone()],
+      True, True, vec![], true);
+    knapsack_test_one(7, vec![2, 3], vec![3, 4], vec![one.clone(), zero.clone(), (2,2).to_interval_set(), four.clone()],
+      False, False, vec![], false);
+    // Bounded variables: the capacity `4` is only reached by `x = [2, 0]` or `x = [0, 1]`, and the profit rules out the latter.
+    knapsack_test_one(8, vec![2, 4], vec![3, 5], vec![dom0_2.clone(), dom0_1.clone(), four.clone(), (6,10).to_interval_set()],
+      Unknown, True, vec![(0, Assignment), (1, Assignment), (3, Assignment)], true);
+  }
+
+  #[test]
+  fn multi_dimensional_test() {
+    let mut vstore = VStoreSet::empty();
+    let mut alloc = |d: IntervalSet<i32>| Box::new(vstore.alloc(d)) as Var<VStoreSet>;
+    let x: Vec<_> = (0..3).map(|_| alloc((0,1).to_interval_set())).collect();
+    let w1 = alloc((0,3).to_interval_set());
+    let w2 = alloc((0,4).to_interval_set());
+    let p1 = alloc((5,7).to_interval_set());
+    let p2 = alloc((0,0).to_interval_set());
+    let mut k1 = Knapsack::new(vec![1, 1, 1], vec![1, 1, 5], x.iter().map(|v| v.bclone()).collect(), w1, p1.bclone());
+    let mut k2 = Knapsack::new(vec![2, 2, 3], vec![0, 0, 0], x.iter().map(|v| v.bclone()).collect(), w2, p2);
+    // The profit of the first dimension requires the last item, which leaves no room for the others in the second dimension.
+    assert!(k1.propagate(&mut vstore));
+    assert_eq!(x[2].read(&vstore), (1,1).to_interval_set());
+    assert!(k2.propagate(&mut vstore));
+    assert!(k1.propagate(&mut vstore));
+    assert_eq!(p1.read(&vstore), (5,5).to_interval_set());
+    let values: Vec<_> = x.iter().map(|v| v.read(&vstore)).collect();
+    assert_eq!(values, vec![(0,0).to_interval_set(), (0,0).to_interval_set(), (1,1).to_interval_set()]);
+  }
+}
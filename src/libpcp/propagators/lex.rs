@@ -0,0 +1,544 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lexicographic ordering constraints between arrays of variables, mostly used to break the symmetries between the rows of a matrix.
+//!
+//! It is the algorithm described in `Global Constraints for Lexicographic Orderings`, Frisch and al., 2002.
+//! The position `alpha` is the first position where `x[alpha]` and `y[alpha]` are not assigned to the same value.
+//! The position `beta` is the first position from which `x[beta..]` is necessarily greater (or equal for the strict ordering) than `y[beta..]`.
+//! Only `x[alpha]` and `y[alpha]` are pruned: `x[alpha] < y[alpha]` if `beta = alpha + 1` and `x[alpha] <= y[alpha]` otherwise.
+//! The filtering is domain consistent and only the bounds of the domains are used.
+//!
+//! `LexChain` is the algorithm described in `Arc-Consistency for a Chain of Lexicographic Ordering Constraints`, Carlsson and Beldiceanu, 2002.
+//! A forward pass computes the smallest feasible tuple of every row from the one of the previous row, and a backward pass computes the greatest feasible tuple from the one of the next row.
+//! The chain is then filtered as `lb[i] <=lex rows[i] <=lex ub[i]` on each row independently, in `O(nm)` for `m` rows of length `n`.
+//! As for `LexLess`, only the bounds of the domains are used.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+
+/// `x <lex y`, or `x <=lex y` if it is built with `lex_leq`.
+#[derive(Debug)]
+pub struct LexLess<VStore>
+{
+  x: Vec<Var<VStore>>,
+  y: Vec<Var<VStore>>,
+  strict: bool
+}
+
+pub type LexLeq<VStore> = LexLess<VStore>;
+
+impl<VStore> LexLess<VStore>
+{
+  /// Precondition: `x.len() == y.len()`.
+  pub fn new(x: Vec<Var<VStore>>, y: Vec<Var<VStore>>) -> Self {
+    LexLess::make(x, y, true)
+  }
+
+  fn make(x: Vec<Var<VStore>>, y: Vec<Var<VStore>>, strict: bool) -> Self {
+    assert_eq!(x.len(), y.len(), "The arrays of a lexicographic ordering must have the same length.");
+    LexLess {
+      x: x,
+      y: y,
+      strict: strict
+    }
+  }
+}
+
+/// Precondition: `x.len() == y.len()`.
+pub fn lex_leq<VStore>(x: Vec<Var<VStore>>, y: Vec<Var<VStore>>) -> LexLeq<VStore> {
+  LexLess::make(x, y, false)
+}
+
+impl<VStore> Clone for LexLess<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    LexLess {
+      x: self.x.iter().map(|v| v.bclone()).collect(),
+      y: self.y.iter().map(|v| v.bclone()).collect(),
+      strict: self.strict
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for LexLess<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("{}(", if self.strict { "lex_less" } else { "lex_leq" });
+    model.display_global("x", &self.x);
+    print!(", ");
+    model.display_global("y", &self.y);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for LexLess<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// `y <=lex x` if `x <lex y`, and `y <lex x` if `x <=lex y`.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(LexLess::make(
+      self.y.iter().map(|v| v.bclone()).collect(),
+      self.x.iter().map(|v| v.bclone()).collect(),
+      !self.strict))
+  }
+}
+
+impl<VStore, Domain, Bound> LexLess<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn ground_equal(&self, store: &VStore, i: usize) -> bool {
+    let x = self.x[i].read(store);
+    let y = self.y[i].read(store);
+    x.is_singleton() && y.is_singleton() && x.lower() == y.lower()
+  }
+
+  fn alpha_from(&self, store: &VStore, mut alpha: usize) -> usize {
+    while alpha < self.x.len() && self.ground_equal(store, alpha) {
+      alpha += 1;
+    }
+    alpha
+  }
+
+  /// Returns `n + 1` if there is no such position.
+  fn beta(&self, store: &VStore, alpha: usize) -> usize {
+    let n = self.x.len();
+    let mut i = alpha;
+    // The first position of the last run where `x[i] >= y[i]` is entailed.
+    let mut run = None;
+    while i < n {
+      let min_x = self.x[i].read(store).lower();
+      let max_y = self.y[i].read(store).upper();
+      if min_x > max_y {
+        break;
+      }
+      if min_x < max_y {
+        run = None;
+      }
+      else if run.is_none() {
+        run = Some(i);
+      }
+      i += 1;
+    }
+    if i < n { run.unwrap_or(i) }
+    else if self.strict { run.unwrap_or(n) }
+    else { n + 1 }
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for LexLess<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: `beta <= alpha`, or `x[alpha] >= y[alpha]` when `beta = alpha + 1`.
+    // True: `x[alpha] < y[alpha]` is entailed, or the arrays are assigned and equal for `<=lex`.
+    // Unknown: Everything else.
+    let n = self.x.len();
+    let alpha = self.alpha_from(store, 0);
+    if alpha == n {
+      return if self.strict { False } else { True };
+    }
+    let x = self.x[alpha].read(store);
+    let y = self.y[alpha].read(store);
+    let beta = self.beta(store, alpha);
+    if beta <= alpha || (beta == alpha + 1 && x.lower() >= y.upper()) {
+      False
+    }
+    else if x.upper() < y.lower() {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for LexLess<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let n = self.x.len();
+    let mut alpha = 0;
+    loop {
+      alpha = self.alpha_from(store, alpha);
+      if alpha == n {
+        return !self.strict;
+      }
+      let beta = self.beta(store, alpha);
+      if beta <= alpha {
+        return false;
+      }
+      let x = self.x[alpha].read(store);
+      let y = self.y[alpha].read(store);
+      let consistent =
+        if beta == alpha + 1 {
+          self.x[alpha].update(store, x.strict_shrink_right(y.upper())) &&
+          self.y[alpha].update(store, y.strict_shrink_left(x.lower()))
+        }
+        else {
+          self.x[alpha].update(store, x.shrink_right(y.upper())) &&
+          self.y[alpha].update(store, y.shrink_left(x.lower()))
+        };
+      if !consistent {
+        return false;
+      }
+      if !self.ground_equal(store, alpha) {
+        return true;
+      }
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for LexLess<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.x.iter().chain(self.y.iter())
+      .flat_map(|v| v.dependencies(FDEvent::Bound))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+/// `rows[0] <=lex rows[1] <=lex ... <=lex rows[m-1]`, or with `<lex` if it is built with `LexChain::strict`.
+#[derive(Debug)]
+pub struct LexChain<VStore>
+{
+  rows: Vec<Vec<Var<VStore>>>,
+  strict: bool
+}
+
+impl<VStore> LexChain<VStore>
+{
+  /// Precondition: the rows have the same length.
+  pub fn new(rows: Vec<Vec<Var<VStore>>>) -> Self {
+    LexChain::make(rows, false)
+  }
+
+  /// Precondition: the rows have the same length.
+  pub fn strict(rows: Vec<Vec<Var<VStore>>>) -> Self {
+    LexChain::make(rows, true)
+  }
+
+  fn make(rows: Vec<Vec<Var<VStore>>>, strict: bool) -> Self {
+    assert!(rows.windows(2).all(|w| w[0].len() == w[1].len()),
+      "The rows of a lexicographic chain must have the same length.");
+    LexChain {
+      rows: rows,
+      strict: strict
+    }
+  }
+}
+
+impl<VStore> Clone for LexChain<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    LexChain {
+      rows: self.rows.iter()
+        .map(|row| row.iter().map(|v| v.bclone()).collect())
+        .collect(),
+      strict: self.strict
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for LexChain<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("{}(", if self.strict { "lex_chain_less" } else { "lex_chain" });
+    for (i, row) in self.rows.iter().enumerate() {
+      if i > 0 {
+        print!(", ");
+      }
+      model.display_global("row", row);
+    }
+    print!(")");
+  }
+}
+
+impl<VStore> LexChain<VStore> where
+  VStore: Collection
+{
+  /// The ordering between every two consecutive rows.
+  fn pairs(&self) -> Vec<LexLess<VStore>> {
+    self.rows.windows(2)
+      .map(|w| LexLess::make(
+        w[0].iter().map(|v| v.bclone()).collect(),
+        w[1].iter().map(|v| v.bclone()).collect(),
+        self.strict))
+      .collect()
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for LexChain<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// Two consecutive rows are not ordered.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Disjunction::new(self.pairs().iter().map(|p| p.not()).collect()))
+  }
+}
+
+impl<VStore, Domain, Bound> LexChain<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn row_bounds(&self, store: &VStore, i: usize) -> Vec<(Bound, Bound)> {
+    self.rows[i].iter()
+      .map(|v| { let d = v.read(store); (d.lower(), d.upper()) })
+      .collect()
+  }
+
+  /// The smallest tuple of `row` greater than `prev` (or equal if the ordering is not strict).
+  /// It is `prev[..j]` followed by the smallest value of `row[j]` greater than `prev[j]` and by the lower bounds of `row[j+1..]`, with `j` as large as possible.
+  fn feasible_lower(&self, row: &[(Bound, Bound)], prev: &[Bound]) -> Option<Vec<Bound>> {
+    let n = row.len();
+    let prefix = row.iter().zip(prev.iter())
+      .take_while(|&((l, u), t)| l <= t && t <= u)
+      .count();
+    if prefix == n && !self.strict {
+      return Some(prev.to_vec());
+    }
+    (0..::std::cmp::min(prefix + 1, n)).rev()
+      .find(|&j| row[j].1 > prev[j])
+      .map(|j| {
+        let mut t = prev[..j].to_vec();
+        t.push(::std::cmp::max(prev[j].clone() + Bound::one(), row[j].0.clone()));
+        t.extend(row[j+1..].iter().map(|b| b.0.clone()));
+        t
+      })
+  }
+
+  /// The greatest tuple of `row` smaller than `next` (or equal if the ordering is not strict), symmetric to `feasible_lower`.
+  fn feasible_upper(&self, row: &[(Bound, Bound)], next: &[Bound]) -> Option<Vec<Bound>> {
+    let n = row.len();
+    let prefix = row.iter().zip(next.iter())
+      .take_while(|&((l, u), t)| l <= t && t <= u)
+      .count();
+    if prefix == n && !self.strict {
+      return Some(next.to_vec());
+    }
+    (0..::std::cmp::min(prefix + 1, n)).rev()
+      .find(|&j| row[j].0 < next[j])
+      .map(|j| {
+        let mut t = next[..j].to_vec();
+        t.push(::std::cmp::min(next[j].clone() - Bound::one(), row[j].1.clone()));
+        t.extend(row[j+1..].iter().map(|b| b.1.clone()));
+        t
+      })
+  }
+
+  /// The feasible lower and upper bounds of every row, `None` if the chain cannot be satisfied.
+  fn feasible_bounds(&self, store: &VStore) -> Option<Vec<(Vec<Bound>, Vec<Bound>)>> {
+    let m = self.rows.len();
+    let rows: Vec<_> = (0..m).map(|i| self.row_bounds(store, i)).collect();
+    let mut lower: Vec<Vec<Bound>> = Vec::with_capacity(m);
+    for (i, row) in rows.iter().enumerate() {
+      let t =
+        if i == 0 { row.iter().map(|b| b.0.clone()).collect() }
+        else { self.feasible_lower(row, &lower[i-1])? };
+      lower.push(t);
+    }
+    let mut upper: Vec<Vec<Bound>> = Vec::with_capacity(m);
+    for (i, row) in rows.iter().enumerate().rev() {
+      let t =
+        if i == m - 1 { row.iter().map(|b| b.1.clone()).collect() }
+        else { self.feasible_upper(row, &upper[m-2-i])? };
+      upper.push(t);
+    }
+    upper.reverse();
+    if lower.iter().zip(upper.iter()).any(|(l, u)| l > u) {
+      return None;
+    }
+    Some(lower.into_iter().zip(upper).collect())
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for LexChain<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: A row has no feasible tuple.
+    // True: Every two consecutive rows are ordered.
+    // Unknown: Everything else.
+    if self.feasible_bounds(store).is_none() {
+      False
+    }
+    else if self.pairs().iter().all(|p| p.is_subsumed(store) == True) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for LexChain<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// Every row is between its feasible lower bound `lb` and upper bound `ub`.
+  /// With `alpha` the first position where `lb[alpha] != ub[alpha]`, the positions before `alpha` are assigned to `lb` and `row[alpha]` is in `[lb[alpha]..ub[alpha]]`.
+  /// The positions after `alpha` are supported by either `lb` or `ub` and are not pruned.
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let bounds = match self.feasible_bounds(store) {
+      Some(bounds) => bounds,
+      None => return false
+    };
+    for (row, (lb, ub)) in self.rows.iter_mut().zip(bounds) {
+      for (j, x) in row.iter_mut().enumerate() {
+        let d = x.read(store);
+        if !x.update(store, d.shrink_left(lb[j].clone()).shrink_right(ub[j].clone())) {
+          return false;
+        }
+        if lb[j] != ub[j] {
+          break;
+        }
+      }
+    }
+    true
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for LexChain<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.rows.iter()
+      .flat_map(|row| row.iter())
+      .flat_map(|v| v.dependencies(FDEvent::Bound))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  /// The first half of the variables is `x` and the second half is `y`.
+  fn lex_test_one(test_num: u32, strict: bool, doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_propagator_test(test_num, |mut x| {
+        let y = x.split_off(x.len() / 2);
+        if strict { LexLess::new(x, y) } else { lex_leq(x, y) }
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  /// The variables are split into rows of length `len`.
+  fn lex_chain_test_one(test_num: u32, strict: bool, len: usize, doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_propagator_test(test_num, |vars| {
+        let mut rows = vec![];
+        let mut vars = vars.into_iter();
+        loop {
+          let row: Vec<_> = vars.by_ref().take(len).collect();
+          if row.is_empty() { break; }
+          rows.push(row);
+        }
+        if strict { LexChain::strict(rows) } else { LexChain::new(rows) }
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn lex_leq_test() {
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let dom0_1 = (0,1).to_interval();
+    let dom0_2 = (0,2).to_interval();
+    let dom1_2 = (1,2).to_interval();
+
+    lex_test_one(1, false, vec![one, two, one, two], True, True, vec![], true);
+    lex_test_one(2, false, vec![one, two, one, one], False, False, vec![], false);
+    lex_test_one(3, false, vec![dom0_2, dom0_2, dom0_2, dom0_2], Unknown, Unknown, vec![], true);
+    // `x[0] <= y[0]`.
+    lex_test_one(4, false, vec![dom1_2, dom0_2, dom0_1, dom0_2], Unknown, Unknown, vec![(0, Assignment), (2, Assignment)], true);
+    // `x[1] > y[1]` hence `x[0] < y[0]`.
+    lex_test_one(5, false, vec![dom0_1, two, dom0_1, zero], Unknown, True, vec![(0, Assignment), (2, Assignment)], true);
+    // `x[0] = y[0]` and then `x[1] <= y[1]`.
+    lex_test_one(6, false, vec![one, dom0_2, one, dom0_1], Unknown, Unknown, vec![(1, Bound)], true);
+    lex_test_one(7, false, vec![one, one, one, one], True, True, vec![], true);
+    lex_test_one(8, false, vec![zero, two, one, zero], True, True, vec![], true);
+  }
+
+  #[test]
+  fn lex_less_test() {
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let dom0_1 = (0,1).to_interval();
+    let dom0_2 = (0,2).to_interval();
+
+    lex_test_one(1, true, vec![one, one, one, one], False, False, vec![], false);
+    lex_test_one(2, true, vec![one, one, one, two], True, True, vec![], true);
+    // The last position must be strictly ordered.
+    lex_test_one(3, true, vec![one, dom0_2, one, dom0_2], Unknown, Unknown, vec![(1, Bound), (3, Bound)], true);
+    // `x[1] >= y[1]` hence `x[0] < y[0]`.
+    lex_test_one(4, true, vec![dom0_1, one, dom0_1, one], Unknown, True, vec![(0, Assignment), (2, Assignment)], true);
+    lex_test_one(5, true, vec![dom0_1, two, zero, one], False, False, vec![], false);
+  }
+
+  #[test]
+  fn lex_chain_test() {
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let dom0_1 = (0,1).to_interval();
+
+    lex_chain_test_one(1, false, 2, vec![zero, one, one, zero, one, one], True, True, vec![], true);
+    lex_chain_test_one(2, false, 2, vec![zero, one, one, one, one, zero], False, False, vec![], false);
+    // The first and the last rows bound the middle row.
+    lex_chain_test_one(3, false, 2, vec![one, zero, dom0_1, dom0_1, zero, one], False, False, vec![], false);
+    lex_chain_test_one(4, false, 2, vec![one, zero, dom0_1, dom0_1, one, one], Unknown, Unknown, vec![(2, Assignment)], true);
+    lex_chain_test_one(5, false, 1, vec![dom0_1, dom0_1, zero], Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+    // `[0,0] < [0,1] < [1,0] < [1,1]`: the first row starts with `0` and the last one with `1`, which is not deduced from the consecutive pairs.
+    lex_chain_test_one(6, true, 2, vec![dom0_1, dom0_1, dom0_1, dom0_1, dom0_1, dom0_1], Unknown, Unknown, vec![(0, Assignment), (4, Assignment)], true);
+    // There are only three tuples from `[0,1]` for four rows.
+    lex_chain_test_one(7, true, 2, vec![zero, one, dom0_1, dom0_1, dom0_1, dom0_1, dom0_1, dom0_1], False, False, vec![], false);
+    // `rows[1] = [1,0]` is the only tuple between `[0,1]` and `[1,1]`.
+    lex_chain_test_one(8, true, 2, vec![dom0_1, one, dom0_1, dom0_1, one, one], Unknown, True, vec![(0, Assignment), (2, Assignment), (3, Assignment)], true);
+  }
+}
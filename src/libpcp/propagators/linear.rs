@@ -0,0 +1,665 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Linear constraints `a[0]*x[0] + ... + a[n-1]*x[n-1] op c` where the coefficients `a` and `c` are integer constants.
+//!
+//! The propagators maintain the bounds of the sum, the contribution of a variable being removed from these bounds to filter this variable.
+//! The bounds of the sum and of each term are kept between two propagations, and saved and restored on backtracking with the state of the propagator.
+//! Since the propagators are not told which variables changed, the bounds of every term are read again, but only the terms that changed update the bounds of the sum.
+//! A term is only filtered when it is wider than the slack of the sum, the other terms cannot be narrowed.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+
+pub type LinearGeq<VStore, Bound> = LinearLeq<VStore, Bound>;
+
+/// `a[0]*x[0] + ... + a[n-1]*x[n-1] >= c` is rewritten into `-a[0]*x[0] + ... + -a[n-1]*x[n-1] <= -c`.
+pub fn linear_geq<VStore, Bound>(coefs: Vec<Bound>, vars: Vec<Var<VStore>>, c: Bound) -> LinearGeq<VStore, Bound> where
+  Bound: IntBound
+{
+  LinearLeq::new(coefs.into_iter().map(|a| -a).collect(), vars, -c)
+}
+
+/// Part of the propagator restored on backtracking: the bounds of the sum and of each term when they were last computed.
+#[derive(Clone, Debug)]
+struct LinearState<Bound>
+{
+  sum: (Bound, Bound),
+  terms: Vec<(Bound, Bound)>
+}
+
+/// Weighted sum shared by the linear propagators, the terms with a zero coefficient are removed.
+#[derive(Debug)]
+struct LinearExpr<VStore, Bound>
+{
+  coefs: Vec<Bound>,
+  vars: Vec<Var<VStore>>,
+  last: Option<LinearState<Bound>>
+}
+
+impl<VStore, Bound> LinearExpr<VStore, Bound> where
+  Bound: IntBound
+{
+  fn new(coefs: Vec<Bound>, vars: Vec<Var<VStore>>) -> Self {
+    assert_eq!(coefs.len(), vars.len(), "Every variable of a linear constraint must have a coefficient.");
+    let (coefs, vars) = coefs.into_iter().zip(vars)
+      .filter(|term| !term.0.is_zero())
+      .unzip();
+    LinearExpr {
+      coefs: coefs,
+      vars: vars,
+      last: None
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for LinearExpr<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    LinearExpr {
+      coefs: self.coefs.clone(),
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      last: self.last.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> LinearExpr<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model, op: &str, c: &Bound) {
+    if self.vars.is_empty() {
+      print!("0");
+    }
+    for (i, (a, x)) in self.coefs.iter().zip(self.vars.iter()).enumerate() {
+      if i > 0 {
+        print!(" + ");
+      }
+      print!("{:?}*", a);
+      x.display(model);
+    }
+    print!(" {} {:?}", op, c);
+  }
+
+  fn dependencies(&self, event: FDEvent) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(event))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+impl<VStore, Bound> LinearExpr<VStore, Bound> where
+  Bound: IntBound + 'static
+{
+  fn save_state(&self) -> Option<PropagatorState> {
+    self.last.clone().map(|state| Box::new(state) as PropagatorState)
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    if let Ok(state) = state.downcast::<LinearState<Bound>>() {
+      self.last = Some(*state);
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> LinearExpr<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// Bounds of the term `a[i]*x[i]`.
+  fn term_bounds(&self, i: usize, store: &VStore) -> (Bound, Bound) {
+    let x = self.vars[i].read(store);
+    let a = self.coefs[i].clone();
+    if a > Bound::zero() {
+      (a.clone() * x.lower(), a * x.upper())
+    }
+    else {
+      (a.clone() * x.upper(), a * x.lower())
+    }
+  }
+
+  /// Bounds of the sum.
+  fn bounds(&self, store: &VStore) -> (Bound, Bound) {
+    (0..self.vars.len())
+      .map(|i| self.term_bounds(i, store))
+      .fold((Bound::zero(), Bound::zero()), |(lb, ub), (l, u)| (lb + l, ub + u))
+  }
+
+  /// The state updated with the current domains, only the terms whose bounds changed since the last propagation update the bounds of the sum.
+  fn updated_state(&mut self, store: &VStore) -> LinearState<Bound> {
+    match self.last.take() {
+      None => {
+        let terms: Vec<_> = (0..self.vars.len()).map(|i| self.term_bounds(i, store)).collect();
+        let sum = terms.iter().cloned()
+          .fold((Bound::zero(), Bound::zero()), |(lb, ub), (l, u)| (lb + l, ub + u));
+        LinearState { sum: sum, terms: terms }
+      }
+      Some(mut state) => {
+        for i in 0..self.vars.len() {
+          let term = self.term_bounds(i, store);
+          if term != state.terms[i] {
+            state.update_term(i, term);
+          }
+        }
+        state
+      }
+    }
+  }
+
+  /// Restricts the term `a[i]*x[i]` to `[lb..ub]`.
+  /// Returns the new bounds of the term, or `None` if the domain of `x[i]` becomes empty.
+  fn restrict_term(&mut self, i: usize, store: &mut VStore, lb: Bound, ub: Bound) -> Option<(Bound, Bound)> {
+    let a = self.coefs[i].clone();
+    let (x_lb, x_ub) =
+      if a > Bound::zero() { (ceil_div(lb, a.clone()), floor_div(ub, a)) }
+      else { (ceil_div(ub, a.clone()), floor_div(lb, a)) };
+    let x = self.vars[i].read(store);
+    if !self.vars[i].update(store, x.shrink_left(x_lb).shrink_right(x_ub)) {
+      return None;
+    }
+    Some(self.term_bounds(i, store))
+  }
+
+  /// Filters the variables such that the sum is in `[lb..ub]`, until a fixed point is reached.
+  /// Without lower bound, the narrowed upper bounds of the terms cannot narrow the other terms, so one pass is enough.
+  fn propagate_bounds(&mut self, store: &mut VStore, lb: Option<Bound>, ub: Bound) -> bool {
+    let mut state = self.updated_state(store);
+    let success = self.filter(store, &mut state, lb, ub);
+    self.last = Some(state);
+    success
+  }
+
+  fn filter(&mut self, store: &mut VStore, state: &mut LinearState<Bound>, lb: Option<Bound>, ub: Bound) -> bool {
+    let (sum_lb, sum_ub) = state.sum.clone();
+    let below = match lb {
+      Some(ref lb) => sum_ub < *lb,
+      None => false
+    };
+    if sum_lb > ub || below {
+      return false;
+    }
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for i in 0..self.vars.len() {
+        let (sum_lb, sum_ub) = state.sum.clone();
+        let (term_lb, term_ub) = state.terms[i].clone();
+        // The other terms are in `[sum_lb - term_lb..sum_ub - term_ub]`, the term is not narrowed if its width is at most the slack of the sum.
+        let width = term_ub.clone() - term_lb.clone();
+        let narrow_ub = ub.clone() - sum_lb.clone() < width;
+        let narrow_lb = match lb {
+          Some(ref lb) => sum_ub.clone() - lb.clone() < width,
+          None => false
+        };
+        if !narrow_ub && !narrow_lb {
+          continue;
+        }
+        let new_lb = match lb {
+          Some(ref lb) => lb.clone() - (sum_ub - term_ub.clone()),
+          None => term_lb.clone()
+        };
+        let new_ub = ub.clone() - (sum_lb - term_lb.clone());
+        match self.restrict_term(i, store, new_lb, new_ub) {
+          None => return false,
+          Some(term) => {
+            if term.0 != term_lb || (lb.is_some() && term.1 != term_ub) {
+              changed = true;
+            }
+            state.update_term(i, term);
+          }
+        }
+      }
+    }
+    true
+  }
+}
+
+impl<Bound: IntBound> LinearState<Bound>
+{
+  /// Replaces the bounds of the term `i`, the bounds of the sum are updated with their variation.
+  fn update_term(&mut self, i: usize, term: (Bound, Bound)) {
+    let (old_lb, old_ub) = self.terms[i].clone();
+    self.sum = (self.sum.0.clone() + term.0.clone() - old_lb, self.sum.1.clone() + term.1.clone() - old_ub);
+    self.terms[i] = term;
+  }
+}
+
+fn floor_div<Bound: IntBound>(a: Bound, b: Bound) -> Bound {
+  a.div_floor(&b)
+}
+
+fn ceil_div<Bound: IntBound>(a: Bound, b: Bound) -> Bound {
+  -(-a).div_floor(&b)
+}
+
+/// `a[0]*x[0] + ... + a[n-1]*x[n-1] <= c`
+#[derive(Debug)]
+pub struct LinearLeq<VStore, Bound>
+{
+  expr: LinearExpr<VStore, Bound>,
+  c: Bound
+}
+
+impl<VStore, Bound> LinearLeq<VStore, Bound> where
+  Bound: IntBound
+{
+  pub fn new(coefs: Vec<Bound>, vars: Vec<Var<VStore>>, c: Bound) -> Self {
+    LinearLeq {
+      expr: LinearExpr::new(coefs, vars),
+      c: c
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for LinearLeq<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    LinearLeq {
+      expr: self.expr.clone(),
+      c: self.c.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for LinearLeq<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    self.expr.display(model, "<=", &self.c);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for LinearLeq<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    let e = self.expr.clone();
+    Box::new(linear_geq(e.coefs, e.vars, self.c.clone() + Bound::one()))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for LinearLeq<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: min(sum) > c
+    // True: max(sum) <= c
+    // Unknown: Everything else.
+    let (lb, ub) = self.expr.bounds(store);
+    if lb > self.c {
+      False
+    }
+    else if ub <= self.c {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for LinearLeq<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound + 'static
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let c = self.c.clone();
+    self.expr.propagate_bounds(store, None, c)
+  }
+
+  fn save_state(&self) -> Option<PropagatorState> {
+    self.expr.save_state()
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    self.expr.restore_state(state)
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for LinearLeq<VStore, Bound> where
+  Bound: IntBound
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    self.expr.dependencies(FDEvent::Bound)
+  }
+}
+
+/// `a[0]*x[0] + ... + a[n-1]*x[n-1] = c`
+#[derive(Debug)]
+pub struct LinearEq<VStore, Bound>
+{
+  expr: LinearExpr<VStore, Bound>,
+  c: Bound
+}
+
+impl<VStore, Bound> LinearEq<VStore, Bound> where
+  Bound: IntBound
+{
+  pub fn new(coefs: Vec<Bound>, vars: Vec<Var<VStore>>, c: Bound) -> Self {
+    LinearEq {
+      expr: LinearExpr::new(coefs, vars),
+      c: c
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for LinearEq<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    LinearEq {
+      expr: self.expr.clone(),
+      c: self.c.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for LinearEq<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    self.expr.display(model, "=", &self.c);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for LinearEq<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    let e = self.expr.clone();
+    Box::new(LinearNeq::new(e.coefs, e.vars, self.c.clone()))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for LinearEq<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: c is not in [min(sum)..max(sum)]
+    // True: min(sum) = max(sum) = c
+    // Unknown: Everything else.
+    let (lb, ub) = self.expr.bounds(store);
+    if self.c < lb || self.c > ub {
+      False
+    }
+    else if lb == ub {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for LinearEq<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound + 'static
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let c = self.c.clone();
+    self.expr.propagate_bounds(store, Some(c.clone()), c)
+  }
+
+  fn save_state(&self) -> Option<PropagatorState> {
+    self.expr.save_state()
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    self.expr.restore_state(state)
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for LinearEq<VStore, Bound> where
+  Bound: IntBound
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    self.expr.dependencies(FDEvent::Bound)
+  }
+}
+
+/// `a[0]*x[0] + ... + a[n-1]*x[n-1] != c`
+#[derive(Debug)]
+pub struct LinearNeq<VStore, Bound>
+{
+  expr: LinearExpr<VStore, Bound>,
+  c: Bound
+}
+
+impl<VStore, Bound> LinearNeq<VStore, Bound> where
+  Bound: IntBound
+{
+  pub fn new(coefs: Vec<Bound>, vars: Vec<Var<VStore>>, c: Bound) -> Self {
+    LinearNeq {
+      expr: LinearExpr::new(coefs, vars),
+      c: c
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for LinearNeq<VStore, Bound> where
+  VStore: Collection,
+  Bound: Clone
+{
+  fn clone(&self) -> Self {
+    LinearNeq {
+      expr: self.expr.clone(),
+      c: self.c.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for LinearNeq<VStore, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    self.expr.display(model, "!=", &self.c);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for LinearNeq<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    let e = self.expr.clone();
+    Box::new(LinearEq::new(e.coefs, e.vars, self.c.clone()))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for LinearNeq<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    let e = self.expr.clone();
+    !LinearEq::new(e.coefs, e.vars, self.c.clone()).is_subsumed(store)
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for LinearNeq<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    // When all the variables but one are assigned, the value making the sum equal to `c` is removed from the last one.
+    let mut unassigned = (0..self.expr.vars.len())
+      .filter(|&i| !self.expr.vars[i].read(store).is_singleton());
+    match (unassigned.next(), unassigned.next()) {
+      (None, _) => {
+        let (lb, _) = self.expr.bounds(store);
+        lb != self.c
+      }
+      (Some(i), None) => {
+        let (lb, _) = self.expr.bounds(store);
+        let (term_lb, _) = self.expr.term_bounds(i, store);
+        let rest = self.c.clone() - (lb - term_lb);
+        let a = self.expr.coefs[i].clone();
+        if rest.is_multiple_of(&a) {
+          let x = self.expr.vars[i].read(store);
+          self.expr.vars[i].update(store, x.difference(&(rest / a)))
+        }
+        else {
+          true
+        }
+      }
+      _ => true
+    }
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for LinearNeq<VStore, Bound> where
+  Bound: IntBound
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    self.expr.dependencies(FDEvent::Assignment)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+  use variable::VStoreFD;
+  use interval::interval_set::*;
+  use search::*;
+  use gcollections::ops::*;
+
+  fn linear_test_one<P, F>(test_num: u32, make_prop: F, coefs: Vec<i32>, doms: Vec<Interval<i32>>, c: i32,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool) where
+   P: PropagatorConcept<VStoreFD, FDEvent>,
+   F: FnOnce(Vec<i32>, Vec<FDVar>, i32) -> P
+  {
+    nary_propagator_test(test_num, move |vars| make_prop(coefs, vars, c),
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn linear_leq_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom0_5 = (0,5).to_interval();
+    let dom2_2 = (2,2).to_interval();
+
+    linear_test_one(1, LinearLeq::new, vec![1,1], vec![dom0_10, dom0_10], 10, Unknown, Unknown, vec![], true);
+    linear_test_one(2, LinearLeq::new, vec![2,3], vec![dom0_10, dom0_10], 10, Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    linear_test_one(3, LinearLeq::new, vec![2,-3], vec![dom0_10, dom0_5], 0, Unknown, Unknown, vec![(0, Bound)], true);
+    linear_test_one(4, LinearLeq::new, vec![2,-3], vec![dom0_5, dom0_5], 10, True, True, vec![], true);
+    linear_test_one(5, LinearLeq::new, vec![2,3], vec![dom2_2, dom2_2], 9, False, False, vec![], false);
+    linear_test_one(6, LinearLeq::new, vec![2,3], vec![dom2_2, dom0_10], 7, Unknown, True, vec![(1, Bound)], true);
+    linear_test_one(7, LinearLeq::new, vec![0,1], vec![dom0_10, dom0_10], 5, Unknown, True, vec![(1, Bound)], true);
+    linear_test_one(8, linear_geq, vec![2,3], vec![dom0_5, dom0_5], 24, Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+    // Without terms, the sum is `0`.
+    linear_test_one(9, LinearLeq::new, vec![0], vec![dom0_10], -1, False, False, vec![], false);
+  }
+
+  #[test]
+  fn linear_eq_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom0_3 = (0,3).to_interval();
+    let dom1_1 = (1,1).to_interval();
+
+    linear_test_one(1, LinearEq::new, vec![1,1], vec![dom0_10, dom0_10], 10, Unknown, Unknown, vec![], true);
+    linear_test_one(2, LinearEq::new, vec![1,1], vec![dom0_10, dom0_3], 12, Unknown, Unknown, vec![(0, Bound), (1, Bound)], true);
+    linear_test_one(3, LinearEq::new, vec![2,3], vec![dom0_10, dom0_10], 1, Unknown, Unknown, vec![], false);
+    linear_test_one(4, LinearEq::new, vec![2,-1], vec![dom0_3, dom1_1], 5, Unknown, True, vec![(0, Assignment)], true);
+    linear_test_one(5, LinearEq::new, vec![1,1], vec![dom0_3, dom0_3], 7, False, False, vec![], false);
+    // The fixed point is reached inside the propagator.
+    linear_test_one(6, LinearEq::new, vec![3,5], vec![dom0_3, dom0_3], 19, Unknown, True, vec![(0, Assignment), (1, Assignment)], true);
+  }
+
+  #[test]
+  fn linear_neq_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom1_1 = (1,1).to_interval();
+    let dom2_2 = (2,2).to_interval();
+
+    linear_test_one(1, LinearNeq::new, vec![1,1], vec![dom0_10, dom0_10], 10, Unknown, Unknown, vec![], true);
+    linear_test_one(2, LinearNeq::new, vec![1,1], vec![dom1_1, dom0_10], 11, Unknown, True, vec![(1, Bound)], true);
+    linear_test_one(3, LinearNeq::new, vec![1,2], vec![dom1_1, dom0_10], 4, Unknown, Unknown, vec![], true);
+    linear_test_one(4, LinearNeq::new, vec![1,2], vec![dom1_1, dom2_2], 5, False, False, vec![], false);
+    linear_test_one(5, LinearNeq::new, vec![1,2], vec![dom1_1, dom2_2], 4, True, True, vec![], true);
+    linear_test_one(6, LinearNeq::new, vec![1,1], vec![dom0_10, dom0_10], 30, True, True, vec![], true);
+  }
+
+  fn assign_and_propagate(space: FDSpace, vars: &mut [Var<VStore>], x: usize, value: i32, expected: SKleene) -> FDSpace {
+    let mut space = space;
+    let dom = vars[x].read(&space.vstore).intersection(&(value, value).to_interval_set());
+    assert!(vars[x].update(&mut space.vstore, dom));
+    assert_eq!(space.consistency(), expected);
+    space
+  }
+
+  fn check_domains(space: &FDSpace, vars: &[Var<VStore>], expected: Vec<(i32, i32)>) {
+    for (x, dom) in vars.iter().zip(expected) {
+      assert_eq!(x.read(&space.vstore), dom.to_interval_set());
+    }
+  }
+
+  #[test]
+  fn linear_backtracking_test() {
+    let mut space = FDSpace::empty();
+    let mut vars: Vec<Var<VStore>> = (0..3)
+      .map(|_| Box::new(space.vstore.alloc((1,3).to_interval_set())) as Var<VStore>)
+      .collect();
+    space.cstore.alloc(Box::new(LinearEq::new(vec![1,1,1], vars.iter().map(|v| v.bclone()).collect(), 6)));
+    assert_eq!(space.consistency(), Unknown);
+
+    let mut frozen = space.freeze();
+    let left = frozen.label();
+    let right = frozen.label();
+
+    // `x[0] = 1` gives `x[1] + x[2] = 5`.
+    let space = assign_and_propagate(frozen.restore(left), &mut vars, 0, 1, Unknown);
+    check_domains(&space, &vars, vec![(1,1), (2,3), (2,3)]);
+    let frozen = space.freeze();
+
+    // The bounds of the sum are restored with the domains: `x[0] = 3` gives `x[1] + x[2] = 3`.
+    let space = frozen.restore(right);
+    check_domains(&space, &vars, vec![(1,3), (1,3), (1,3)]);
+    let space = assign_and_propagate(space, &mut vars, 0, 3, Unknown);
+    check_domains(&space, &vars, vec![(3,3), (1,2), (1,2)]);
+    let space = assign_and_propagate(space, &mut vars, 1, 2, True);
+    check_domains(&space, &vars, vec![(3,3), (2,2), (1,1)]);
+  }
+}
@@ -18,12 +18,134 @@
 
 pub mod cmp;
 pub mod distinct;
+pub mod distinct_domain;
 pub mod cumulative;
+pub mod disjunctive;
+pub mod theta_lambda_tree;
 pub mod all_equal;
+pub mod linear;
+pub mod element;
+pub mod table;
+pub mod automaton;
+pub mod regular;
+pub mod global_cardinality;
+pub mod circuit;
+pub mod inverse;
+pub mod lex;
+pub mod among;
+pub mod bin_packing;
+pub mod diffn;
+pub mod nvalue;
+pub mod increasing;
+pub mod sort;
+pub mod sequence;
+pub mod knapsack;
 
 pub use propagators::cmp::*;
 pub use propagators::distinct::*;
+pub use propagators::distinct_domain::*;
 pub use propagators::all_equal::*;
+pub use propagators::theta_lambda_tree::*;
+pub use propagators::linear::*;
+pub use propagators::element::*;
+pub use propagators::table::*;
+pub use propagators::automaton::*;
+pub use propagators::regular::*;
+pub use propagators::global_cardinality::*;
+pub use propagators::circuit::*;
+pub use propagators::inverse::*;
+pub use propagators::lex::*;
+pub use propagators::among::*;
+pub use propagators::bin_packing::*;
+pub use propagators::diffn::*;
+pub use propagators::nvalue::*;
+pub use propagators::increasing::*;
+pub use propagators::sort::*;
+pub use propagators::sequence::*;
+pub use propagators::knapsack::*;
+
+use concept::*;
+
+/// The relation between a number `c` counted by a propagator and a variable `n`: `c = n`, `c <= n` or `c >= n`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relation {
+  Eq,
+  AtMost,
+  AtLeast
+}
+
+/// The positions `0..n` of an array as bounds.
+pub fn positions<Bound: IntBound>(n: usize) -> Vec<Bound> {
+  let mut positions = Vec::with_capacity(n);
+  let mut i = Bound::zero();
+  for _ in 0..n {
+    positions.push(i.clone());
+    i = i + Bound::one();
+  }
+  positions
+}
+
+/// Enumerates the values of `dom` in increasing order, it is linear in the size of the range `[dom.lower()..dom.upper()]`.
+pub fn domain_values<Domain, Bound>(dom: &Domain) -> Vec<Bound> where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  let mut values = vec![];
+  if dom.is_empty() {
+    return values;
+  }
+  let mut v = dom.lower();
+  let ub = dom.upper();
+  while v <= ub {
+    if dom.contains(&v) {
+      values.push(v.clone());
+    }
+    v = v + Bound::one();
+  }
+  values
+}
+
+/// Number of values in `dom`, or `cap` if it is greater than `cap`.
+pub fn cardinality_upto<Domain, Bound>(dom: &Domain, cap: u64) -> u64 where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  let mut count = 0;
+  if dom.is_empty() {
+    return count;
+  }
+  let mut v = dom.lower();
+  let ub = dom.upper();
+  while v <= ub && count < cap {
+    if dom.contains(&v) {
+      count += 1;
+    }
+    v = v + Bound::one();
+  }
+  count
+}
+
+/// Restricts `dom` to the values of `values` (sorted and without duplicates) by removing the holes between them.
+/// The holes are only removed if `Domain` can represent them, otherwise only the bounds are restricted.
+pub fn restrict_to_values<Domain, Bound>(dom: Domain, values: &[Bound]) -> Domain where
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  if values.is_empty() {
+    return Domain::empty();
+  }
+  let mut dom = dom
+    .shrink_left(values[0].clone())
+    .shrink_right(values[values.len()-1].clone());
+  for w in values.windows(2) {
+    let hole_lb = w[0].clone() + Bound::one();
+    let hole_ub = w[1].clone() - Bound::one();
+    if hole_lb <= hole_ub {
+      dom = dom.difference(&Domain::new(hole_lb, hole_ub));
+    }
+  }
+  dom
+}
 
 #[cfg(test)]
 pub mod test
@@ -32,10 +154,17 @@ pub mod test
   use propagation::*;
   use gcollections::ops::*;
   use concept::*;
-  use variable::VStoreFD;
+  use variable::{VStoreFD, VStoreSet};
+  use variable::ops::DrainDelta;
   use propagation::events::*;
   use interval::interval::*;
+  use interval::interval_set::*;
   use variable::store::test::consume_delta;
+  use trilean::SKleene::*;
+  use term::*;
+  use propagators::*;
+  use propagators::disjunctive::*;
+  use propagators::cumulative::*;
 
   // fn error_msg<T: Debug, VStore>(test_num: u32, msg: &str,
   //   prop: &Formula<VStore>, before: &T, after: &T) -> String
@@ -107,11 +236,13 @@ pub mod test
   // }
 
   pub type FDVar = Var<VStoreFD>;
+  pub type SetFDVar = Var<VStoreSet>;
 
-  pub fn test_propagation<P>(test_num: u32, mut prop: P, vstore: &mut VStoreFD,
+  pub fn test_propagation<P, VStore>(test_num: u32, mut prop: P, vstore: &mut VStore,
     before: SKleene, after: SKleene,
     delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool) where
-   P: PropagatorConcept<VStoreFD, FDEvent>
+   P: PropagatorConcept<VStore, FDEvent>,
+   VStore: DrainDelta<FDEvent>
   {
     // test_properties(test_num, vstore, prop.bclone());
     println!("Test number {}", test_num);
@@ -164,4 +295,270 @@ pub mod test
     let propagator = make_prop(vars);
     test_propagation(test_num, propagator, &mut vstore, before, after, delta_expected, propagate_success);
   }
+
+  pub fn nary_set_propagator_test<P, FnProp>(test_num: u32, make_prop: FnProp, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool) where
+   P: PropagatorConcept<VStoreSet, FDEvent>,
+   FnProp: FnOnce(Vec<SetFDVar>) -> P
+  {
+    let mut vstore = VStoreSet::empty();
+    let vars = doms.into_iter()
+      .map(|d| Box::new(vstore.alloc(d)) as Var<VStoreSet>)
+      .collect();
+    let propagator = make_prop(vars);
+    test_propagation(test_num, propagator, &mut vstore, before, after, delta_expected, propagate_success);
+  }
+  /// A xorshift generator, so the random tests are reproducible.
+  pub fn next_random(seed: &mut u64) -> u64 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    *seed
+  }
+
+  /// A non-empty domain with a random subset of the values in `[lb..ub]`, it is a singleton once in three times.
+  fn random_domain(seed: &mut u64, lb: i32, ub: i32) -> IntervalSet<i32> {
+    let singleton = next_random(seed) % 3 == 0;
+    let mut values: Vec<(i32, i32)> = (lb..ub+1)
+      .filter(|_| !singleton && next_random(seed) % 3 != 0)
+      .map(|v| (v, v))
+      .collect();
+    if values.is_empty() {
+      let v = lb + (next_random(seed) % ((ub - lb + 1) as u64)) as i32;
+      values.push((v, v));
+    }
+    values.to_interval_set()
+  }
+
+  fn assignments(doms: &[IntervalSet<i32>]) -> Vec<Vec<i32>> {
+    let mut assignments = vec![vec![]];
+    for dom in doms {
+      let values: Vec<i32> = (dom.lower()..dom.upper()+1).filter(|v| dom.contains(v)).collect();
+      assignments = assignments.into_iter()
+        .flat_map(|a| values.iter().map(move |&v| { let mut a = a.clone(); a.push(v); a }))
+        .collect();
+    }
+    assignments
+  }
+
+  fn alloc_all(doms: &[IntervalSet<i32>]) -> (VStoreSet, Vec<SetFDVar>) {
+    let mut vstore = VStoreSet::empty();
+    let vars = doms.iter()
+      .map(|d| Box::new(vstore.alloc(d.clone())) as Var<VStoreSet>)
+      .collect();
+    (vstore, vars)
+  }
+
+  /// The propagation of `f` keeps every assignment in `solutions`, and fails only if there is none.
+  fn check_sound(name: &str, mut f: Formula<VStoreSet>, doms: &[IntervalSet<i32>], solutions: &[&Vec<i32>]) {
+    let (mut vstore, vars) = alloc_all(doms);
+    if !f.propagate(&mut vstore) {
+      assert!(solutions.is_empty(), "{}: the propagation of `{:?}` failed on {:?}.", name, f, doms);
+      return;
+    }
+    for s in solutions {
+      for (x, v) in vars.iter().zip(s.iter()) {
+        assert!(x.read(&vstore).contains(v), "{}: `{:?}` removed the solution {:?} from {:?}.", name, f, s, doms);
+      }
+    }
+  }
+
+  /// On every assignment of random domains over `[lb..ub]`, one of `p` and `p.not()` is entailed and the other is disentailed.
+  /// Moreover, the propagation of each of them keeps the assignments satisfying it.
+  fn complementary_test<F>(name: &str, arity: usize, lb: i32, ub: i32, make_prop: F) where
+   F: Fn(Vec<SetFDVar>) -> Formula<VStoreSet>
+  {
+    let mut seed = 0x2545F4914F6CDD1D;
+    for _ in 0..100 {
+      let doms: Vec<_> = (0..arity).map(|_| random_domain(&mut seed, lb, ub)).collect();
+      let p = make_prop(alloc_all(&doms).1);
+      let not_p = p.not();
+      let mut sat = vec![];
+      let mut unsat = vec![];
+      let all = assignments(&doms);
+      for a in &all {
+        let singletons: Vec<_> = a.iter().map(|&v| (v, v).to_interval_set()).collect();
+        let vstore = alloc_all(&singletons).0;
+        let status = (p.is_subsumed(&vstore), not_p.is_subsumed(&vstore), not_p.not().is_subsumed(&vstore));
+        match status {
+          (True, False, True) => sat.push(a),
+          (False, True, False) => unsat.push(a),
+          _ => panic!("{}: `{:?}` and its negation are not complementary on {:?}: {:?}.", name, p, a, status)
+        }
+      }
+      check_sound(name, p, &doms, &sat);
+      check_sound(name, not_p, &doms, &unsat);
+    }
+  }
+
+  #[test]
+  fn not_complementary_test() {
+    let var = |v: &Vec<SetFDVar>, i: usize| v[i].bclone();
+    complementary_test("x = y", 2, -1, 3, |v| Box::new(XEqY::new(var(&v, 0), var(&v, 1))));
+    complementary_test("x != y", 2, -1, 3, |v| Box::new(XNeqY::new(var(&v, 0), var(&v, 1))));
+    complementary_test("x < y", 2, -1, 3, |v| Box::new(XLessY::new(var(&v, 0), var(&v, 1))));
+    complementary_test("x <= y", 2, -1, 3, |v| Box::new(x_leq_y(var(&v, 0), var(&v, 1))));
+    complementary_test("x > y", 2, -1, 3, |v| Box::new(x_greater_y(var(&v, 0), var(&v, 1))));
+    complementary_test("x >= y", 2, -1, 3, |v| Box::new(x_geq_y(var(&v, 0), var(&v, 1))));
+    complementary_test("x = y + z", 3, -1, 3, |v| Box::new(XEqYPlusZ::new(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x < y + z", 3, -1, 3, |v| Box::new(XLessYPlusZ::new(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x > y + z", 3, -1, 3, |v| Box::new(XGreaterYPlusZ::new(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x <= y + z", 3, -1, 3, |v| Box::new(x_leq_y_plus_z(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x >= y + z", 3, -1, 3, |v| Box::new(x_geq_y_plus_z(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x = y * z", 3, -2, 2, |v| Box::new(XEqYMulZ::new(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x = y / z", 3, -2, 2, |v| Box::new(XEqYDivZ::new(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x = y % z", 3, -2, 2, |v| Box::new(XEqYModZ::new(var(&v, 0), var(&v, 1), var(&v, 2))));
+    complementary_test("x = |y|", 2, -3, 3, |v| Box::new(XEqAbsY::new(var(&v, 0), var(&v, 1))));
+    for k in 0..4 {
+      complementary_test("x = y^k", 2, -3, 9, |v| Box::new(XEqYPowK::new(var(&v, 0), var(&v, 1), k)));
+    }
+    complementary_test("x = max(y, z)", 3, -1, 3, |v| Box::new(x_eq_max(var(&v, 0), vec![var(&v, 1), var(&v, 2)])));
+    complementary_test("x = min(y, z)", 3, -1, 3, |v| Box::new(x_eq_min(var(&v, 0), vec![var(&v, 1), var(&v, 2)])));
+    complementary_test("distinct", 3, -1, 2, |v| Box::new(Distinct::new(v)));
+    complementary_test("distinct_domain", 3, -1, 2, |v| Box::new(DistinctDomain::new(v)));
+    complementary_test("all_equal", 3, -1, 2, |v| Box::new(AllEqual::new(v)));
+    complementary_test("linear_leq", 3, -1, 3, |v| Box::new(LinearLeq::new(vec![2, -1, 1], v, 2)));
+    complementary_test("linear_eq", 3, -1, 3, |v| Box::new(LinearEq::new(vec![2, -1, 1], v, 2)));
+    complementary_test("linear_neq", 3, -1, 3, |v| Box::new(LinearNeq::new(vec![2, -1, 1], v, 2)));
+    complementary_test("element_const", 2, -1, 3, |v| Box::new(ElementConst::new(var(&v, 0), vec![2, 0, 2], var(&v, 1))));
+    complementary_test("element_var", 4, -1, 2, |v| Box::new(ElementVar::new(var(&v, 0), vec![var(&v, 1), var(&v, 2)], var(&v, 3))));
+    complementary_test("table", 2, 0, 3, |v| Box::new(Table::new(v, vec![vec![0, 1], vec![1, 1], vec![2, 3]])));
+    complementary_test("forbidden_table", 2, 0, 3, |v| Box::new(Table::forbidden(v, vec![vec![0, 1], vec![1, 1], vec![2, 3]])));
+    complementary_test("regular", 3, -1, 2, |v| Box::new(Regular::new(v, Automaton::from_regex("0* 1 [0 1]*").unwrap())));
+    complementary_test("global_cardinality", 3, -1, 2, |v| Box::new(GlobalCardinality::new(v, vec![0, 1], vec![(1, 2), (0, 1)])));
+    complementary_test("global_cardinality_counts", 4, -1, 2, |mut v| {
+      let counts = v.split_off(2);
+      Box::new(GlobalCardinality::with_counts(v, vec![0, 1], counts))
+    });
+    complementary_test("circuit", 3, -1, 3, |v| Box::new(Circuit::new(v)));
+    complementary_test("subcircuit", 3, -1, 3, |v| Box::new(Subcircuit::new(v)));
+    complementary_test("inverse", 4, -1, 2, |mut x| {
+      let y = x.split_off(2);
+      Box::new(Inverse::new(x, y))
+    });
+    complementary_test("lex_less", 4, -1, 2, |mut x| {
+      let y = x.split_off(2);
+      Box::new(LexLess::new(x, y))
+    });
+    complementary_test("lex_leq", 4, -1, 2, |mut x| {
+      let y = x.split_off(2);
+      Box::new(lex_leq(x, y))
+    });
+    complementary_test("lex_chain", 6, 0, 1, |mut x| {
+      let z = x.split_off(4);
+      let y = x.split_off(2);
+      Box::new(LexChain::new(vec![x, y, z]))
+    });
+    complementary_test("among", 4, 0, 3, |mut x| {
+      let n = x.pop().unwrap();
+      Box::new(Among::new(n, x, vec![1, 3]))
+    });
+    complementary_test("at_most", 4, 0, 3, |mut x| {
+      let n = x.pop().unwrap();
+      Box::new(at_most(n, x, 1))
+    });
+    complementary_test("at_least", 4, 0, 3, |mut x| {
+      let n = x.pop().unwrap();
+      Box::new(at_least(n, x, 1))
+    });
+    complementary_test("bin_packing", 5, 0, 3, |mut assignment| {
+      let loads = assignment.split_off(3);
+      Box::new(BinPacking::new(assignment, vec![2, 1, 1], loads))
+    });
+    complementary_test("diffn", 8, -1, 2, |mut x| {
+      let h = x.split_off(6);
+      let w = x.split_off(4);
+      let y = x.split_off(2);
+      Box::new(Diffn::new(x, y, w, h))
+    });
+    complementary_test("nvalue", 4, 0, 3, |mut x| {
+      let n = x.pop().unwrap();
+      Box::new(NValue::new(n, x))
+    });
+    complementary_test("at_most_nvalue", 4, 0, 3, |mut x| {
+      let n = x.pop().unwrap();
+      Box::new(at_most_nvalue(n, x))
+    });
+    complementary_test("at_least_nvalue", 4, 0, 3, |mut x| {
+      let n = x.pop().unwrap();
+      Box::new(at_least_nvalue(n, x))
+    });
+    complementary_test("increasing", 3, -1, 2, |x| Box::new(Increasing::new(x)));
+    complementary_test("strictly_increasing", 3, -1, 2, |x| Box::new(strictly_increasing(x)));
+    complementary_test("decreasing", 3, -1, 2, |x| Box::new(decreasing(x)));
+    complementary_test("strictly_decreasing", 3, -1, 2, |x| Box::new(strictly_decreasing(x)));
+    complementary_test("sort", 6, 0, 3, |mut x| {
+      let y = x.split_off(3);
+      Box::new(Sort::new(x, y))
+    });
+    complementary_test("sequence", 5, -1, 1, |x| Box::new(::propagators::Sequence::new(x, 3, 1, 2)));
+    complementary_test("knapsack", 5, -1, 3, |mut x| {
+      let profit = x.pop().unwrap();
+      let capacity = x.pop().unwrap();
+      Box::new(Knapsack::new(vec![1, 2, 1], vec![2, -1, 1], x, capacity, profit))
+    });
+    complementary_test("disjunctive", 4, 0, 3, |mut starts| {
+      let durations = starts.split_off(2);
+      Box::new(Disjunctive::new(starts, durations))
+    });
+    complementary_test("cumulative", 4, 0, 2, |mut starts| {
+      let durations = starts.split_off(2);
+      let resources = vec![Box::new(Constant::new(1)) as Var<VStoreSet>, Box::new(Constant::new(1)) as Var<VStoreSet>];
+      Box::new(Cumulative::new(starts, durations, resources, Box::new(Constant::new(1))))
+    });
+  }
+
+  /// Same as `nary_propagator_test` for a formula, such as the negation of a propagator.
+  fn nary_formula_test<F>(test_num: u32, make_formula: F, doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene,
+    delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool) where
+   F: FnOnce(Vec<FDVar>) -> Formula<VStoreFD>
+  {
+    let mut vstore = VStoreFD::empty();
+    let vars = doms.into_iter()
+      .map(|d| Box::new(vstore.alloc(d)) as Var<VStoreFD>)
+      .collect();
+    let mut f = make_formula(vars);
+    println!("Test number {}", test_num);
+    assert_eq!(f.is_subsumed(&vstore), before);
+    assert_eq!(f.propagate(&mut vstore), propagate_success);
+    if propagate_success {
+      consume_delta(&mut vstore, delta_expected);
+    }
+    assert_eq!(f.is_subsumed(&vstore), after);
+  }
+
+  #[test]
+  fn not_pruning_test() {
+    use logic::NotFormula;
+    use propagation::events::FDEvent::*;
+    use propagators::Sequence;
+    let b = (0,1).to_interval();
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let three = (3,3).to_interval();
+
+    // `x[1] <= x[2]` is entailed, so `x[0] > x[1]`.
+    nary_formula_test(1, |x| Increasing::new(x).not(), vec![(0,3).to_interval(), two, three],
+      Unknown, True, vec![(0, Assignment)], true);
+    // `x[0] >= x[1]` for the strict variant.
+    nary_formula_test(2, |x| strictly_increasing(x).not(), vec![(0,3).to_interval(), two, three],
+      Unknown, True, vec![(0, Bound)], true);
+    // The only window needs two ones.
+    nary_formula_test(3, |x| Sequence::new(x, 2, 1, 1).not(), vec![one, b],
+      Unknown, True, vec![(1, Assignment)], true);
+    nary_formula_test(4, |x| Sequence::new(x, 2, 1, 1).not(), vec![one, zero],
+      False, False, vec![], false);
+    // At most one value since two values are at most reached, and the last variable takes the assigned value.
+    nary_formula_test(5, |mut x| { let n = x.pop().unwrap(); NValue::new(n, x).not() },
+      vec![one, one, (1,2).to_interval(), two], Unknown, True, vec![(2, Assignment)], true);
+    // Two tasks must overlap.
+    nary_formula_test(6, |mut s| { let d = s.split_off(2); Disjunctive::new(s, d).not() },
+      vec![zero, (0,5).to_interval(), two, two], Unknown, True, vec![(1, Bound)], true);
+    // A value outside of the alphabet `{0, 1}` is needed.
+    nary_formula_test(7, |x| Regular::new(x, Automaton::from_regex("[0 1]*").unwrap()).not(),
+      vec![one, zero, (0,2).to_interval()], Unknown, True, vec![(2, Assignment)], true);
+  }
 }
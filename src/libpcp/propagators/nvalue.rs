@@ -0,0 +1,372 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint `nvalue(n, vars)` ensures that `n` is the number of distinct values taken by the variables `vars`.
+//! `Distinct` and `AllEqual` are the special cases where `n` is respectively the number of variables and `1`.
+//! The variants `at_most_nvalue` and `at_least_nvalue` ensure that the number of distinct values is at most or at least `n`.
+//!
+//! The bounds of `n` are computed on the intervals of the domains of `vars`:
+//!  * Lower bound: the minimal number of values intersecting every interval, obtained greedily by considering the intervals by increasing upper bound.
+//!  * Upper bound: the size of a maximum matching between the intervals and the values, also obtained greedily by giving to each interval, by increasing upper bound, its smallest value not taken yet.
+//!
+//! When `n` is tight, the variables are pruned:
+//!  * If the upper bound of `n` is the number of distinct values already assigned, the other variables must take one of these values, unless it is `at_least_nvalue`.
+//!  * If the lower bound of `n` requires every unassigned variable to take a new value, the assigned values are removed from them, unless it is `at_most_nvalue`.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use term::*;
+use propagators::{Relation, positions, restrict_to_values};
+
+/// The number of distinct values of `vars` is equal to `n`, at most `n` if it is built with `at_most_nvalue` or at least `n` if it is built with `at_least_nvalue`.
+#[derive(Debug)]
+pub struct NValue<VStore>
+{
+  n: Var<VStore>,
+  vars: Vec<Var<VStore>>,
+  relation: Relation
+}
+
+pub type AtMostNValue<VStore> = NValue<VStore>;
+pub type AtLeastNValue<VStore> = NValue<VStore>;
+
+impl<VStore> NValue<VStore>
+{
+  pub fn new(n: Var<VStore>, vars: Vec<Var<VStore>>) -> Self {
+    NValue::make(n, vars, Relation::Eq)
+  }
+
+  fn make(n: Var<VStore>, vars: Vec<Var<VStore>>, relation: Relation) -> Self {
+    NValue {
+      n: n,
+      vars: vars,
+      relation: relation
+    }
+  }
+}
+
+/// The variables of `vars` take at most `n` distinct values.
+pub fn at_most_nvalue<VStore>(n: Var<VStore>, vars: Vec<Var<VStore>>) -> AtMostNValue<VStore> {
+  NValue::make(n, vars, Relation::AtMost)
+}
+
+/// The variables of `vars` take at least `n` distinct values.
+pub fn at_least_nvalue<VStore>(n: Var<VStore>, vars: Vec<Var<VStore>>) -> AtLeastNValue<VStore> {
+  NValue::make(n, vars, Relation::AtLeast)
+}
+
+impl<VStore> Clone for NValue<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    NValue {
+      n: self.n.bclone(),
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      relation: self.relation
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for NValue<VStore>
+{
+  fn display(&self, model: &Model) {
+    let name = match self.relation {
+      Relation::Eq => "nvalue",
+      Relation::AtMost => "at_most_nvalue",
+      Relation::AtLeast => "at_least_nvalue"
+    };
+    print!("{}(", name);
+    self.n.display(model);
+    print!(", ");
+    if !self.vars.is_empty() {
+      model.display_global("x", &self.vars);
+    }
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for NValue<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// `c != n` is `c <= n - 1 \/ c >= n + 1`, `c > n` is `c >= n + 1` and `c < n` is `c <= n - 1`, where `c` is the number of distinct values.
+  fn not(&self) -> Formula<VStore> {
+    let at_most_pred = || Box::new(NValue::make(
+      Box::new(Addition::new(self.n.bclone(), -Bound::one())),
+      self.vars.iter().map(|v| v.bclone()).collect(),
+      Relation::AtMost)) as Formula<VStore>;
+    let at_least_succ = || Box::new(NValue::make(
+      Box::new(Addition::new(self.n.bclone(), Bound::one())),
+      self.vars.iter().map(|v| v.bclone()).collect(),
+      Relation::AtLeast)) as Formula<VStore>;
+    match self.relation {
+      Relation::Eq => Box::new(Disjunction::new(vec![at_most_pred(), at_least_succ()])),
+      Relation::AtMost => at_least_succ(),
+      Relation::AtLeast => at_most_pred()
+    }
+  }
+}
+
+/// The minimal number of values such that every interval contains one of them.
+fn min_values<Bound: IntBound>(intervals: &[(Bound, Bound)]) -> usize {
+  let mut by_upper = intervals.to_vec();
+  by_upper.sort_by(|a, b| a.1.cmp(&b.1));
+  let mut count = 0;
+  let mut last: Option<Bound> = None;
+  for (lb, ub) in by_upper {
+    match last {
+      Some(ref v) if lb <= *v => (),
+      _ => {
+        count += 1;
+        last = Some(ub);
+      }
+    }
+  }
+  count
+}
+
+/// The maximal number of intervals that can be given pairwise distinct values.
+fn max_values<Bound: IntBound>(intervals: &[(Bound, Bound)]) -> usize {
+  let mut by_upper = intervals.to_vec();
+  by_upper.sort_by(|a, b| a.1.cmp(&b.1));
+  let mut taken: Vec<Bound> = vec![];
+  for (mut v, ub) in by_upper {
+    while taken.contains(&v) {
+      v = v + Bound::one();
+    }
+    if v <= ub {
+      taken.push(v);
+    }
+  }
+  taken.len()
+}
+
+impl<VStore, Domain, Bound> NValue<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The bounds `[lb..ub]` of the number of distinct values.
+  fn count_bounds(&self, store: &VStore) -> (Bound, Bound) {
+    let intervals: Vec<_> = self.vars.iter()
+      .map(|x| x.read(store))
+      .map(|d| (d.lower(), d.upper()))
+      .collect();
+    let numbers: Vec<Bound> = positions(self.vars.len() + 1);
+    (numbers[min_values(&intervals)].clone(), numbers[max_values(&intervals)].clone())
+  }
+
+  /// The distinct values of the assigned variables, sorted, and the number of unassigned variables.
+  fn assigned_values(&self, store: &VStore) -> (Vec<Bound>, usize) {
+    let mut values = vec![];
+    let mut unassigned = 0;
+    for x in &self.vars {
+      let dom = x.read(store);
+      if dom.is_singleton() { values.push(dom.lower()); }
+      else { unassigned += 1; }
+    }
+    values.sort();
+    values.dedup();
+    (values, unassigned)
+  }
+
+  /// Restricts the unassigned variables to `values` if `inside`, or removes `values` from them otherwise.
+  fn prune_unassigned(&mut self, store: &mut VStore, values: &[Bound], inside: bool) -> bool {
+    for x in &mut self.vars {
+      let dom = x.read(store);
+      if !dom.is_singleton() {
+        let dom =
+          if inside { restrict_to_values(dom, values) }
+          else { values.iter().fold(dom, |d, v| d.difference(v)) };
+        if !x.update(store, dom) {
+          return false;
+        }
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for NValue<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: `n` and the bounds `[lb..ub]` of the number of distinct values cannot satisfy the relation.
+    // True: every value of `[lb..ub]` and `n` satisfies the relation.
+    // Unknown: Everything else.
+    let n = self.n.read(store);
+    if n.is_empty() {
+      return False;
+    }
+    let (lb, ub) = self.count_bounds(store);
+    match self.relation {
+      Relation::Eq =>
+        if n.upper() < lb || n.lower() > ub { False }
+        else if n.is_singleton() && lb == ub && n.lower() == lb { True }
+        else { Unknown },
+      Relation::AtMost =>
+        if lb > n.upper() { False }
+        else if ub <= n.lower() { True }
+        else { Unknown },
+      Relation::AtLeast =>
+        if ub < n.lower() { False }
+        else if lb >= n.upper() { True }
+        else { Unknown }
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for NValue<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let (lb, ub) = self.count_bounds(store);
+    let mut n = self.n.read(store);
+    if self.relation != Relation::AtLeast {
+      n = n.shrink_left(lb);
+    }
+    if self.relation != Relation::AtMost {
+      n = n.shrink_right(ub);
+    }
+    if !self.n.update(store, n) {
+      return false;
+    }
+    let n = self.n.read(store);
+    let (values, unassigned) = self.assigned_values(store);
+    let numbers: Vec<Bound> = positions(self.vars.len() + 1);
+    if unassigned == 0 {
+      true
+    }
+    else if self.relation != Relation::AtLeast && numbers[values.len()] == n.upper() {
+      self.prune_unassigned(store, &values, true)
+    }
+    else if self.relation != Relation::AtMost && numbers[values.len() + unassigned] == n.lower() {
+      self.prune_unassigned(store, &values, false)
+    }
+    else {
+      true
+    }
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for NValue<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.extend(self.n.dependencies(FDEvent::Bound));
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use variable::VStoreSet;
+  use propagators::test::*;
+
+  /// The number of values `n` is the last variable.
+  fn nvalue_test_one(test_num: u32, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nvalue_relation_test_one(test_num, NValue::new, doms, before, after, delta_expected, propagate_success);
+  }
+
+  fn nvalue_relation_test_one<F>(test_num: u32, make: F, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool) where
+   F: FnOnce(SetFDVar, Vec<SetFDVar>) -> NValue<VStoreSet>
+  {
+    nary_set_propagator_test(test_num, |mut vars: Vec<SetFDVar>| {
+        let n = vars.pop().unwrap();
+        make(n, vars)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn nvalue_test() {
+    let dom0_3 = (0,3).to_interval_set();
+    let dom0_9 = (0,9).to_interval_set();
+    let dom1_2 = (1,2).to_interval_set();
+    let dom1_3 = (1,3).to_interval_set();
+    let dom2_3 = (2,3).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let three = (3,3).to_interval_set();
+    let zero_two = vec![(0,0), (2,2)].to_interval_set();
+
+    nvalue_test_one(1, vec![dom0_3.clone(), dom0_3.clone(), dom0_9.clone()], Unknown, Unknown, vec![(2, Bound)], true);
+    // `[1..2]` and `[2..3]` can share the value `2`, so `n` cannot be `0`.
+    nvalue_test_one(2, vec![dom1_2.clone(), dom2_3.clone(), zero_two.clone()], Unknown, Unknown, vec![(2, Assignment)], true);
+    nvalue_test_one(3, vec![(0,0).to_interval_set(), dom2_3.clone(), one.clone(), one.clone()], False, False, vec![], false);
+    nvalue_test_one(4, vec![one.clone(), two.clone(), one.clone(), dom0_3.clone()], Unknown, True, vec![(3, Assignment)], true);
+    // Only one value is left, the variables take the values already assigned.
+    nvalue_test_one(5, vec![one.clone(), dom0_3.clone(), dom1_3.clone(), one.clone()], Unknown, True, vec![(1, Assignment), (2, Assignment)], true);
+    // Every variable takes a new value, as in `Distinct`.
+    nvalue_test_one(6, vec![one.clone(), dom0_3.clone(), dom1_2.clone(), three.clone()], Unknown, Unknown, vec![(1, Inner), (2, Assignment)], true);
+    nvalue_test_one(7, vec![one.clone(), two.clone(), three.clone()], False, False, vec![], false);
+    // The holes are not considered in the bounds of `n`.
+    nvalue_test_one(8, vec![zero_two.clone(), zero_two.clone(), zero_two.clone(), three.clone()], Unknown, Unknown, vec![], true);
+    nvalue_test_one(9, vec![one.clone(), two.clone(), dom1_2.clone()], Unknown, True, vec![(2, Assignment)], true);
+  }
+
+  #[test]
+  fn at_most_at_least_nvalue_test() {
+    let dom0_3 = (0,3).to_interval_set();
+    let dom0_9 = (0,9).to_interval_set();
+    let dom1_3 = (1,3).to_interval_set();
+    let one = (1,1).to_interval_set();
+    let two = (2,2).to_interval_set();
+    let three = (3,3).to_interval_set();
+
+    nvalue_relation_test_one(1, at_most_nvalue, vec![one.clone(), two.clone(), dom1_3.clone(), dom0_9.clone()], Unknown, Unknown, vec![(3, Bound)], true);
+    // The values already assigned are the only ones left.
+    nvalue_relation_test_one(2, at_most_nvalue, vec![one.clone(), two.clone(), dom0_3.clone(), two.clone()], Unknown, True, vec![(2, Bound)], true);
+    nvalue_relation_test_one(3, at_most_nvalue, vec![one.clone(), two.clone(), three.clone(), (0,2).to_interval_set()], False, False, vec![], false);
+    // `n` is not bounded from below.
+    nvalue_relation_test_one(4, at_least_nvalue, vec![one.clone(), two.clone(), dom1_3.clone(), dom0_9.clone()], Unknown, Unknown, vec![(3, Bound)], true);
+    // The last variable must take a new value.
+    nvalue_relation_test_one(5, at_least_nvalue, vec![one.clone(), one.clone(), dom0_3.clone(), two.clone()], Unknown, Unknown, vec![(2, Inner)], true);
+    nvalue_relation_test_one(6, at_least_nvalue, vec![one.clone(), one.clone(), one.clone(), (2,3).to_interval_set()], False, False, vec![], false);
+  }
+
+  #[test]
+  fn bounds_test() {
+    assert_eq!(min_values::<i32>(&[]), 0);
+    assert_eq!(max_values::<i32>(&[]), 0);
+    let intervals = vec![(0, 2), (1, 1), (3, 5), (4, 4), (1, 2)];
+    assert_eq!(min_values(&intervals), 2);
+    assert_eq!(max_values(&intervals), 5);
+    let intervals = vec![(0, 1), (0, 1), (0, 1), (1, 2)];
+    assert_eq!(min_values(&intervals), 1);
+    assert_eq!(max_values(&intervals), 3);
+  }
+}
@@ -0,0 +1,269 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint `Regular` restricts the sequence of the values of a vector of variables to be a word accepted by a deterministic finite automaton.
+//!
+//! It is the filtering algorithm on the layered graph described in `A Regular Language Membership Constraint for Finite Sequences of Variables`, Pesant, 2004.
+//! The layer `i` contains the states of the automaton reachable after reading the values of `x[0..i]`, the transitions between two layers are labelled by the values of `x[i]`.
+//! A value is kept if it labels a transition on a path from the initial state to an accepting state, hence the filtering is domain consistent.
+//! The values without their own transition from a state take the transition of this state for the other values, if any.
+//!
+//! The negation is `Regular` over the complement of the automaton, see `Automaton::complement`.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use propagators::automaton::*;
+use propagators::{restrict_to_values, cardinality_upto};
+use std::rc::Rc;
+
+/// `x[0] x[1] ... x[n-1]` is accepted by `automaton`.
+#[derive(Debug)]
+pub struct Regular<VStore, Bound>
+{
+  vars: Vec<Var<VStore>>,
+  automaton: Rc<Automaton<Bound>>
+}
+
+impl<VStore, Bound> Regular<VStore, Bound>
+{
+  pub fn new(vars: Vec<Var<VStore>>, automaton: Automaton<Bound>) -> Self {
+    Regular {
+      vars: vars,
+      automaton: Rc::new(automaton)
+    }
+  }
+}
+
+impl<VStore, Bound> Clone for Regular<VStore, Bound> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Regular {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      automaton: self.automaton.clone()
+    }
+  }
+}
+
+impl<VStore, Bound> DisplayStateful<Model> for Regular<VStore, Bound>
+{
+  fn display(&self, model: &Model) {
+    if self.vars.is_empty() {
+      print!("regular()");
+    }
+    else {
+      model.display_global("regular", &self.vars);
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Regular<VStore, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Regular {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      automaton: Rc::new(self.automaton.complement())
+    })
+  }
+}
+
+impl<VStore, Domain, Bound> Regular<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The states reached from `q` with the values of `dom`.
+  fn successors(&self, q: usize, dom: &Domain) -> Vec<usize> {
+    let a = &self.automaton;
+    let mut labels = 0;
+    let mut successors = vec![];
+    for &(ref v, next) in a.transitions(q) {
+      if dom.contains(v) {
+        successors.push(next);
+        labels += 1;
+      }
+    }
+    if let Some(others) = a.others(q) {
+      if cardinality_upto(dom, labels + 1) != labels {
+        successors.push(others);
+      }
+    }
+    successors
+  }
+
+  /// `layers[i][q]` is true if the state `q` is on a path from the initial state to an accepting state, after reading `x[0..i]`.
+  fn layered_graph(&self, doms: &[Domain]) -> Vec<Vec<bool>> {
+    let a = &self.automaton;
+    let n = doms.len();
+    let mut layers = vec![vec![false; a.num_states()]; n + 1];
+    layers[0][a.initial()] = true;
+    for i in 0..n {
+      for q in 0..a.num_states() {
+        if layers[i][q] {
+          for next in self.successors(q, &doms[i]) {
+            layers[i+1][next] = true;
+          }
+        }
+      }
+    }
+    for (q, state) in layers[n].iter_mut().enumerate() {
+      *state = *state && a.is_accepting(q);
+    }
+    for i in (0..n).rev() {
+      for q in 0..a.num_states() {
+        layers[i][q] = layers[i][q] && self.successors(q, &doms[i]).into_iter()
+          .any(|next| layers[i+1][next]);
+      }
+    }
+    layers
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Regular<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: no path from the initial state to an accepting state.
+    // True: every state reachable with the values of the domains has a transition for every value of the next variable, and every state reachable at the end is accepting.
+    // Unknown: Everything else.
+    let doms: Vec<Domain> = self.vars.iter().map(|v| v.read(store)).collect();
+    let a = &self.automaton;
+    if !self.layered_graph(&doms)[0][a.initial()] {
+      return False;
+    }
+    let mut reachable = vec![false; a.num_states()];
+    reachable[a.initial()] = true;
+    for dom in &doms {
+      let mut next_layer = vec![false; a.num_states()];
+      for q in (0..a.num_states()).filter(|&q| reachable[q]) {
+        let labels = a.transitions(q).iter().filter(|t| dom.contains(&t.0)).count() as u64;
+        if a.others(q).is_none() && cardinality_upto(dom, labels + 1) != labels {
+          return Unknown;
+        }
+        for next in self.successors(q, dom) {
+          next_layer[next] = true;
+        }
+      }
+      reachable = next_layer;
+    }
+    if (0..a.num_states()).all(|q| !reachable[q] || a.is_accepting(q)) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Regular<VStore, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let doms: Vec<Domain> = self.vars.iter().map(|v| v.read(store)).collect();
+    let layers = self.layered_graph(&doms);
+    let a = self.automaton.clone();
+    if !layers[0][a.initial()] {
+      return false;
+    }
+    for (i, dom) in doms.into_iter().enumerate() {
+      let states: Vec<usize> = (0..a.num_states()).filter(|&q| layers[i][q]).collect();
+      let mut symbols: Vec<Bound> = states.iter()
+        .flat_map(|&q| a.transitions(q).iter().map(|t| t.0.clone()))
+        .filter(|v| dom.contains(v))
+        .collect();
+      symbols.sort();
+      symbols.dedup();
+      let supported = |v: &Bound| states.iter().any(|&q| a.next(q, v).is_some_and(|next| layers[i+1][next]));
+      // The values outside of `symbols` are supported by the transitions for the other values.
+      let others = states.iter().any(|&q| a.others(q).is_some_and(|next| layers[i+1][next]));
+      let dom =
+        if others {
+          symbols.iter().filter(|v| !supported(v)).fold(dom, |dom, v| dom.difference(v))
+        }
+        else {
+          let symbols: Vec<Bound> = symbols.iter().filter(|v| supported(v)).cloned().collect();
+          restrict_to_values(dom, &symbols)
+        };
+      if !self.vars[i].update(store, dom) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore, Bound> PropagatorDependencies<FDEvent> for Regular<VStore, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+
+  fn regular_test_one(test_num: u32, regex: &str, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    let automaton = Automaton::from_regex(regex).unwrap();
+    nary_set_propagator_test(test_num, |vars| Regular::new(vars, automaton),
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn regular_test() {
+    // `0` is a day shift, `1` a night shift and `2` a rest day.
+    let nights = "([0 2] | 1{1,3} [0 2])* 1{0,3}";
+    let rest = "([0 2] | 1+ 2)* 1*";
+    let dom0_2 = (0,2).to_interval_set();
+    let dom0_1 = (0,1).to_interval_set();
+    let dom0_0 = (0,0).to_interval_set();
+    let dom1_1 = (1,1).to_interval_set();
+    let dom2_2 = (2,2).to_interval_set();
+
+    regular_test_one(1, nights, vec![dom0_2.clone(); 4], Unknown, Unknown, vec![], true);
+    regular_test_one(2, nights, vec![dom1_1.clone(), dom1_1.clone(), dom1_1.clone(), dom0_2.clone()], Unknown, True, vec![(3, Inner)], true);
+    regular_test_one(3, nights, vec![dom0_2.clone(), dom1_1.clone(), dom1_1.clone(), dom1_1.clone(), dom0_2.clone()], Unknown, True, vec![(0, Inner), (4, Inner)], true);
+    regular_test_one(4, nights, vec![dom1_1.clone(); 4], False, False, vec![], false);
+    regular_test_one(5, nights, vec![dom0_1.clone(); 3], True, True, vec![], true);
+    regular_test_one(6, rest, vec![dom1_1.clone(), dom0_2.clone(), dom0_0.clone()], Unknown, True, vec![(1, Assignment)], true);
+    regular_test_one(7, rest, vec![dom0_2.clone(), dom0_2.clone(), dom0_0.clone()], Unknown, Unknown, vec![(1, Inner)], true);
+    regular_test_one(8, rest, vec![dom1_1.clone(), dom0_0.clone()], False, False, vec![], false);
+    regular_test_one(9, rest, vec![dom2_2.clone(), (3,5).to_interval_set()], False, False, vec![], false);
+    regular_test_one(10, rest, vec![], True, True, vec![], true);
+  }
+}
@@ -0,0 +1,362 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint `sequence(x, k, l, u)` ensures that every window of `k` consecutive Boolean variables of `x` contains between `l` and `u` variables equal to `1`.
+//!
+//! The filtering is domain consistent, as the one of `Revisiting the Sequence Constraint`, van Hoeve and al., 2006, but it is not their incremental algorithm.
+//! It relies on the encoding of the constraint with the prefix sums `S[t] = x[0] + ... + x[t-1]` described in `Encodings of the Sequence Constraint`, Brand and al., 2007.
+//! The constraints `0 <= S[t+1] - S[t] <= 1` and `l <= S[i+k] - S[i] <= u` form a system of difference constraints, satisfiable if and only if its graph has no negative cycle.
+//! A potential is computed by Bellman-Ford, and the shortest paths between `S[t]` and `S[t+1]` of every unassigned `x[t]` are then computed by Dijkstra on the reweighted graph.
+//! They are the largest and, negated, the smallest values of `x[t] = S[t+1] - S[t]` over all the solutions.
+//!
+//! The potential is kept between two propagations and is the starting point of the next Bellman-Ford, which often has few edges to repair.
+//! It does not need to be restored on backtracking: Bellman-Ford converges from any starting vector when there is no negative cycle, so a stale potential only costs more rounds.
+//! A propagation is in `O(n^2)` in the worst case for Bellman-Ford, and in `O(m n log n)` for the Dijkstra of the `m` unassigned variables.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use term::Constant;
+use propagators::{positions, XLessY, x_greater_y, LinearLeq};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Precondition: the variables are Boolean, the values outside of `[0..1]` are removed by the propagation.
+#[derive(Debug)]
+pub struct Sequence<VStore>
+{
+  vars: Vec<Var<VStore>>,
+  k: usize,
+  l: usize,
+  u: usize,
+  /// The potential of the prefix sums graph found by the last propagation.
+  potential: Vec<i64>
+}
+
+impl<VStore> Sequence<VStore>
+{
+  /// Every window of `k` variables has at least `l` and at most `u` variables equal to `1`.
+  pub fn new(vars: Vec<Var<VStore>>, k: usize, l: usize, u: usize) -> Self {
+    assert!(k > 0 && l <= u, "The windows of `Sequence` must be non-empty with `l <= u`.");
+    let potential = vec![0; vars.len() + 1];
+    Sequence {
+      vars: vars,
+      k: k,
+      l: l,
+      u: u,
+      potential: potential
+    }
+  }
+}
+
+impl<VStore> Clone for Sequence<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Sequence {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      k: self.k,
+      l: self.l,
+      u: self.u,
+      potential: self.potential.clone()
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Sequence<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("sequence(");
+    if !self.vars.is_empty() {
+      model.display_global("x", &self.vars);
+    }
+    print!(", {}, {}, {})", self.k, self.l, self.u);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Sequence<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// A variable is not Boolean, or a window has less than `l` or more than `u` ones: `sum(window) <= l - 1 \/ -sum(window) <= -(u + 1)`.
+  /// Without variables, the constraint is always entailed and its negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    if self.vars.is_empty() {
+      return Box::new(Negation::new(Box::new(self.clone())));
+    }
+    let numbers: Vec<Bound> = positions(self.u + 2);
+    let constant = |v: &Bound| Box::new(Constant::new(v.clone())) as Var<VStore>;
+    let mut fs: Vec<Formula<VStore>> = vec![];
+    for x in &self.vars {
+      fs.push(Box::new(XLessY::new(x.bclone(), constant(&Bound::zero()))));
+      fs.push(Box::new(x_greater_y(x.bclone(), constant(&Bound::one()))));
+    }
+    for i in 0..self.windows() {
+      let window = || self.vars[i..i + self.k].iter().map(|v| v.bclone()).collect();
+      fs.push(Box::new(LinearLeq::new(vec![Bound::one(); self.k], window(),
+        numbers[self.l].clone() - Bound::one())));
+      fs.push(Box::new(LinearLeq::new(vec![-Bound::one(); self.k], window(),
+        -numbers[self.u + 1].clone())));
+    }
+    Box::new(Disjunction::new(fs))
+  }
+}
+
+/// The graph of the difference constraints between the prefix sums, `adj[a]` contains `(b, w)` for `S[b] - S[a] <= w`.
+fn prefix_sums_graph(lo: &[i64], hi: &[i64], k: usize, l: i64, u: i64) -> Vec<Vec<(usize, i64)>> {
+  let n = lo.len();
+  let mut adj = vec![vec![]; n + 1];
+  for t in 0..n {
+    adj[t].push((t + 1, hi[t]));
+    adj[t + 1].push((t, -lo[t]));
+  }
+  for i in (0..n + 1).take_while(|i| i + k <= n) {
+    adj[i].push((i + k, u));
+    adj[i + k].push((i, -l));
+  }
+  adj
+}
+
+/// A potential `p` such that `p[b] <= p[a] + w` for every edge, `None` if there is a negative cycle.
+/// Bellman-Ford starts from `from`, which can be any vector, it is usually the potential of a close graph.
+fn potential(adj: &[Vec<(usize, i64)>], from: &[i64]) -> Option<Vec<i64>> {
+  let mut p = from.to_vec();
+  for _ in 0..adj.len() + 1 {
+    let mut changed = false;
+    for a in 0..adj.len() {
+      for &(b, w) in &adj[a] {
+        if p[a] + w < p[b] {
+          p[b] = p[a] + w;
+          changed = true;
+        }
+      }
+    }
+    if !changed {
+      return Some(p);
+    }
+  }
+  None
+}
+
+/// The shortest distances from `source`, the graph is reweighted by the potential `p` to only have non-negative weights.
+fn distances_from(adj: &[Vec<(usize, i64)>], p: &[i64], source: usize) -> Vec<i64> {
+  let mut dist: Vec<Option<i64>> = vec![None; adj.len()];
+  let mut queue = BinaryHeap::new();
+  dist[source] = Some(0);
+  queue.push(Reverse((0, source)));
+  while let Some(Reverse((d, a))) = queue.pop() {
+    match dist[a] {
+      Some(da) if d > da => continue,
+      _ => ()
+    }
+    for &(b, w) in &adj[a] {
+      let db = d + w + p[a] - p[b];
+      match dist[b] {
+        Some(old) if db >= old => (),
+        _ => {
+          dist[b] = Some(db);
+          queue.push(Reverse((db, b)));
+        }
+      }
+    }
+  }
+  // The graph is strongly connected through the edges between consecutive prefix sums.
+  dist.into_iter().enumerate()
+    .map(|(b, d)| d.unwrap() - p[source] + p[b])
+    .collect()
+}
+
+/// For every `x[t]` with the bounds `[lo[t]..hi[t]]`, whether `0` and `1` belong to a solution, `None` if there is no solution.
+/// The potential `p` is updated to the one of the current graph if there is a solution.
+fn supports(lo: &[i64], hi: &[i64], k: usize, l: i64, u: i64, p: &mut Vec<i64>) -> Option<Vec<(bool, bool)>> {
+  let adj = prefix_sums_graph(lo, hi, k, l, u);
+  *p = potential(&adj, p)?;
+  // The distances from `S[t]`, kept for the next variable which needs the distances from `S[t+1]` and `S[t+2]`.
+  let mut from_prev: Option<(usize, Vec<i64>)> = None;
+  Some((0..lo.len()).map(|t| {
+    if lo[t] == hi[t] {
+      return (lo[t] == 0, hi[t] == 1);
+    }
+    let from_t = match from_prev.take() {
+      Some((s, dist)) if s == t => dist,
+      _ => distances_from(&adj, p, t)
+    };
+    let from_next = distances_from(&adj, p, t + 1);
+    let max = from_t[t + 1];
+    let min = -from_next[t];
+    from_prev = Some((t + 1, from_next));
+    (min <= 0, max >= 1)
+  }).collect())
+}
+
+impl<VStore, Domain, Bound> Sequence<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The bounds of every variable restricted to `[0..1]`, `None` if one of them has no Boolean value.
+  fn boolean_bounds(&self, store: &VStore) -> Option<(Vec<i64>, Vec<i64>)> {
+    let mut lo = vec![];
+    let mut hi = vec![];
+    for x in &self.vars {
+      let dom = x.read(store);
+      match (dom.contains(&Bound::zero()), dom.contains(&Bound::one())) {
+        (false, false) => return None,
+        (zero, one) => {
+          lo.push(if zero { 0 } else { 1 });
+          hi.push(if one { 1 } else { 0 });
+        }
+      }
+    }
+    Some((lo, hi))
+  }
+
+  fn windows(&self) -> usize {
+    (self.vars.len() + 1).saturating_sub(self.k)
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Sequence<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: a variable has no Boolean value or the difference constraints have no solution.
+    // True: the variables are Boolean and every window has at least `l` sure ones and at most `u` possible ones.
+    // Unknown: Everything else.
+    let (lo, hi) = match self.boolean_bounds(store) {
+      Some(bounds) => bounds,
+      None => return False
+    };
+    let (l, u) = (self.l as i64, self.u as i64);
+    if potential(&prefix_sums_graph(&lo, &hi, self.k, l, u), &self.potential).is_none() {
+      return False;
+    }
+    let boolean = self.vars.iter().all(|x| {
+      let dom = x.read(store);
+      dom.lower() >= Bound::zero() && dom.upper() <= Bound::one()
+    });
+    let entailed = (0..self.windows()).all(|i| {
+      let window = i..i + self.k;
+      lo[window.clone()].iter().sum::<i64>() >= l && hi[window].iter().sum::<i64>() <= u
+    });
+    if boolean && entailed { True } else { Unknown }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Sequence<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let (lo, hi) = match self.boolean_bounds(store) {
+      Some(bounds) => bounds,
+      None => return false
+    };
+    let supports = match supports(&lo, &hi, self.k, self.l as i64, self.u as i64, &mut self.potential) {
+      Some(s) => s,
+      None => return false
+    };
+    for (x, (zero, one)) in self.vars.iter_mut().zip(supports) {
+      let mut dom = x.read(store);
+      dom = if zero { dom.shrink_left(Bound::zero()) } else { dom.shrink_left(Bound::one()) };
+      dom = if one { dom.shrink_right(Bound::one()) } else { dom.shrink_right(Bound::zero()) };
+      if !x.update(store, dom) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Sequence<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+
+  fn sequence_test_one(test_num: u32, k: usize, l: usize, u: usize, doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_propagator_test(test_num, |vars| Sequence::new(vars, k, l, u),
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn sequence_test() {
+    let b = (0,1).to_interval();
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let dom0_3 = (0,3).to_interval();
+
+    sequence_test_one(1, 2, 1, 1, vec![b, b, b, b], Unknown, Unknown, vec![], true);
+    sequence_test_one(2, 2, 1, 1, vec![one, b, b, b], Unknown, True, vec![(1, Assignment), (2, Assignment), (3, Assignment)], true);
+    // The window `x[2..4]` needs `x[2] = 1`, so `x[1] = 0` and `x[0] = 1`, which no window taken separately forces.
+    sequence_test_one(3, 2, 1, 1, vec![b, b, b, zero, b, zero], Unknown, True,
+      vec![(0, Assignment), (1, Assignment), (2, Assignment), (4, Assignment)], true);
+    sequence_test_one(4, 3, 2, 2, vec![one, one, one, b], False, False, vec![], false);
+    // The first and last windows need their only unassigned variable.
+    sequence_test_one(5, 3, 1, 2, vec![zero, zero, b, zero, zero, b], Unknown, True, vec![(2, Assignment), (5, Assignment)], true);
+    sequence_test_one(6, 2, 0, 1, vec![one, zero, one, zero], True, True, vec![], true);
+    sequence_test_one(7, 2, 0, 1, vec![dom0_3, one], Unknown, True, vec![(0, Assignment)], true);
+    // No window of size `3` in two variables.
+    sequence_test_one(8, 3, 3, 3, vec![zero, dom0_3], Unknown, True, vec![(1, Bound)], true);
+  }
+
+  #[test]
+  fn supports_test() {
+    let fresh = |lo: &[i64], hi: &[i64], k, l, u| supports(lo, hi, k, l, u, &mut vec![0; lo.len() + 1]);
+    assert_eq!(fresh(&[0, 0, 0], &[1, 1, 1], 2, 1, 1), Some(vec![(true, true), (true, true), (true, true)]));
+    assert_eq!(fresh(&[1, 0, 0], &[1, 1, 1], 2, 1, 1), Some(vec![(false, true), (true, false), (false, true)]));
+    assert_eq!(fresh(&[0, 1, 1], &[1, 1, 1], 2, 0, 1), None);
+    assert_eq!(fresh(&[0, 0], &[1, 1], 3, 3, 3), Some(vec![(true, true), (true, true)]));
+  }
+
+  #[test]
+  fn kept_potential_test() {
+    let mut p = vec![0; 5];
+    assert_eq!(supports(&[1, 0, 0, 0], &[1, 1, 1, 1], 2, 1, 1, &mut p),
+      Some(vec![(false, true), (true, false), (false, true), (true, false)]));
+    // The potential is unchanged by a failure.
+    let before_failure = p.clone();
+    assert_eq!(supports(&[1, 1, 0, 0], &[1, 1, 1, 1], 2, 1, 1, &mut p), None);
+    assert_eq!(p, before_failure);
+    // The potential of the narrowed graph is still valid once the domains are restored.
+    assert_eq!(supports(&[0, 0, 0, 0], &[1, 1, 1, 1], 2, 1, 1, &mut p), Some(vec![(true, true); 4]));
+    assert_eq!(supports(&[0, 0, 0, 0], &[1, 1, 1, 0], 2, 1, 1, &mut p),
+      Some(vec![(false, true), (true, false), (false, true), (true, false)]));
+  }
+}
@@ -0,0 +1,550 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The constraint `sort(x, y)` ensures that `y` is the array `x` sorted in increasing order.
+//!
+//! The filtering follows `Faster Algorithms for Bound-Consistency of the Sortedness and the Alldifferent Constraint`, Mehlhorn and Thiel, 2000.
+//!  * The bounds of `y` are normalized to be increasing and `y[j]` is restricted to the `j`-th smallest lower and upper bounds of `x`.
+//!  * Every `y[j]`, in increasing order, is matched to the variable of `x` with the smallest upper bound among the ones that can take a value of `y[j]`.
+//!    Since the bounds of `y` are increasing, there is a solution if and only if this greedy matching succeeds.
+//!  * In the graph where `y[j]` has an edge to every `y[k]` intersecting the variable matched to `y[j]`, these `y[k]` form a range of indices.
+//!    The strongly connected components are computed on these ranges, and every variable of `x` is narrowed to the values of the `y[k]` in the component of its `y[j]`.
+//!  * The bounds of `y` are narrowed to the sorted bounds of `x`.
+//!
+//! The bounds of `x` are consistent, but not always the ones of `y`.
+//! For example, with `x = ([3..6], [4..6], [5..5])` and `y = ([1..4], [4..7], [5..6])`, `y[1]` is narrowed to `[4..6]` although `6` would leave `5` to `y[0]`.
+//! Every step is in `O(n log n)`, and so is a propagation.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use std::cmp::{min, max, Reverse};
+use std::collections::BinaryHeap;
+
+#[derive(Debug)]
+pub struct Sort<VStore>
+{
+  x: Vec<Var<VStore>>,
+  y: Vec<Var<VStore>>
+}
+
+impl<VStore> Sort<VStore>
+{
+  /// Precondition: `x.len() == y.len()`.
+  pub fn new(x: Vec<Var<VStore>>, y: Vec<Var<VStore>>) -> Self {
+    assert_eq!(x.len(), y.len(), "The arrays of `Sort` must have the same length.");
+    Sort {
+      x: x,
+      y: y
+    }
+  }
+}
+
+impl<VStore> Clone for Sort<VStore> where
+  VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Sort {
+      x: self.x.iter().map(|v| v.bclone()).collect(),
+      y: self.y.iter().map(|v| v.bclone()).collect()
+    }
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Sort<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("sort(");
+    model.display_global("x", &self.x);
+    print!(", ");
+    model.display_global("y", &self.y);
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Sort<VStore> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  /// That `y` is not a permutation of `x` depends on the values taken by the variables, it cannot be stated with a fixed set of propagators, so the negation is the check-only `Negation`.
+  fn not(&self) -> Formula<VStore> {
+    Box::new(Negation::new(Box::new(self.clone())))
+  }
+}
+
+/// The bounds of an array of variables.
+type Intervals<Bound> = Vec<(Bound, Bound)>;
+
+/// Makes the lower and upper bounds of `y` increasing, `false` if one of them becomes empty.
+fn normalize<Bound: IntBound>(y: &mut [(Bound, Bound)]) -> bool {
+  let n = y.len();
+  for j in 1..n {
+    if y[j].0 < y[j-1].0 {
+      y[j].0 = y[j-1].0.clone();
+    }
+  }
+  for j in (1..n).rev() {
+    if y[j-1].1 > y[j].1 {
+      y[j-1].1 = y[j].1.clone();
+    }
+  }
+  y.iter().all(|b| b.0 <= b.1)
+}
+
+/// The bounds of `y` normalized and restricted by the sorted bounds of `x`, `None` if one of them is empty.
+fn sorted_bounds<Bound: IntBound>(x: &[(Bound, Bound)], y: &[(Bound, Bound)]) -> Option<Vec<(Bound, Bound)>> {
+  let mut lower: Vec<_> = x.iter().map(|b| b.0.clone()).collect();
+  let mut upper: Vec<_> = x.iter().map(|b| b.1.clone()).collect();
+  lower.sort();
+  upper.sort();
+  let mut y: Vec<_> = y.iter().zip(lower.into_iter().zip(upper))
+    .map(|(b, (lb, ub))| (max(b.0.clone(), lb), min(b.1.clone(), ub)))
+    .collect();
+  if normalize(&mut y) { Some(y) } else { None }
+}
+
+/// `matching[j]` is the variable of `x` matched to `y[j]`, `None` if there is no matching.
+/// Precondition: the lower and upper bounds of `y` are increasing.
+fn greedy_matching<Bound: IntBound>(x: &[(Bound, Bound)], y: &[(Bound, Bound)]) -> Option<Vec<usize>> {
+  let n = x.len();
+  let mut by_lower: Vec<usize> = (0..n).collect();
+  by_lower.sort_by(|&i, &j| x[i].0.cmp(&x[j].0));
+  let mut next = 0;
+  let mut available = BinaryHeap::new();
+  let mut matching = vec![];
+  for yj in y {
+    while next < n && x[by_lower[next]].0 <= yj.1 {
+      let i = by_lower[next];
+      available.push(Reverse((x[i].1.clone(), i)));
+      next += 1;
+    }
+    match available.pop() {
+      Some(Reverse((ub, i))) if ub >= yj.0 => matching.push(i),
+      _ => return None
+    }
+  }
+  Some(matching)
+}
+
+/// The nodes `0..n` not visited yet, `find(i)` is the first of them from `i`, or `n` if there is none.
+struct Unvisited
+{
+  next: Vec<usize>
+}
+
+impl Unvisited
+{
+  fn new(n: usize) -> Self {
+    Unvisited { next: (0..n + 1).collect() }
+  }
+
+  fn find(&mut self, i: usize) -> usize {
+    let mut root = i;
+    while self.next[root] != root {
+      root = self.next[root];
+    }
+    let mut i = i;
+    while self.next[i] != root {
+      let next = self.next[i];
+      self.next[i] = root;
+      i = next;
+    }
+    root
+  }
+
+  fn visit(&mut self, i: usize) {
+    self.next[i] = i + 1;
+  }
+}
+
+/// Segment tree over `0..n` where every range `ranges[j]` is stored in the nodes covering it, to find the ranges containing a node.
+struct Stabbing
+{
+  n: usize,
+  nodes: Vec<Vec<usize>>
+}
+
+impl Stabbing
+{
+  fn new(ranges: &[(usize, usize)]) -> Self {
+    let n = ranges.len();
+    let mut stabbing = Stabbing { n: n, nodes: vec![vec![]; 4 * n] };
+    for (j, &(l, r)) in ranges.iter().enumerate() {
+      stabbing.insert(1, 0, n - 1, l, r, j);
+    }
+    stabbing
+  }
+
+  fn insert(&mut self, node: usize, lo: usize, hi: usize, l: usize, r: usize, j: usize) {
+    if l <= lo && hi <= r {
+      self.nodes[node].push(j);
+    }
+    else {
+      let mid = (lo + hi) / 2;
+      if l <= mid { self.insert(2 * node, lo, mid, l, r, j); }
+      if r > mid { self.insert(2 * node + 1, mid + 1, hi, l, r, j); }
+    }
+  }
+
+  /// A range containing `k` whose index is not `visited`, the visited ranges met are removed.
+  fn pop(&mut self, k: usize, visited: &[bool]) -> Option<usize> {
+    let (mut node, mut lo, mut hi) = (1, 0, self.n - 1);
+    loop {
+      while let Some(j) = self.nodes[node].pop() {
+        if !visited[j] {
+          return Some(j);
+        }
+      }
+      if lo == hi {
+        return None;
+      }
+      let mid = (lo + hi) / 2;
+      node *= 2;
+      if k <= mid { hi = mid; }
+      else { node += 1; lo = mid + 1; }
+    }
+  }
+}
+
+/// Kosaraju's algorithm on the graph where the node `j` has an edge to every node of `ranges[j] = [l..r]`, returns the component of every node.
+/// The successors are found with `Unvisited` and the predecessors with `Stabbing`, so it is in `O(n log n)`.
+fn range_components(ranges: &[(usize, usize)]) -> Vec<usize> {
+  let n = ranges.len();
+  let mut unvisited = Unvisited::new(n);
+  let mut order = vec![];
+  for root in 0..n {
+    if unvisited.find(root) != root {
+      continue;
+    }
+    unvisited.visit(root);
+    let mut frames = vec![root];
+    while let Some(&u) = frames.last() {
+      let w = unvisited.find(ranges[u].0);
+      if w <= ranges[u].1 {
+        unvisited.visit(w);
+        frames.push(w);
+      }
+      else {
+        frames.pop();
+        order.push(u);
+      }
+    }
+  }
+  let mut stabbing = Stabbing::new(ranges);
+  let mut visited = vec![false; n];
+  let mut component = vec![0; n];
+  let mut next_component = 0;
+  for &root in order.iter().rev() {
+    if visited[root] {
+      continue;
+    }
+    visited[root] = true;
+    let mut frames = vec![root];
+    while let Some(&u) = frames.last() {
+      component[u] = next_component;
+      match stabbing.pop(u, &visited) {
+        Some(w) => {
+          visited[w] = true;
+          frames.push(w);
+        }
+        None => { frames.pop(); }
+      }
+    }
+    next_component += 1;
+  }
+  component
+}
+
+impl<VStore, Domain, Bound> Sort<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn bounds(vars: &[Var<VStore>], store: &VStore) -> Vec<(Bound, Bound)> {
+    vars.iter()
+      .map(|v| v.read(store))
+      .map(|d| (d.lower(), d.upper()))
+      .collect()
+  }
+
+  /// The bounds of `x` and the normalized bounds of `y`, `None` if there is no solution.
+  fn all_bounds(&self, store: &VStore) -> Option<(Intervals<Bound>, Intervals<Bound>)> {
+    let x = Self::bounds(&self.x, store);
+    let y = sorted_bounds(&x, &Self::bounds(&self.y, store))?;
+    Some((x, y))
+  }
+
+  /// The bounds of every `x[i]` narrowed to the values of the `y[k]` it can be matched to.
+  /// In the graph where `y[j]` has an edge to every `y[k]` intersecting the variable matched to `y[j]`, these `y[k]` form a range since the bounds of `y` are increasing, and `x[i]` can be matched to the ones in the strongly connected component of `y[j]`.
+  fn matchable_bounds(x: &[(Bound, Bound)], y: &[(Bound, Bound)], matching: &[usize]) -> Vec<(Bound, Bound)> {
+    let n = x.len();
+    let ranges: Vec<(usize, usize)> = matching.iter()
+      .map(|&i| (y.partition_point(|yk| yk.1 < x[i].0), y.partition_point(|yk| yk.0 <= x[i].1) - 1))
+      .collect();
+    let component = range_components(&ranges);
+    let mut members = vec![vec![]; n];
+    for (k, &c) in component.iter().enumerate() {
+      members[c].push(k);
+    }
+    let mut bounds = x.to_vec();
+    for (j, &i) in matching.iter().enumerate() {
+      let (l, r) = ranges[j];
+      let members = &members[component[j]];
+      let first = members[members.partition_point(|&k| k < l)];
+      let last = members[members.partition_point(|&k| k <= r) - 1];
+      bounds[i] = (max(x[i].0.clone(), y[first].0.clone()), min(x[i].1.clone(), y[last].1.clone()));
+    }
+    bounds
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Sort<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // False: the normalized bounds of `y` are empty or there is no matching between `x` and `y`.
+    // True: all the variables are assigned, `y` is then necessarily `x` sorted.
+    // Unknown: Everything else.
+    let (x, y) = match self.all_bounds(store) {
+      Some(bounds) => bounds,
+      None => return False
+    };
+    if greedy_matching(&x, &y).is_none() {
+      False
+    }
+    else if self.x.iter().chain(self.y.iter()).all(|v| v.read(store).is_singleton()) {
+      True
+    }
+    else {
+      Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Sort<VStore> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let (x, y) = match self.all_bounds(store) {
+      Some(bounds) => bounds,
+      None => return false
+    };
+    let matching = match greedy_matching(&x, &y) {
+      Some(matching) => matching,
+      None => return false
+    };
+    let x = Self::matchable_bounds(&x, &y, &matching);
+    let y = match sorted_bounds(&x, &y) {
+      Some(bounds) => bounds,
+      None => return false
+    };
+    for (v, (lb, ub)) in self.x.iter_mut().chain(self.y.iter_mut()).zip(x.into_iter().chain(y)) {
+      let dom = v.read(store).shrink_left(lb).shrink_right(ub);
+      if !v.update(store, dom) {
+        return false;
+      }
+    }
+    true
+  }
+}
+
+impl<VStore> PropagatorDependencies<FDEvent> for Sort<VStore>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.x.iter()
+      .chain(self.y.iter())
+      .flat_map(|v| v.dependencies(FDEvent::Bound))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval::*;
+  use propagators::test::*;
+  use variable::VStoreFD;
+
+  /// The variables are numbered `x[0], ..., x[n-1], y[0], ..., y[n-1]`.
+  fn sort_test_one(test_num: u32, doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_propagator_test(test_num, |mut x: Vec<FDVar>| {
+        let n = x.len() / 2;
+        let y = x.split_off(n);
+        Sort::new(x, y)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn sort_test() {
+    let dom0_3 = (0,3).to_interval();
+    let dom0_9 = (0,9).to_interval();
+    let dom0_2 = (0,2).to_interval();
+    let dom0_4 = (0,4).to_interval();
+    let dom1_4 = (1,4).to_interval();
+    let dom2_3 = (2,3).to_interval();
+    let dom1_2 = (1,2).to_interval();
+    let dom4_5 = (4,5).to_interval();
+    let zero = (0,0).to_interval();
+    let one = (1,1).to_interval();
+    let two = (2,2).to_interval();
+    let three = (3,3).to_interval();
+    let four = (4,4).to_interval();
+
+    sort_test_one(1, vec![three, one, two, dom0_9, dom0_9, dom0_9], Unknown, True,
+      vec![(3, Assignment), (4, Assignment), (5, Assignment)], true);
+    sort_test_one(2, vec![three, one, two, one, two, three], True, True, vec![], true);
+    sort_test_one(3, vec![three, one, two, one, three, two], False, False, vec![], false);
+    // `x[0]` is the smallest value, so it is `y[0]`.
+    sort_test_one(4, vec![zero, dom0_9, dom0_3, dom0_9], Unknown, Unknown, vec![(2, Assignment)], true);
+    // `x[0]` and `x[1]` take the values of `y[0]` and `y[1]`, so `x[2]` is matched to `y[2]`.
+    sort_test_one(5, vec![dom1_2, dom1_2, dom0_9, dom0_3, dom0_3, dom4_5], Unknown, Unknown,
+      vec![(2, Bound), (3, Bound), (4, Bound)], true);
+    sort_test_one(6, vec![dom4_5, dom4_5, dom0_9, dom0_3], False, False, vec![], false);
+    // `x[2]` cannot be `y[2] = 4` so it is `2`, whereas `x[0]` can still be `0` or `4`.
+    // Only `2` is supported for `y[1]`, but the second smallest lower bound of `x` is `1`.
+    sort_test_one(7, vec![dom0_4, dom1_4, dom2_3, dom0_4, dom0_2, four], Unknown, Unknown,
+      vec![(2, Assignment), (3, Bound), (4, Bound)], true);
+  }
+
+  #[test]
+  fn greedy_matching_test() {
+    let x = vec![(0, 4), (1, 1), (5, 5)];
+    let y = vec![(0, 1), (3, 4), (4, 5)];
+    assert_eq!(greedy_matching(&x, &y), Some(vec![1, 0, 2]));
+    let x = vec![(0, 1), (0, 1), (0, 1)];
+    let y = vec![(0, 1), (0, 1), (0, 1)];
+    assert_eq!(greedy_matching(&x, &y), Some(vec![0, 1, 2]));
+    let x = vec![(0, 1), (5, 5)];
+    let y = vec![(0, 1), (2, 3)];
+    assert_eq!(greedy_matching(&x, &y), None);
+  }
+
+  /// `true` if there is still a matching when `y[j]` is restricted to `[lb..ub]`.
+  fn matching_with<Bound: IntBound>(x: &[(Bound, Bound)], y: &[(Bound, Bound)], j: usize, lb: Bound, ub: Bound) -> bool {
+    let mut y = y.to_vec();
+    y[j] = (max(y[j].0.clone(), lb), min(y[j].1.clone(), ub));
+    normalize(&mut y) && greedy_matching(x, &y).is_some()
+  }
+
+  /// The first value of `candidates` satisfying `pred`, which must hold for all the values after it.
+  fn first_satisfying<Bound, F>(candidates: &[Bound], pred: F) -> Bound where
+   Bound: IntBound,
+   F: Fn(Bound) -> bool
+  {
+    let (mut lo, mut hi) = (0, candidates.len() - 1);
+    while lo < hi {
+      let mid = (lo + hi) / 2;
+      if pred(candidates[mid].clone()) { hi = mid; }
+      else { lo = mid + 1; }
+    }
+    candidates[lo].clone()
+  }
+
+  /// The smallest and largest values of every `y[j]` for which there is a matching.
+  /// Precondition: there is a matching between `x` and `y`.
+  fn supported_bounds<Bound: IntBound>(x: &[(Bound, Bound)], y: &[(Bound, Bound)]) -> Vec<(Bound, Bound)> {
+    let sorted = |mut values: Vec<Bound>| { values.sort(); values.dedup(); values };
+    let lower = sorted(x.iter().chain(y.iter()).map(|b| b.0.clone()).collect());
+    let upper = sorted(x.iter().chain(y.iter()).map(|b| b.1.clone()).collect());
+    (0..y.len()).map(|j| {
+      let (ref y_lb, ref y_ub) = y[j];
+      let lb_candidates: Vec<_> = lower.iter().filter(|&v| v >= y_lb && v < y_ub).cloned()
+        .chain(Some(y_ub.clone())).collect();
+      let mut ub_candidates: Vec<_> = upper.iter().filter(|&v| v > y_lb && v <= y_ub).cloned()
+        .chain(Some(y_lb.clone())).collect();
+      ub_candidates.sort_by(|a, b| b.cmp(a));
+      let lb = first_satisfying(&lb_candidates, |v| matching_with(x, y, j, y_lb.clone(), v));
+      let ub = first_satisfying(&ub_candidates, |v| matching_with(x, y, j, v, y_ub.clone()));
+      (lb, ub)
+    }).collect()
+  }
+
+  /// The smallest and largest values of every `x[i]` for which there is a matching, every value is tried.
+  fn supported_x_bounds(x: &[(i32, i32)], y: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    (0..x.len()).map(|i| {
+      let supported: Vec<i32> = (x[i].0..x[i].1 + 1).filter(|&v| {
+        let mut x = x.to_vec();
+        x[i] = (v, v);
+        sorted_bounds(&x, y).and_then(|y| greedy_matching(&x, &y)).is_some()
+      }).collect();
+      (supported[0], supported[supported.len() - 1])
+    }).collect()
+  }
+
+  /// The greedy matching with a binary search on the bounds of `y`, and every value of `x`, is the oracle of the filtering.
+  /// The bounds of `x` are exact, whereas the bounds of `y` can be larger than the supported ones.
+  #[test]
+  fn oracle_test() {
+    let mut seed = 0x2545F4914F6CDD1D;
+    let mut random_bounds = |n: usize| -> Vec<(i32, i32)> {
+      (0..n).map(|_| {
+        let a = (next_random(&mut seed) % 8) as i32;
+        let b = (next_random(&mut seed) % 8) as i32;
+        (min(a, b), max(a, b))
+      }).collect()
+    };
+    for n in 1..6 {
+      for _ in 0..500 {
+        let x = random_bounds(n);
+        let y = random_bounds(n);
+        let y = match sorted_bounds(&x, &y) {
+          Some(y) => y,
+          None => continue
+        };
+        let matching = match greedy_matching(&x, &y) {
+          Some(matching) => matching,
+          None => continue
+        };
+        let x_bounds = Sort::<VStoreFD>::matchable_bounds(&x, &y, &matching);
+        assert_eq!(x_bounds, supported_x_bounds(&x, &y), "x = {:?}, y = {:?}", x, y);
+        let y_bounds = sorted_bounds(&x_bounds, &y).unwrap();
+        for (b, supported) in y_bounds.iter().zip(supported_bounds(&x, &y)) {
+          assert!(b.0 <= supported.0 && supported.1 <= b.1, "x = {:?}, y = {:?}", x, y);
+        }
+      }
+    }
+    // `y[1]` cannot be `6` since `y[2]` would also be `6`, leaving `5` to `y[0]`.
+    let x = vec![(3, 6), (4, 6), (5, 5)];
+    let y = sorted_bounds(&x, &[(1, 4), (4, 7), (5, 6)]).unwrap();
+    assert_eq!(supported_bounds(&x, &y)[1], (4, 5));
+    let matching = greedy_matching(&x, &y).unwrap();
+    assert_eq!(sorted_bounds(&Sort::<VStoreFD>::matchable_bounds(&x, &y, &matching), &y).unwrap()[1], (4, 6));
+  }
+
+  #[test]
+  fn range_components_test() {
+    // `1` only reaches itself.
+    assert_eq!(range_components(&[(0, 2), (1, 1), (0, 2)]), vec![0, 1, 0]);
+    assert_eq!(range_components(&[(0, 1), (1, 2), (2, 2)]), vec![0, 1, 2]);
+    assert_eq!(range_components(&[(0, 1), (0, 2), (1, 2)]), vec![0, 0, 0]);
+  }
+}
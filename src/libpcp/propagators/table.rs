@@ -0,0 +1,529 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The extensional constraint `Table` restricts a vector of variables to a list of allowed tuples, or forbids a list of tuples.
+//!
+//! It is the compact-table algorithm described in `Compact-Table: Efficiently Filtering Table Constraints with Reversible Sparse Bit-Sets`, Demeulenaere et al., 2016, and its extension to negative tables in `Extending Compact-Table to Negative and Short Tables`, Verhaeghe et al., 2017.
+//! The set of the tuples that are valid (every value of the tuple is in the domain of its variable) is represented by a reversible sparse bitset, which is the internal state of the propagator saved and restored with the constraints store.
+
+use kernel::*;
+use trilean::SKleene;
+use trilean::SKleene::*;
+use model::*;
+use logic::*;
+use propagation::*;
+use propagation::events::*;
+use gcollections::*;
+use concept::*;
+use propagators::{restrict_to_values, cardinality_upto};
+use std::rc::Rc;
+
+/// Bitset of fixed size where the indexes of the non-zero words are kept in the prefix `index[0..limit]`.
+/// Words never become non-zero again during the propagation, thus only the words and `limit` need to be restored on backtracking.
+#[derive(Clone, Debug)]
+struct SparseBitSet
+{
+  words: Vec<u64>,
+  index: Vec<usize>,
+  limit: usize,
+  mask: Vec<u64>
+}
+
+impl SparseBitSet
+{
+  /// The bitset `{0..n-1}`.
+  fn full(n: usize) -> Self {
+    let num_words = n.div_ceil(64);
+    let mut words = vec![!0u64; num_words];
+    if n & 63 != 0 {
+      words[num_words - 1] = (1u64 << (n & 63)) - 1;
+    }
+    SparseBitSet {
+      words: words,
+      index: (0..num_words).collect(),
+      limit: num_words,
+      mask: vec![0; num_words]
+    }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.limit == 0
+  }
+
+  fn clear_mask(&mut self) {
+    for i in 0..self.limit {
+      self.mask[self.index[i]] = 0;
+    }
+  }
+
+  fn add_to_mask(&mut self, m: &[u64]) {
+    for i in 0..self.limit {
+      let w = self.index[i];
+      self.mask[w] |= m[w];
+    }
+  }
+
+  fn reverse_mask(&mut self) {
+    for i in 0..self.limit {
+      let w = self.index[i];
+      self.mask[w] = !self.mask[w];
+    }
+  }
+
+  fn intersect_with_mask(&mut self) {
+    let mut i = self.limit;
+    while i > 0 {
+      i -= 1;
+      let w = self.index[i];
+      self.words[w] &= self.mask[w];
+      if self.words[w] == 0 {
+        self.index[i] = self.index[self.limit - 1];
+        self.index[self.limit - 1] = w;
+        self.limit -= 1;
+      }
+    }
+  }
+
+  /// Index of a word having a common bit with `m`.
+  fn intersect_index(&self, m: &[u64]) -> Option<usize> {
+    self.index[0..self.limit].iter()
+      .find(|&&w| self.words[w] & m[w] != 0)
+      .cloned()
+  }
+
+  fn count(&self) -> u64 {
+    self.index[0..self.limit].iter()
+      .map(|&w| self.words[w].count_ones() as u64)
+      .sum()
+  }
+
+  /// Number of bits in common with `m`.
+  fn count_and(&self, m: &[u64]) -> u64 {
+    self.index[0..self.limit].iter()
+      .map(|&w| (self.words[w] & m[w]).count_ones() as u64)
+      .sum()
+  }
+
+  fn ones(&self) -> Vec<usize> {
+    let mut ones = vec![];
+    for &w in &self.index[0..self.limit] {
+      let mut word = self.words[w];
+      while word != 0 {
+        ones.push(w * 64 + word.trailing_zeros() as usize);
+        word &= word - 1;
+      }
+    }
+    ones
+  }
+}
+
+/// The values of a variable appearing in the table, sorted, and the bitset of the tuples supporting each of them.
+#[derive(Debug)]
+struct Supports<Bound>
+{
+  values: Vec<Bound>,
+  masks: Vec<Vec<u64>>
+}
+
+impl<Bound> Supports<Bound> where
+  Bound: IntBound
+{
+  fn new(tuples: &[Vec<Bound>], x: usize) -> Self {
+    let mut values: Vec<Bound> = tuples.iter().map(|t| t[x].clone()).collect();
+    values.sort();
+    values.dedup();
+    let num_words = tuples.len().div_ceil(64);
+    let mut masks = vec![vec![0u64; num_words]; values.len()];
+    for (t, tuple) in tuples.iter().enumerate() {
+      let k = values.binary_search(&tuple[x]).unwrap();
+      masks[k][t / 64] |= 1u64 << (t % 64);
+    }
+    Supports {
+      values: values,
+      masks: masks
+    }
+  }
+}
+
+/// Part of the propagator restored on backtracking: the valid tuples and the domains of the variables when they were last computed.
+#[derive(Clone, Debug)]
+struct TableState<Domain>
+{
+  current: SparseBitSet,
+  last: Option<Vec<Domain>>
+}
+
+/// `(x[0], ..., x[n-1])` is one of the allowed tuples, or none of the forbidden tuples.
+#[derive(Debug)]
+pub struct Table<VStore, Domain, Bound>
+{
+  vars: Vec<Var<VStore>>,
+  tuples: Rc<Vec<Vec<Bound>>>,
+  supports: Rc<Vec<Supports<Bound>>>,
+  positive: bool,
+  state: TableState<Domain>,
+  residues: Vec<Vec<usize>>
+}
+
+impl<VStore, Domain, Bound> Table<VStore, Domain, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  /// The tuples of `vars` must be in `tuples`.
+  /// Precondition: `vars.len() > 0` and every tuple has the length of `vars`.
+  pub fn new(vars: Vec<Var<VStore>>, tuples: Vec<Vec<Bound>>) -> Self {
+    Table::with_sign(vars, tuples, true)
+  }
+
+  /// The tuples of `vars` must not be in `tuples`.
+  /// Precondition: `vars.len() > 0` and every tuple has the length of `vars`.
+  pub fn forbidden(vars: Vec<Var<VStore>>, tuples: Vec<Vec<Bound>>) -> Self {
+    Table::with_sign(vars, tuples, false)
+  }
+
+  fn with_sign(vars: Vec<Var<VStore>>, mut tuples: Vec<Vec<Bound>>, positive: bool) -> Self {
+    assert!(!vars.is_empty(), "Variable array in `Table` must be non-empty.");
+    assert!(tuples.iter().all(|t| t.len() == vars.len()),
+      "Every tuple of `Table` must have one value per variable.");
+    tuples.sort();
+    tuples.dedup();
+    let supports: Vec<_> = (0..vars.len()).map(|x| Supports::new(&tuples, x)).collect();
+    let residues = supports.iter().map(|s| vec![0; s.values.len()]).collect();
+    Table {
+      state: TableState {
+        current: SparseBitSet::full(tuples.len()),
+        last: None
+      },
+      vars: vars,
+      tuples: Rc::new(tuples),
+      supports: Rc::new(supports),
+      positive: positive,
+      residues: residues
+    }
+  }
+
+  /// Removes the tuples that are not valid anymore from the current table.
+  /// For each variable, the tuples are either removed by the values deleted since the last update, or kept by the remaining values, depending on which set is the smallest.
+  fn update_table(&mut self, doms: &[Domain]) {
+    for (x, dom) in doms.iter().enumerate() {
+      if self.state.current.is_empty() {
+        return;
+      }
+      let supports = &self.supports[x];
+      let current = &mut self.state.current;
+      let kept: Vec<usize> = (0..supports.values.len())
+        .filter(|&k| dom.contains(&supports.values[k]))
+        .collect();
+      current.clear_mask();
+      match self.state.last {
+        Some(ref last) if last[x].is_subset(dom) => continue,
+        Some(ref last) => {
+          let removed: Vec<usize> = (0..supports.values.len())
+            .filter(|&k| last[x].contains(&supports.values[k]) && !dom.contains(&supports.values[k]))
+            .collect();
+          if removed.len() < kept.len() {
+            for k in removed {
+              current.add_to_mask(&supports.masks[k]);
+            }
+            current.reverse_mask();
+          }
+          else {
+            for &k in &kept {
+              current.add_to_mask(&supports.masks[k]);
+            }
+          }
+        }
+        None => {
+          for &k in &kept {
+            current.add_to_mask(&supports.masks[k]);
+          }
+        }
+      }
+      current.intersect_with_mask();
+    }
+  }
+
+  /// Keeps the values of the variables supported by a valid tuple, the values not in the table are removed.
+  fn filter_positive(&mut self, store: &mut VStore, doms: &mut [Domain]) -> bool {
+    for (x, dom) in doms.iter_mut().enumerate() {
+      let supports = &self.supports[x];
+      let mut supported = vec![];
+      for k in 0..supports.values.len() {
+        if !dom.contains(&supports.values[k]) {
+          continue;
+        }
+        let mask = &supports.masks[k];
+        let r = self.residues[x][k];
+        if self.state.current.words[r] & mask[r] != 0 {
+          supported.push(supports.values[k].clone());
+        }
+        else if let Some(r) = self.state.current.intersect_index(mask) {
+          self.residues[x][k] = r;
+          supported.push(supports.values[k].clone());
+        }
+      }
+      *dom = restrict_to_values(dom.clone(), &supported);
+      if !self.vars[x].update(store, dom.clone()) {
+        return false;
+      }
+    }
+    true
+  }
+
+  /// Removes a value `a` from a variable `x` when every combination of values of the other variables with `x = a` is forbidden by a valid tuple.
+  /// The domains of the other variables are considered as before the filtering, hence it may require several propagations to reach a fixed point.
+  fn filter_negative(&mut self, store: &mut VStore, doms: &[Domain]) -> bool {
+    let valid = self.state.current.count();
+    let sizes: Vec<u64> = doms.iter().map(|d| cardinality_upto(d, valid + 1)).collect();
+    for (x, dom) in doms.iter().enumerate() {
+      let others = (0..self.vars.len())
+        .filter(|&y| y != x)
+        .fold(1u64, |p, y| p.saturating_mul(sizes[y]));
+      if others > valid {
+        continue;
+      }
+      let supports = &self.supports[x];
+      let mut dom = dom.clone();
+      for k in 0..supports.values.len() {
+        if dom.contains(&supports.values[k])
+         && self.state.current.count_and(&supports.masks[k]) == others {
+          dom = dom.difference(&supports.values[k]);
+        }
+      }
+      if !self.vars[x].update(store, dom) {
+        return false;
+      }
+    }
+    true
+  }
+
+  /// Number of tuples in the current table having all their values in the domains of the variables.
+  fn count_valid(&self, store: &VStore) -> u64 {
+    let doms: Vec<Domain> = self.vars.iter().map(|v| v.read(store)).collect();
+    self.state.current.ones().into_iter()
+      .filter(|&t| self.tuples[t].iter().zip(doms.iter()).all(|(v, d)| d.contains(v)))
+      .count() as u64
+  }
+
+  /// Number of tuples in the cartesian product of the domains, at most `cap + 1`.
+  fn product_upto(&self, store: &VStore, cap: u64) -> u64 {
+    self.vars.iter()
+      .map(|v| cardinality_upto(&v.read(store), cap + 1))
+      .fold(1u64, |p, s| p.saturating_mul(s))
+      .min(cap + 1)
+  }
+}
+
+impl<VStore, Domain, Bound> Clone for Table<VStore, Domain, Bound> where
+  VStore: Collection,
+  Domain: Clone
+{
+  fn clone(&self) -> Self {
+    Table {
+      vars: self.vars.iter().map(|v| v.bclone()).collect(),
+      tuples: self.tuples.clone(),
+      supports: self.supports.clone(),
+      positive: self.positive,
+      state: self.state.clone(),
+      residues: self.residues.clone()
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> DisplayStateful<Model> for Table<VStore, Domain, Bound> where
+  Bound: IntBound
+{
+  fn display(&self, model: &Model) {
+    model.display_global("table", &self.vars);
+    let op = if self.positive { "in" } else { "not in" };
+    print!(" {} {:?}", op, self.tuples);
+  }
+}
+
+impl<VStore, Domain, Bound> NotFormula<VStore> for Table<VStore, Domain, Bound> where
+  VStore: VStoreConcept<Item=Domain> + 'static,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound + 'static,
+{
+  fn not(&self) -> Formula<VStore> {
+    let vars = self.vars.iter().map(|v| v.bclone()).collect();
+    Box::new(Table::with_sign(vars, (*self.tuples).clone(), !self.positive))
+  }
+}
+
+impl<VStore, Domain, Bound> Subsumption<VStore> for Table<VStore, Domain, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound>,
+  Bound: IntBound
+{
+  fn is_subsumed(&self, store: &VStore) -> SKleene {
+    // The current table contains every valid tuple, and the tuples are distinct.
+    // Allowed tuples: False if no tuple is valid, True if every combination of values is a valid tuple.
+    // Forbidden tuples: the other way around.
+    let valid = self.count_valid(store);
+    let all = self.product_upto(store, valid);
+    match (valid == 0, valid == all) {
+      (true, _) if self.positive => False,
+      (true, _) => True,
+      (_, true) if self.positive => True,
+      (_, true) => False,
+      _ => Unknown
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> Propagator<VStore> for Table<VStore, Domain, Bound> where
+  VStore: Collection<Item=Domain>,
+  Domain: IntDomain<Item=Bound> + 'static,
+  Bound: IntBound
+{
+  fn propagate(&mut self, store: &mut VStore) -> bool {
+    let mut doms: Vec<Domain> = self.vars.iter().map(|v| v.read(store)).collect();
+    self.update_table(&doms);
+    if self.positive {
+      if self.state.current.is_empty() || !self.filter_positive(store, &mut doms) {
+        return false;
+      }
+    }
+    else if !self.filter_negative(store, &doms) {
+      return false;
+    }
+    // The tuples of the values removed by the negative filtering are not removed from the current table yet.
+    self.state.last = Some(doms);
+    true
+  }
+
+  fn save_state(&self) -> Option<PropagatorState> {
+    Some(Box::new(self.state.clone()))
+  }
+
+  fn restore_state(&mut self, state: PropagatorState) {
+    if let Ok(state) = state.downcast::<TableState<Domain>>() {
+      self.state = *state;
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> PropagatorDependencies<FDEvent> for Table<VStore, Domain, Bound>
+{
+  fn dependencies(&self) -> Vec<(usize, FDEvent)> {
+    let mut deps: Vec<_> = self.vars.iter()
+      .flat_map(|v| v.dependencies(FDEvent::Inner))
+      .collect();
+    deps.sort();
+    deps.dedup();
+    deps
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use propagation::events::FDEvent::*;
+  use interval::interval_set::*;
+  use propagators::test::*;
+  use search::*;
+  use gcollections::ops::*;
+
+  fn tuples() -> Vec<Vec<i32>> {
+    vec![
+      vec![1, 1, 2],
+      vec![1, 2, 3],
+      vec![2, 2, 1],
+      vec![3, 1, 3],
+      vec![3, 3, 3]
+    ]
+  }
+
+  fn table_test_one(test_num: u32, positive: bool, doms: Vec<IntervalSet<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_set_propagator_test(test_num,
+      |vars| if positive { Table::new(vars, tuples()) } else { Table::forbidden(vars, tuples()) },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn table_test() {
+    let dom1_3 = (1,3).to_interval_set();
+    let dom1_1 = (1,1).to_interval_set();
+    let dom2_2 = (2,2).to_interval_set();
+    let dom3_3 = (3,3).to_interval_set();
+
+    table_test_one(1, true, vec![dom1_3.clone(), dom1_3.clone(), dom1_3.clone()], Unknown, Unknown, vec![], true);
+    table_test_one(2, true, vec![(0,5).to_interval_set(), dom1_3.clone(), dom1_3.clone()], Unknown, Unknown, vec![(0, Bound)], true);
+    table_test_one(3, true, vec![dom1_3.clone(), dom1_3.clone(), dom3_3.clone()], Unknown, Unknown, vec![(0, Inner)], true);
+    table_test_one(4, true, vec![dom2_2.clone(), dom1_3.clone(), dom1_3.clone()], Unknown, True, vec![(1, Assignment), (2, Assignment)], true);
+    table_test_one(5, true, vec![dom1_3.clone(), dom3_3.clone(), dom1_3.clone()], Unknown, True, vec![(0, Assignment), (2, Assignment)], true);
+    table_test_one(6, true, vec![dom2_2.clone(), dom1_1.clone(), dom1_3.clone()], False, False, vec![], false);
+    table_test_one(7, true, vec![dom2_2.clone(), dom2_2.clone(), dom1_1.clone()], True, True, vec![], true);
+  }
+
+  #[test]
+  fn table_forbidden_test() {
+    let dom1_2 = (1,2).to_interval_set();
+    let dom1_3 = (1,3).to_interval_set();
+    let dom1_1 = (1,1).to_interval_set();
+    let dom2_2 = (2,2).to_interval_set();
+    let dom3_3 = (3,3).to_interval_set();
+
+    table_test_one(1, false, vec![dom1_3.clone(), dom1_3.clone(), dom1_3.clone()], Unknown, Unknown, vec![], true);
+    // `(1, 1, 2)` and `(1, 2, 3)` are forbidden.
+    table_test_one(2, false, vec![dom1_1.clone(), dom1_2.clone(), (2,3).to_interval_set()], Unknown, Unknown, vec![], true);
+    table_test_one(3, false, vec![dom1_1.clone(), dom1_1.clone(), dom1_3.clone()], Unknown, True, vec![(2, Inner)], true);
+    table_test_one(4, false, vec![dom3_3.clone(), dom1_3.clone(), dom3_3.clone()], Unknown, True, vec![(1, Assignment)], true);
+    table_test_one(5, false, vec![dom2_2.clone(), dom2_2.clone(), dom1_1.clone()], False, False, vec![], false);
+    table_test_one(6, false, vec![dom2_2.clone(), dom2_2.clone(), dom2_2.clone()], True, True, vec![], true);
+  }
+
+  fn assign_and_propagate(space: FDSpace, vars: &mut Vec<Var<VStore>>, x: usize, value: i32, expected: SKleene) -> FDSpace {
+    let mut space = space;
+    let dom = vars[x].read(&space.vstore).intersection(&(value, value).to_interval_set());
+    assert!(vars[x].update(&mut space.vstore, dom));
+    assert_eq!(space.consistency(), expected);
+    space
+  }
+
+  fn check_domains(space: &FDSpace, vars: &Vec<Var<VStore>>, expected: Vec<IntervalSet<i32>>) {
+    for (x, dom) in vars.iter().zip(expected) {
+      assert_eq!(x.read(&space.vstore), dom);
+    }
+  }
+
+  #[test]
+  fn table_backtracking_test() {
+    let mut space = FDSpace::empty();
+    let mut vars: Vec<Var<VStore>> = (0..3)
+      .map(|_| Box::new(space.vstore.alloc((1,3).to_interval_set())) as Var<VStore>)
+      .collect();
+    space.cstore.alloc(Box::new(Table::new(vars.iter().map(|v| v.bclone()).collect(), tuples())));
+    assert_eq!(space.consistency(), Unknown);
+
+    let mut frozen = space.freeze();
+    let left = frozen.label();
+    let right = frozen.label();
+
+    // `x[0] = 1` keeps the tuples `(1, 1, 2)` and `(1, 2, 3)`.
+    let space = assign_and_propagate(frozen.restore(left), &mut vars, 0, 1, Unknown);
+    check_domains(&space, &vars, vec![(1,1).to_interval_set(), (1,2).to_interval_set(), (2,3).to_interval_set()]);
+    let frozen = space.freeze();
+
+    // On backtracking, the removed tuples must be valid again: `x[0] = 3` keeps `(3, 1, 3)` and `(3, 3, 3)`.
+    let space = frozen.restore(right);
+    check_domains(&space, &vars, vec![(1,3).to_interval_set(), (1,3).to_interval_set(), (1,3).to_interval_set()]);
+    let space = assign_and_propagate(space, &mut vars, 0, 3, True);
+    check_domains(&space, &vars, vec![(3,3).to_interval_set(), vec![(1,1),(3,3)].to_interval_set(), (3,3).to_interval_set()]);
+  }
+}
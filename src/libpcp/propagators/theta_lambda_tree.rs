@@ -0,0 +1,304 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The Θ-Λ-tree is the data structure underlying the scheduling algorithms of Vilím (`Max Energy Filtering Algorithm for Discrete Cumulative Resources`, 2009, and `Edge Finding Filtering Algorithm for Discrete Cumulative Resources in O(kn log n)`, 2009).
+//!
+//! The leaves are the tasks sorted by earliest start dates. A task is either in the set Θ (white leaf), in the set Λ (gray leaf) or absent.
+//! Every node maintains the energy and the envelope of the tasks of its subtree, the envelope of a set of tasks Ω being `max{ C * est(Ω') + e(Ω') | Ω' ⊆ Ω }` where `Ω'` ranges over the sets of tasks starting after a given date.
+//! The gray versions of these values are computed over the sets containing the tasks in Θ and at most one task of Λ.
+//! For a unary resource, the capacity `C` is `1`, the energy is the duration of the tasks and the envelope is the earliest completion time.
+
+use concept::*;
+use std::cmp::max;
+
+#[derive(Clone, Debug)]
+struct ThetaNode<Bound>
+{
+  energy: Bound,
+  envelope: Option<Bound>,
+  gray_energy: Option<Bound>,
+  gray_envelope: Option<Bound>
+}
+
+impl<Bound> ThetaNode<Bound> where
+  Bound: IntBound
+{
+  fn empty() -> Self {
+    ThetaNode {
+      energy: Bound::zero(),
+      envelope: None,
+      gray_energy: None,
+      gray_envelope: None
+    }
+  }
+
+  fn combine(left: &Self, right: &Self) -> Self {
+    ThetaNode {
+      energy: left.energy.clone() + right.energy.clone(),
+      envelope: max_opt(
+        right.envelope.clone(),
+        plus(&left.envelope, &right.energy)),
+      gray_energy: max_opt(
+        plus(&left.gray_energy, &right.energy),
+        plus_opt(&right.gray_energy, &Some(left.energy.clone()))),
+      gray_envelope: max_opt(
+        right.gray_envelope.clone(),
+        max_opt(
+          plus_opt(&left.envelope, &right.gray_energy),
+          plus(&left.gray_envelope, &right.energy)))
+    }
+  }
+}
+
+/// `None` represents minus infinity.
+fn plus<Bound: IntBound>(a: &Option<Bound>, b: &Bound) -> Option<Bound> {
+  a.clone().map(|a| a + b.clone())
+}
+
+fn plus_opt<Bound: IntBound>(a: &Option<Bound>, b: &Option<Bound>) -> Option<Bound> {
+  match (a.clone(), b.clone()) {
+    (Some(a), Some(b)) => Some(a + b),
+    _ => None
+  }
+}
+
+fn max_opt<Bound: IntBound>(a: Option<Bound>, b: Option<Bound>) -> Option<Bound> {
+  match (a, b) {
+    (Some(a), Some(b)) => Some(max(a, b)),
+    (a, None) => a,
+    (None, b) => b
+  }
+}
+
+/// Complete binary tree stored in an array, the node `i` has the children `2i` and `2i+1`, and the leaf of the position `p` is the node `leaves + p`.
+#[derive(Clone, Debug)]
+pub struct ThetaLambdaTree<Bound>
+{
+  leaves: usize,
+  nodes: Vec<ThetaNode<Bound>>
+}
+
+impl<Bound> ThetaLambdaTree<Bound> where
+  Bound: IntBound
+{
+  /// Creates an empty tree with `n` positions.
+  pub fn new(n: usize) -> Self {
+    let leaves = n.next_power_of_two();
+    ThetaLambdaTree {
+      leaves: leaves,
+      nodes: vec![ThetaNode::empty(); 2 * leaves]
+    }
+  }
+
+  /// Inserts a task in Θ at the position `pos`, its envelope is usually `C * est + energy`.
+  pub fn insert(&mut self, pos: usize, energy: Bound, envelope: Bound) {
+    let leaf = self.leaves + pos;
+    self.nodes[leaf] = ThetaNode {
+      energy: energy.clone(),
+      envelope: Some(envelope.clone()),
+      gray_energy: None,
+      gray_envelope: None
+    };
+    self.update_path(leaf);
+  }
+
+  /// Moves the task at the position `pos` from Θ to Λ.
+  pub fn gray(&mut self, pos: usize) {
+    let leaf = self.leaves + pos;
+    let node = self.nodes[leaf].clone();
+    self.nodes[leaf] = ThetaNode {
+      energy: Bound::zero(),
+      envelope: None,
+      gray_energy: Some(node.energy),
+      gray_envelope: node.envelope
+    };
+    self.update_path(leaf);
+  }
+
+  /// Removes the task at the position `pos` from the tree.
+  pub fn remove(&mut self, pos: usize) {
+    let leaf = self.leaves + pos;
+    self.nodes[leaf] = ThetaNode::empty();
+    self.update_path(leaf);
+  }
+
+  fn update_path(&mut self, leaf: usize) {
+    let mut node = leaf / 2;
+    while node > 0 {
+      self.nodes[node] = ThetaNode::combine(&self.nodes[2 * node], &self.nodes[2 * node + 1]);
+      node /= 2;
+    }
+  }
+
+  fn root(&self) -> &ThetaNode<Bound> {
+    &self.nodes[1]
+  }
+
+  /// Energy of Θ.
+  pub fn energy(&self) -> Bound {
+    self.root().energy.clone()
+  }
+
+  /// Envelope of Θ, `None` if Θ is empty.
+  pub fn envelope(&self) -> Option<Bound> {
+    self.root().envelope.clone()
+  }
+
+  /// Maximal envelope of Θ with one task of Λ, `None` if Λ is empty.
+  pub fn gray_envelope(&self) -> Option<Bound> {
+    self.root().gray_envelope.clone()
+  }
+
+  /// Position of the task of Λ responsible for the gray envelope.
+  /// Precondition: Λ is not empty.
+  pub fn responsible_gray(&self) -> usize {
+    let mut node = 1;
+    let mut envelope = true;
+    while node < self.leaves {
+      let left = &self.nodes[2 * node];
+      let right = &self.nodes[2 * node + 1];
+      let current = &self.nodes[node];
+      if envelope {
+        if current.gray_envelope == right.gray_envelope {
+          node = 2 * node + 1;
+        }
+        else if current.gray_envelope == plus_opt(&left.envelope, &right.gray_energy) {
+          node = 2 * node + 1;
+          envelope = false;
+        }
+        else {
+          node *= 2;
+        }
+      }
+      else if current.gray_energy == plus(&left.gray_energy, &right.energy) {
+        node *= 2;
+      }
+      else {
+        node = 2 * node + 1;
+      }
+    }
+    node - self.leaves
+  }
+
+  /// Rightmost position `p` of a task in Θ such that `envelope(p) + e(Θ after p) > bound`, where `envelope(p)` is the envelope of the task at `p`.
+  pub fn rightmost_above(&self, bound: &Bound) -> Option<usize> {
+    if !above(&self.envelope(), bound) {
+      return None;
+    }
+    let mut node = 1;
+    let mut energy_after = Bound::zero();
+    while node < self.leaves {
+      let right = &self.nodes[2 * node + 1];
+      if above(&plus(&right.envelope, &energy_after), bound) {
+        node = 2 * node + 1;
+      }
+      else {
+        energy_after = energy_after + right.energy.clone();
+        node *= 2;
+      }
+    }
+    Some(node - self.leaves)
+  }
+
+  /// Envelope of Θ restricted to the tasks starting at a position smaller or equal to `pos`, the energy of all the tasks after them being included.
+  pub fn envelope_upto(&self, pos: usize) -> Option<Bound> {
+    let mut node = self.leaves + pos;
+    let mut envelope = self.nodes[node].envelope.clone();
+    let mut energy = self.nodes[node].energy.clone();
+    while node > 1 {
+      let sibling = &self.nodes[node ^ 1];
+      if node & 1 == 0 {
+        envelope = plus(&envelope, &sibling.energy);
+      }
+      else {
+        envelope = max_opt(envelope, plus(&sibling.envelope, &energy));
+      }
+      energy = energy + sibling.energy.clone();
+      node /= 2;
+    }
+    envelope
+  }
+}
+
+fn above<Bound: IntBound>(value: &Option<Bound>, bound: &Bound) -> bool {
+  match *value {
+    Some(ref v) => v > bound,
+    None => false
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  // Tasks (est, duration) of a unary resource sorted by est.
+  fn unary_tree(tasks: &[(i32, i32)]) -> ThetaLambdaTree<i32> {
+    let mut tree = ThetaLambdaTree::new(tasks.len());
+    for (i, &(est, dur)) in tasks.iter().enumerate() {
+      tree.insert(i, dur, est + dur);
+    }
+    tree
+  }
+
+  #[test]
+  fn earliest_completion_time_test() {
+    let mut tree = unary_tree(&[(0, 5), (2, 1), (4, 3), (25, 2)]);
+    assert_eq!(tree.envelope(), Some(27));
+    assert_eq!(tree.energy(), 11);
+    tree.remove(3);
+    assert_eq!(tree.envelope(), Some(9));
+    tree.remove(0);
+    assert_eq!(tree.envelope(), Some(7));
+    assert_eq!(tree.gray_envelope(), None);
+  }
+
+  #[test]
+  fn gray_test() {
+    let mut tree = unary_tree(&[(0, 5), (2, 1), (4, 3), (25, 2)]);
+    tree.remove(3);
+    tree.gray(2);
+    assert_eq!(tree.envelope(), Some(6));
+    assert_eq!(tree.gray_envelope(), Some(9));
+    assert_eq!(tree.responsible_gray(), 2);
+    tree.gray(0);
+    assert_eq!(tree.envelope(), Some(3));
+    assert_eq!(tree.gray_envelope(), Some(7));
+    assert_eq!(tree.responsible_gray(), 2);
+    tree.remove(2);
+    assert_eq!(tree.gray_envelope(), Some(6));
+    assert_eq!(tree.responsible_gray(), 0);
+  }
+
+  #[test]
+  fn envelope_upto_test() {
+    let tree = unary_tree(&[(0, 5), (2, 1), (4, 3), (25, 2)]);
+    assert_eq!(tree.envelope_upto(0), Some(11));
+    assert_eq!(tree.envelope_upto(2), Some(11));
+    assert_eq!(tree.envelope_upto(3), Some(27));
+    assert_eq!(tree.rightmost_above(&26), Some(3));
+    assert_eq!(tree.rightmost_above(&10), Some(3));
+    assert_eq!(tree.rightmost_above(&27), None);
+  }
+
+  #[test]
+  fn single_leaf_test() {
+    let mut tree = unary_tree(&[(3, 2)]);
+    assert_eq!(tree.envelope(), Some(5));
+    assert_eq!(tree.rightmost_above(&4), Some(0));
+    assert_eq!(tree.envelope_upto(0), Some(5));
+    tree.gray(0);
+    assert_eq!(tree.responsible_gray(), 0);
+    assert_eq!(tree.gray_envelope(), Some(5));
+  }
+}
@@ -0,0 +1,181 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use term::ops::*;
+use propagation::events::*;
+use model::*;
+use concept::*;
+use gcollections::kind::*;
+
+/// The view `max(x1,...,xN)`, its domain is approximated by its bounds.
+#[derive(Debug)]
+pub struct Maximum<VStore>
+{
+  vars: Vec<Var<VStore>>
+}
+
+impl<VStore> Maximum<VStore> {
+  pub fn new(vars: Vec<Var<VStore>>) -> Self {
+    Maximum {
+      vars: vars
+    }
+  }
+}
+
+impl<VStore> Clone for Maximum<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Maximum::new(self.vars.iter().map(|v| v.bclone()).collect())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Maximum<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("max(");
+    for (i, v) in self.vars.iter().enumerate() {
+      if i > 0 {
+        print!(", ");
+      }
+      v.display(model);
+    }
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> StoreMonotonicUpdate<VStore> for Maximum<VStore> where
+ VStore: VStoreConcept<Item=Domain>,
+ Domain: IntDomain<Item=Bound>,
+ Bound: IntBound
+{
+  /// Every variable is lower or equal to `value.upper()`, and if only one variable can reach `value.lower()`, it is greater or equal to it.
+  fn update(&mut self, store: &mut VStore, value: Domain) -> bool {
+    if self.vars.len() == 1 {
+      return self.vars[0].update(store, value);
+    }
+    if value.is_empty() {
+      return false;
+    }
+    let mut reaching = vec![];
+    for (i, v) in self.vars.iter_mut().enumerate() {
+      let dom = v.read(store).shrink_right(value.upper());
+      if !dom.is_empty() && dom.upper() >= value.lower() {
+        reaching.push(i);
+      }
+      if !v.update(store, dom) {
+        return false;
+      }
+    }
+    match reaching.len() {
+      0 => false,
+      1 => {
+        let v = &mut self.vars[reaching[0]];
+        let dom = v.read(store).shrink_left(value.lower());
+        v.update(store, dom)
+      }
+      _ => true
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> StoreRead<VStore> for Maximum<VStore> where
+ VStore: VStoreConcept<Item=Domain>,
+ Domain: IntDomain<Item=Bound>,
+ Bound: IntBound
+{
+  fn read(&self, store: &VStore) -> Domain {
+    let mut iter = self.vars.iter().map(|v| v.read(store));
+    let first = iter.next().expect("At least one variable in maximum.");
+    if self.vars.len() == 1 {
+      return first;
+    }
+    let (lb, ub) = iter.fold((first.lower(), first.upper()), |(lb, ub), d| {
+      (::std::cmp::max(lb, d.lower()), ::std::cmp::max(ub, d.upper()))
+    });
+    Domain::new(lb, ub)
+  }
+}
+
+impl<VStore> ViewDependencies<FDEvent> for Maximum<VStore>
+{
+  fn dependencies(&self, event: FDEvent) -> Vec<(usize, FDEvent)> {
+    self.vars.iter()
+      .flat_map(|v| v.dependencies(event))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use trilean::SKleene;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent;
+  use propagation::events::FDEvent::*;
+  use propagators::test::*;
+  use propagators::cmp::{XLessY, x_eq_max};
+  use term::addition::Addition;
+  use variable::VStoreFD;
+  use gcollections::ops::*;
+  use interval::interval::*;
+
+  type VStore = VStoreFD;
+
+  #[test]
+  fn x_eq_max_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom0_3 = (0,3).to_interval();
+    let dom2_5 = (2,5).to_interval();
+    let dom4_8 = (4,8).to_interval();
+    let dom6_9 = (6,9).to_interval();
+    let dom1_1 = (1,1).to_interval();
+    let dom5_5 = (5,5).to_interval();
+
+    x_eq_max_test_one(1, vec![dom0_10, dom0_3, dom2_5], Unknown, Unknown, vec![(0, Bound)], true);
+    // Only `x2` can reach `4`.
+    x_eq_max_test_one(2, vec![dom4_8, dom0_3, dom2_5], Unknown, Unknown, vec![(0, Bound), (2, Bound)], true);
+    x_eq_max_test_one(3, vec![dom1_1, dom0_3, dom2_5], False, False, vec![], false);
+    x_eq_max_test_one(4, vec![dom5_5, dom0_3, dom0_10], Unknown, True, vec![(2, Assignment)], true);
+    x_eq_max_test_one(5, vec![dom0_10, dom6_9, dom0_3], Unknown, Unknown, vec![(0, Bound)], true);
+    x_eq_max_test_one(6, vec![dom5_5, dom1_1, dom5_5], True, True, vec![], true);
+    x_eq_max_test_one(7, vec![dom2_5, dom0_10], Unknown, Unknown, vec![(1, Bound)], true);
+  }
+
+  /// The first variable is `x`.
+  fn x_eq_max_test_one(test_num: u32, doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_propagator_test(test_num, |mut ys| {
+        let x = ys.remove(0);
+        x_eq_max(x, ys)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+
+  #[test]
+  fn makespan_test() {
+    // `max(s1 + 3, s2 + 5) < 10`.
+    let mut store = VStore::empty();
+    let s1 = Box::new(store.alloc((0,10).to_interval())) as Var<VStore>;
+    let s2 = Box::new(store.alloc((0,10).to_interval())) as Var<VStore>;
+    let makespan = Maximum::new(vec![
+      Box::new(Addition::new(s1, 3)) as Var<VStore>,
+      Box::new(Addition::new(s2, 5)) as Var<VStore>]);
+    let ten = Box::new(store.alloc((10,10).to_interval())) as Var<VStore>;
+    let makespan_lt_10 = XLessY::new(Box::new(makespan), ten);
+    test_propagation(1, makespan_lt_10, &mut store, Unknown, True, vec![(0, Bound), (1, Bound)], true);
+  }
+}
@@ -0,0 +1,159 @@
+// Copyright 2018 Pierre Talbot (IRCAM)
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//     http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use kernel::*;
+use term::ops::*;
+use propagation::events::*;
+use model::*;
+use concept::*;
+use gcollections::kind::*;
+
+/// The view `min(x1,...,xN)`, its domain is approximated by its bounds.
+#[derive(Debug)]
+pub struct Minimum<VStore>
+{
+  vars: Vec<Var<VStore>>
+}
+
+impl<VStore> Minimum<VStore> {
+  pub fn new(vars: Vec<Var<VStore>>) -> Self {
+    Minimum {
+      vars: vars
+    }
+  }
+}
+
+impl<VStore> Clone for Minimum<VStore> where
+ VStore: Collection
+{
+  fn clone(&self) -> Self {
+    Minimum::new(self.vars.iter().map(|v| v.bclone()).collect())
+  }
+}
+
+impl<VStore> DisplayStateful<Model> for Minimum<VStore>
+{
+  fn display(&self, model: &Model) {
+    print!("min(");
+    for (i, v) in self.vars.iter().enumerate() {
+      if i > 0 {
+        print!(", ");
+      }
+      v.display(model);
+    }
+    print!(")");
+  }
+}
+
+impl<VStore, Domain, Bound> StoreMonotonicUpdate<VStore> for Minimum<VStore> where
+ VStore: VStoreConcept<Item=Domain>,
+ Domain: IntDomain<Item=Bound>,
+ Bound: IntBound
+{
+  /// Every variable is greater or equal to `value.lower()`, and if only one variable can reach `value.upper()`, it is lower or equal to it.
+  fn update(&mut self, store: &mut VStore, value: Domain) -> bool {
+    if self.vars.len() == 1 {
+      return self.vars[0].update(store, value);
+    }
+    if value.is_empty() {
+      return false;
+    }
+    let mut reaching = vec![];
+    for (i, v) in self.vars.iter_mut().enumerate() {
+      let dom = v.read(store).shrink_left(value.lower());
+      if !dom.is_empty() && dom.lower() <= value.upper() {
+        reaching.push(i);
+      }
+      if !v.update(store, dom) {
+        return false;
+      }
+    }
+    match reaching.len() {
+      0 => false,
+      1 => {
+        let v = &mut self.vars[reaching[0]];
+        let dom = v.read(store).shrink_right(value.upper());
+        v.update(store, dom)
+      }
+      _ => true
+    }
+  }
+}
+
+impl<VStore, Domain, Bound> StoreRead<VStore> for Minimum<VStore> where
+ VStore: VStoreConcept<Item=Domain>,
+ Domain: IntDomain<Item=Bound>,
+ Bound: IntBound
+{
+  fn read(&self, store: &VStore) -> Domain {
+    let mut iter = self.vars.iter().map(|v| v.read(store));
+    let first = iter.next().expect("At least one variable in minimum.");
+    if self.vars.len() == 1 {
+      return first;
+    }
+    let (lb, ub) = iter.fold((first.lower(), first.upper()), |(lb, ub), d| {
+      (::std::cmp::min(lb, d.lower()), ::std::cmp::min(ub, d.upper()))
+    });
+    Domain::new(lb, ub)
+  }
+}
+
+impl<VStore> ViewDependencies<FDEvent> for Minimum<VStore>
+{
+  fn dependencies(&self, event: FDEvent) -> Vec<(usize, FDEvent)> {
+    self.vars.iter()
+      .flat_map(|v| v.dependencies(event))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use trilean::SKleene;
+  use trilean::SKleene::*;
+  use propagation::events::FDEvent;
+  use propagation::events::FDEvent::*;
+  use propagators::test::*;
+  use propagators::cmp::x_eq_min;
+  use interval::interval::*;
+
+  #[test]
+  fn x_eq_min_test() {
+    let dom0_10 = (0,10).to_interval();
+    let dom5_8 = (5,8).to_interval();
+    let dom6_8 = (6,8).to_interval();
+    let dom3_6 = (3,6).to_interval();
+    let dom0_4 = (0,4).to_interval();
+    let dom9_9 = (9,9).to_interval();
+    let dom5_5 = (5,5).to_interval();
+
+    x_eq_min_test_one(1, vec![dom0_10, dom5_8, dom3_6], Unknown, Unknown, vec![(0, Bound)], true);
+    // Only `x2` can reach `4`.
+    x_eq_min_test_one(2, vec![dom0_4, dom5_8, dom3_6], Unknown, Unknown, vec![(0, Bound), (2, Bound)], true);
+    x_eq_min_test_one(3, vec![dom9_9, dom5_8, dom3_6], False, False, vec![], false);
+    x_eq_min_test_one(4, vec![dom5_5, dom6_8, dom0_10], Unknown, True, vec![(2, Assignment)], true);
+    x_eq_min_test_one(5, vec![dom5_5, dom9_9, dom5_5], True, True, vec![], true);
+  }
+
+  /// The first variable is `x`.
+  fn x_eq_min_test_one(test_num: u32, doms: Vec<Interval<i32>>,
+    before: SKleene, after: SKleene, delta_expected: Vec<(usize, FDEvent)>, propagate_success: bool)
+  {
+    nary_propagator_test(test_num, |mut ys| {
+        let x = ys.remove(0);
+        x_eq_min(x, ys)
+      },
+      doms, before, after, delta_expected, propagate_success);
+  }
+}
@@ -17,8 +17,12 @@ pub mod constant;
 pub mod addition;
 pub mod ops;
 pub mod sum;
+pub mod maximum;
+pub mod minimum;
 
 pub use term::identity::Identity;
 pub use term::constant::Constant;
 pub use term::addition::Addition;
 pub use term::sum::Sum;
+pub use term::maximum::Maximum;
+pub use term::minimum::Minimum;
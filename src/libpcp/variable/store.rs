@@ -280,7 +280,9 @@ pub mod test {
   pub type Domain = Interval<i32>;
   pub type VStore = VStoreFD;
 
-  pub fn consume_delta(store: &mut VStore, delta_expected: Vec<(usize, FDEvent)>) {
+  pub fn consume_delta<S>(store: &mut S, delta_expected: Vec<(usize, FDEvent)>) where
+   S: DrainDelta<FDEvent>
+  {
     let res: Vec<(usize, FDEvent)> = store.drain_delta().collect();
     assert_eq!(res, delta_expected);
     assert!(store.drain_delta().next().is_none());